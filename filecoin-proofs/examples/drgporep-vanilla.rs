@@ -102,7 +102,7 @@ fn do_the_work<H: Hasher>(data_size: usize, m: usize, sloth_iter: usize, challen
         tau: Some(tau),
     };
 
-    let priv_inputs = PrivateInputs::<H> { aux: &aux };
+    let priv_inputs = PrivateInputs::<H>::new(&aux);
 
     param_duration += start.elapsed();
     let samples: u32 = 30;
@@ -14,6 +14,7 @@ use sapling_crypto::circuit::multipack;
 use sapling_crypto::jubjub::JubjubBls12;
 
 use storage_proofs::circuit;
+use storage_proofs::circuit::por::binary_auth_path;
 use storage_proofs::example_helper::Example;
 use storage_proofs::test_helper::random_merkle_path;
 
@@ -58,7 +59,13 @@ impl<'a> Example<'a, circuit::ppor::ParallelProofOfRetrievability<'a, Bls12>> fo
         circuit::ppor::ParallelProofOfRetrievability {
             params: engine_params,
             values,
-            auth_paths: self.auth_paths.clone(),
+            auth_paths: self
+                .auth_paths
+                .iter()
+                .cloned()
+                .map(binary_auth_path)
+                .collect(),
+            arity: 2,
             root: Some(self.root),
         }
     }
@@ -77,6 +84,7 @@ impl<'a> Example<'a, circuit::ppor::ParallelProofOfRetrievability<'a, Bls12>> fo
                 params: jubjub_params,
                 values: vec![None; challenge_count],
                 auth_paths: vec![vec![None; tree_depth]; challenge_count],
+                arity: 2,
                 root: None,
             },
             rng,
@@ -110,7 +118,13 @@ impl<'a> Example<'a, circuit::ppor::ParallelProofOfRetrievability<'a, Bls12>> fo
             let c = circuit::ppor::ParallelProofOfRetrievability {
                 params: engine_params,
                 values,
-                auth_paths: self.auth_paths.clone(),
+                auth_paths: self
+                    .auth_paths
+                    .iter()
+                    .cloned()
+                    .map(binary_auth_path)
+                    .collect(),
+                arity: 2,
                 root: Some(self.root),
             };
 
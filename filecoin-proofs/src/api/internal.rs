@@ -6,12 +6,14 @@ use std::path::PathBuf;
 use std::sync::Mutex;
 
 use bellman::groth16;
+use memmap::MmapOptions;
 use pairing::bls12_381::{Bls12, Fr};
 use pairing::{Engine, PrimeField};
 use sapling_crypto::jubjub::JubjubBls12;
 
 use sector_base::api::bytes_amount::{PaddedBytesAmount, UnpaddedBytesAmount};
-use sector_base::api::sector_store::SectorConfig;
+use sector_base::api::sector_access::SectorAccess;
+use sector_base::api::sector_store::{SectorConfig, SectorManager};
 use sector_base::io::fr32::write_unpadded;
 use std::path::Path;
 use storage_proofs::circuit::multi_proof::MultiProof;
@@ -19,12 +21,12 @@ use storage_proofs::circuit::vdf_post::{VDFPoStCircuit, VDFPostCompound};
 use storage_proofs::circuit::zigzag::ZigZagCompound;
 use storage_proofs::compound_proof::{self, CompoundProof};
 use storage_proofs::drgporep::{self, DrgParams};
-use storage_proofs::drgraph::{DefaultTreeHasher, Graph};
+use storage_proofs::drgraph::{DefaultTreeHasher, Graph, PARALLEL_MERKLE};
 use storage_proofs::fr32::{bytes_into_fr, fr_into_bytes, Fr32Ary};
 use storage_proofs::hasher::pedersen::{PedersenDomain, PedersenHasher};
 use storage_proofs::hasher::{Domain, Hasher};
-use storage_proofs::layered_drgporep::{self, LayerChallenges};
-use storage_proofs::merkle::MerkleTree;
+use storage_proofs::layered_drgporep::{self, LayerChallenges, Layers};
+use storage_proofs::merkle::{MerkleTree, TreeStorage, DEFAULT_ARITY};
 use storage_proofs::porep::{replica_id, PoRep, Tau};
 use storage_proofs::proof::ProofScheme;
 use storage_proofs::vdf_post::{self, VDFPoSt};
@@ -33,6 +35,7 @@ use storage_proofs::zigzag_drgporep::ZigZagDrgPoRep;
 use storage_proofs::zigzag_graph::ZigZagBucketGraph;
 
 use crate::error;
+use crate::metrics::METRICS;
 use crate::FCP_LOG;
 
 type Commitment = Fr32Ary;
@@ -56,6 +59,11 @@ const POST_PROOF_BYTES: usize = SNARK_BYTES * POST_PARTITIONS;
 
 type SnarkProof = [u8; POREP_PROOF_BYTES];
 
+/// How many of the lowest levels of a replica's merkle tree are left out of
+/// the file persisted alongside it, to be recomputed from the replica when
+/// proving. The file is then about a sixteenth of the size of the replica.
+const OMITTED_TREE_LEVELS: usize = 5;
+
 lazy_static! {
     pub static ref ENGINE_PARAMS: JubjubBls12 = JubjubBls12::new();
 }
@@ -80,9 +88,13 @@ fn lookup_groth_params<F: FnOnce() -> error::Result<Bls12GrothParams>>(
     let cache = &mut (*GROTH_PARAM_MEMORY_CACHE).lock().unwrap();
     info!(FCP_LOG, "trying groth parameters memory cache for: {}", &identifier; "target" => "params");
     let params = match cache.entry(identifier) {
-        Entry::Vacant(entry) => entry.insert(generator()?).clone(),
+        Entry::Vacant(entry) => {
+            METRICS.record_parameter_cache_lookup(false);
+            entry.insert(generator()?).clone()
+        }
         Entry::Occupied(entry) => {
             info!(FCP_LOG, "found params in memory cache"; "target" => "params");
+            METRICS.record_parameter_cache_lookup(true);
             entry.get().clone()
         }
     };
@@ -99,9 +111,13 @@ fn lookup_verifying_key<F: FnOnce() -> error::Result<Bls12VerifyingKey>>(
 
     info!(FCP_LOG, "trying verifying key memory cache for: {}", &vk_identifier; "target" => "verifying_key");
     let verifying_key = match cache.entry(vk_identifier) {
-        Entry::Vacant(entry) => entry.insert(generator()?).clone(),
+        Entry::Vacant(entry) => {
+            METRICS.record_parameter_cache_lookup(false);
+            entry.insert(generator()?).clone()
+        }
         Entry::Occupied(entry) => {
             info!(FCP_LOG, "found verifying_key in memory cache"; "target" => "verifying_key");
+            METRICS.record_parameter_cache_lookup(true);
             entry.get().clone()
         }
     };
@@ -264,7 +280,7 @@ pub struct PoStOutput {
 }
 
 pub struct PoStInputPart {
-    pub sealed_sector_access: Option<String>,
+    pub sealed_sector_access: Option<SectorAccess>,
     pub comm_r: [u8; 32],
 }
 
@@ -289,9 +305,12 @@ pub fn fake_generate_post(
     })
 }
 
+// Generates a proof-of-spacetime over the sealed sectors of the input, which
+// are held by the provided manager.
 pub fn generate_post(
     sector_bytes: PaddedBytesAmount,
     input: PoStInput,
+    manager: &SectorManager,
 ) -> error::Result<PoStOutput> {
     let faults: Vec<u64> = Vec::new();
 
@@ -325,21 +344,24 @@ pub fn generate_post(
         faults: Vec::new(),
     };
 
+    // Errors are returned rather than unwrapped, since a panic here would
+    // take down the PoSt worker which called us.
     let trees: Vec<Tree> = input
         .input_parts
         .iter()
-        .map(|p| {
-            if let Some(s) = &p.sealed_sector_access {
-                make_merkle_tree(
-                    s,
-                    PaddedBytesAmount(pub_params.vanilla_params.sector_size as u64),
-                )
-                .unwrap()
-            } else {
-                panic!("faults are not yet supported")
-            }
+        .map(|p| match &p.sealed_sector_access {
+            Some(access) => open_replica_tree(
+                manager,
+                access,
+                &p.comm_r,
+                PaddedBytesAmount(pub_params.vanilla_params.sector_size as u64),
+            ),
+            None => Err(format_err!(
+                "faults are not yet supported: no sealed sector access for comm_r {:?}",
+                p.comm_r
+            )),
         })
-        .collect();
+        .collect::<error::Result<_>>()?;
 
     let borrowed_trees: Vec<&Tree> = trees.iter().map(|t| t).collect();
 
@@ -419,13 +441,91 @@ fn make_merkle_tree<T: Into<PathBuf> + AsRef<Path>>(
     sealed_path: T,
     bytes: PaddedBytesAmount,
 ) -> storage_proofs::error::Result<Tree> {
-    let mut f_in = File::open(sealed_path.into())?;
-    let mut data = Vec::new();
-    f_in.read_to_end(&mut data)?;
+    let f_in = File::open(sealed_path.as_ref())?;
+
+    // The replica, which is as large as the sector, is mapped rather than read
+    // into memory. It may not be modified while it is mapped.
+    let data = unsafe { MmapOptions::new().map(&f_in)? };
 
     let g = public_params(bytes).drg_porep_public_params.graph;
 
-    g.merkle_tree(&data)
+    // The trees of every sector proven are held at once.
+    g.merkle_tree_aux(&data, 32, PARALLEL_MERKLE, tree_storage(&sealed_path))
+}
+
+// Memory-maps the merkle trees of the replica at sealed_path into temporary
+// files on the replica's own disk, which has space reserved for them, rather
+// than into the system temporary directory.
+fn tree_storage<T: AsRef<Path>>(sealed_path: T) -> TreeStorage {
+    match sealed_path.as_ref().parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => TreeStorage::Mmap(dir.to_path_buf()),
+        _ => TreeStorage::Mmap(PathBuf::from(".")),
+    }
+}
+
+// Opens the merkle tree of the sealed sector identified by access from the
+// upper levels persisted alongside it when it was sealed, which must commit to
+// comm_r. The tree is rebuilt from the replica if they are missing (e.g. the
+// replica was imported) or do not commit to comm_r, and persisted again if the
+// rebuilt tree does, so that it need not be rebuilt for the next proof.
+fn open_replica_tree(
+    manager: &SectorManager,
+    access: &SectorAccess,
+    comm_r: &Commitment,
+    bytes: PaddedBytesAmount,
+) -> error::Result<Tree> {
+    let sealed_path = manager.local_path(access)?;
+    let comm_r = PedersenDomain::try_from_bytes(comm_r)?;
+
+    match manager.sector_tree_path(access) {
+        Ok(Some(tree_path)) => match Tree::open_partial(
+            &sealed_path,
+            tree_path,
+            DEFAULT_ARITY,
+            OMITTED_TREE_LEVELS,
+            &comm_r,
+        ) {
+            Ok(tree) => return Ok(tree),
+            Err(err) => {
+                warn!(FCP_LOG, "persisted merkle tree does not commit to comm_r ({}), rebuilding it", err; "sector_access" => format!("{}", access));
+            }
+        },
+        Ok(None) => {
+            info!(FCP_LOG, "no persisted merkle tree, rebuilding it"; "sector_access" => format!("{}", access));
+        }
+        Err(err) => {
+            info!(FCP_LOG, "could not find persisted merkle tree ({}), rebuilding it", err; "sector_access" => format!("{}", access));
+        }
+    }
+
+    let tree = make_merkle_tree(&sealed_path, bytes)?;
+
+    if tree.root() == comm_r {
+        let mut upper_levels = Vec::new();
+
+        let persisted = tree
+            .write_upper_levels_to(&mut upper_levels, OMITTED_TREE_LEVELS)
+            .map_err(failure::Error::from)
+            .and_then(|_| Ok(manager.write_sector_tree(access, &upper_levels)?));
+
+        if let Err(err) = persisted {
+            warn!(FCP_LOG, "could not persist rebuilt merkle tree ({})", err; "sector_access" => format!("{}", access));
+        }
+    }
+
+    Ok(tree)
+}
+
+// Rebuilds the merkle tree of the replica at sealed_path, ignoring any tree
+// persisted alongside it, and returns its root, which should equal the comm_r
+// produced when the replica was sealed.
+pub fn compute_comm_r<T: Into<PathBuf> + AsRef<Path>>(
+    sector_config: &SectorConfig,
+    sealed_path: T,
+) -> error::Result<Commitment> {
+    let tree = make_merkle_tree(sealed_path, sector_config.sector_bytes())?;
+
+    Ok(commitment_from_fr::<Bls12>(tree.root().into()))
 }
 
 pub struct SealOutput {
@@ -435,10 +535,14 @@ pub struct SealOutput {
     pub snark_proof: SnarkProof,
 }
 
+// Seals the staged sector at in_path into a replica at out_path and writes the
+// upper levels of the replica's merkle tree to tree_out, so that they can be
+// persisted alongside the replica.
 pub fn seal<T: Into<PathBuf> + AsRef<Path>>(
     sector_config: &SectorConfig,
     in_path: T,
     out_path: T,
+    tree_out: &mut Write,
     prover_id_in: &FrSafe,
     sector_id_in: &FrSafe,
 ) -> error::Result<SealOutput> {
@@ -469,14 +573,20 @@ pub fn seal<T: Into<PathBuf> + AsRef<Path>>(
 
     let compound_public_params = ZigZagCompound::setup(&compound_setup_params)?;
 
-    let (tau, aux) = ZigZagDrgPoRep::replicate(
+    let (tau, aux) = ZigZagDrgPoRep::replicate_with_storage(
         &compound_public_params.vanilla_params,
         &replica_id,
         &mut data,
-        None,
+        &tree_storage(&out_path),
     )?;
 
-    write_data(out_path, &data)?;
+    write_data(&out_path, &data)?;
+
+    // Proofs of spacetime are generated from the final replica's tree: keep
+    // it, rather than rebuilding it from the replica for every proof.
+    aux[aux.len() - 1].write_upper_levels_to(tree_out, OMITTED_TREE_LEVELS)?;
+
+    METRICS.add_bytes_replicated(data.len() as u64);
 
     let public_tau = tau.simplify();
 
@@ -647,10 +757,12 @@ mod tests {
     struct Harness {
         prover_id: FrSafe,
         seal_output: SealOutput,
-        sealed_access: String,
+        sealed_access: SectorAccess,
+        sealed_path: PathBuf,
         sector_id: FrSafe,
         store: Box<SectorStore>,
-        unseal_access: String,
+        unseal_access: SectorAccess,
+        unseal_path: PathBuf,
         written_contents: Vec<Vec<u8>>,
     }
 
@@ -679,6 +791,10 @@ mod tests {
             .new_sealed_sector_access()
             .expect("could not create unseal access");
 
+        let staged_path = mgr.local_path(&staged_access).unwrap();
+        let sealed_path = mgr.local_path(&sealed_access).unwrap();
+        let unseal_path = mgr.local_path(&unseal_access).unwrap();
+
         let prover_id = [2; 31];
         let sector_id = [0; 31];
 
@@ -701,8 +817,20 @@ mod tests {
             written_contents.push(contents);
         }
 
-        let seal_output = seal(cfg, &staged_access, &sealed_access, &prover_id, &sector_id)
-            .expect("failed to seal");
+        let mut tree_bytes = Vec::new();
+
+        let seal_output = seal(
+            cfg,
+            &staged_path,
+            &sealed_path,
+            &mut tree_bytes,
+            &prover_id,
+            &sector_id,
+        )
+        .expect("failed to seal");
+
+        mgr.write_sector_tree(&sealed_access, &tree_bytes)
+            .expect("failed to persist tree");
 
         let SealOutput {
             comm_r,
@@ -737,8 +865,8 @@ mod tests {
             u64::from(
                 get_unsealed_range(
                     cfg,
-                    &sealed_access,
-                    &unseal_access,
+                    &sealed_path,
+                    &unseal_path,
                     &prover_id,
                     &sector_id,
                     0,
@@ -752,9 +880,11 @@ mod tests {
             prover_id,
             seal_output,
             sealed_access,
+            sealed_path,
             sector_id,
             store,
             unseal_access,
+            unseal_path,
             written_contents,
         }
     }
@@ -820,11 +950,12 @@ mod tests {
                         comm_r,
                     },
                     PoStInputPart {
-                        sealed_sector_access: Some(h.sealed_access),
+                        sealed_sector_access: Some(h.sealed_access.clone()),
                         comm_r,
                     },
                 ],
             },
+            h.store.manager(),
         )
         .expect("PoSt generation failed");
 
@@ -843,7 +974,7 @@ mod tests {
     fn seal_unsealed_roundtrip_aux(cs: ConfiguredStore, bytes_amt: BytesAmount) {
         let h = create_harness(&cs, &vec![bytes_amt]);
 
-        let mut file = File::open(&h.unseal_access).unwrap();
+        let mut file = File::open(&h.unseal_path).unwrap();
         let mut buf = Vec::new();
         file.read_to_end(&mut buf).unwrap();
 
@@ -920,8 +1051,8 @@ mod tests {
             u64::from(
                 get_unsealed_range(
                     h.store.config(),
-                    &PathBuf::from(&h.sealed_access),
-                    &PathBuf::from(&h.unseal_access),
+                    &h.sealed_path,
+                    &h.unseal_path,
                     &h.prover_id,
                     &h.sector_id,
                     offset,
@@ -931,7 +1062,7 @@ mod tests {
             )
         );
 
-        let mut file = File::open(&h.unseal_access).unwrap();
+        let mut file = File::open(&h.unseal_path).unwrap();
         let mut buf = Vec::new();
         file.read_to_end(&mut buf).unwrap();
 
@@ -969,10 +1100,12 @@ mod tests {
             .new_sealed_sector_access()
             .expect("could not create unseal access");
 
+        let unseal_path = h.store.manager().local_path(&unseal_access).unwrap();
+
         let _ = get_unsealed_range(
             h.store.config(),
-            &h.sealed_access,
-            &unseal_access,
+            &h.sealed_path,
+            &unseal_path,
             &h.prover_id,
             &h.sector_id,
             0,
@@ -980,7 +1113,7 @@ mod tests {
         )
         .expect("failed to unseal");
 
-        let mut file = File::open(&unseal_access).unwrap();
+        let mut file = File::open(&unseal_path).unwrap();
         let mut buf_from_file = Vec::new();
         file.read_to_end(&mut buf_from_file).unwrap();
 
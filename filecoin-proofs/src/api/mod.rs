@@ -3,17 +3,30 @@ use crate::api::responses::err_code_and_msg;
 use crate::api::responses::FCPResponseStatus;
 use crate::api::responses::FFIPieceMetadata;
 use crate::api::responses::FFISealStatus;
+use crate::api::responses::FFISealedSectorHealth;
+use crate::api::responses::FFISectorBuilderConfig;
+use crate::api::responses::FFIShutdownMode;
 use crate::api::sector_builder::metadata::SealStatus;
+use crate::api::sector_builder::metadata::SealedSectorHealth;
+use crate::api::sector_builder::retry::{SealErrorClass, SealRetryPolicy};
+use crate::api::sector_builder::scrubber::ScrubPolicy;
+use crate::api::sector_builder::unseal_cache::UnsealCacheConfig;
 use crate::api::sector_builder::SectorBuilder;
+use crate::api::sector_builder::SectorBuilderConfig;
+use crate::api::sector_builder::ShutdownMode;
+use crate::api::sector_builder::{PieceFilter, PieceSealStatus};
 use ffi_toolkit::rust_str_to_c_str;
 use ffi_toolkit::{c_str_to_rust_str, raw_ptr};
 use libc;
+use sector_base::api::bytes_amount::UnpaddedBytesAmount;
 use sector_base::api::disk_backed_storage::new_sector_config;
 use sector_base::api::disk_backed_storage::ConfiguredStore;
 use std::ffi::CString;
 use std::mem;
+use std::path::PathBuf;
 use std::ptr;
 use std::slice::from_raw_parts;
+use std::time::Duration;
 
 pub mod internal;
 pub mod responses;
@@ -200,7 +213,16 @@ pub unsafe extern "C" fn verify_post(
     // Box::into_raw(Box::new(response))
 }
 
-/// Initializes and returns a SectorBuilder.
+/// Returns the default settings of a SectorBuilder, which callers of
+/// init_sector_builder_with_config may override.
+///
+#[no_mangle]
+pub extern "C" fn get_default_sector_builder_config() -> FFISectorBuilderConfig {
+    into_ffi_sector_builder_config(&SectorBuilderConfig::default())
+}
+
+/// Initializes and returns a SectorBuilder, configured with the settings
+/// returned by get_default_sector_builder_config.
 ///
 #[no_mangle]
 pub unsafe extern "C" fn init_sector_builder(
@@ -211,6 +233,62 @@ pub unsafe extern "C" fn init_sector_builder(
     sealed_sector_dir: *const libc::c_char,
     staged_sector_dir: *const libc::c_char,
     max_num_staged_sectors: u8,
+) -> *mut responses::InitSectorBuilderResponse {
+    init_sector_builder_aux(
+        sector_store_config_ptr,
+        last_used_sector_id,
+        metadata_dir,
+        prover_id,
+        sealed_sector_dir,
+        staged_sector_dir,
+        max_num_staged_sectors,
+        SectorBuilderConfig::default(),
+    )
+}
+
+/// Initializes and returns a SectorBuilder, configured with the provided
+/// settings. If sector_builder_config_ptr is null, it behaves as
+/// init_sector_builder.
+///
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn init_sector_builder_with_config(
+    sector_store_config_ptr: *const ConfiguredStore,
+    last_used_sector_id: u64,
+    metadata_dir: *const libc::c_char,
+    prover_id: &[u8; 31],
+    sealed_sector_dir: *const libc::c_char,
+    staged_sector_dir: *const libc::c_char,
+    max_num_staged_sectors: u8,
+    sector_builder_config_ptr: *const FFISectorBuilderConfig,
+) -> *mut responses::InitSectorBuilderResponse {
+    let sector_builder_config = match sector_builder_config_ptr.as_ref() {
+        Some(config) => from_ffi_sector_builder_config(config),
+        None => Default::default(),
+    };
+
+    init_sector_builder_aux(
+        sector_store_config_ptr,
+        last_used_sector_id,
+        metadata_dir,
+        prover_id,
+        sealed_sector_dir,
+        staged_sector_dir,
+        max_num_staged_sectors,
+        sector_builder_config,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn init_sector_builder_aux(
+    sector_store_config_ptr: *const ConfiguredStore,
+    last_used_sector_id: u64,
+    metadata_dir: *const libc::c_char,
+    prover_id: &[u8; 31],
+    sealed_sector_dir: *const libc::c_char,
+    staged_sector_dir: *const libc::c_char,
+    max_num_staged_sectors: u8,
+    sector_builder_config: SectorBuilderConfig,
 ) -> *mut responses::InitSectorBuilderResponse {
     let mut response: responses::InitSectorBuilderResponse = Default::default();
 
@@ -223,6 +301,7 @@ pub unsafe extern "C" fn init_sector_builder(
             c_str_to_rust_str(sealed_sector_dir).to_string(),
             c_str_to_rust_str(staged_sector_dir).to_string(),
             max_num_staged_sectors,
+            sector_builder_config,
         ) {
             Ok(sb) => {
                 response.status_code = FCPResponseStatus::FCPNoError;
@@ -245,6 +324,34 @@ pub unsafe extern "C" fn init_sector_builder(
     raw_ptr(response)
 }
 
+/// Returns a new SectorBuilder which manages the sectors of another prover.
+/// The returned SectorBuilder shares seal workers and sector storage with the
+/// provided one, but its staged and sealed state is kept separately. Both
+/// must be destroyed with destroy_sector_builder.
+///
+#[no_mangle]
+pub unsafe extern "C" fn add_prover_to_sector_builder(
+    ptr: *mut SectorBuilder,
+    prover_id: &[u8; 31],
+    last_used_sector_id: u64,
+) -> *mut responses::InitSectorBuilderResponse {
+    let mut response: responses::InitSectorBuilderResponse = Default::default();
+
+    match (*ptr).add_prover(*prover_id, last_used_sector_id) {
+        Ok(sb) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_builder = raw_ptr(sb);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
 /// Destroys a SectorBuilder.
 ///
 #[no_mangle]
@@ -252,6 +359,36 @@ pub unsafe extern "C" fn destroy_sector_builder(ptr: *mut SectorBuilder) {
     let _ = Box::from_raw(ptr);
 }
 
+/// Shuts down and destroys a SectorBuilder, persisting a final snapshot of its
+/// metadata. In Drain mode, waits for queued and in-progress seals to finish
+/// first. The SectorBuilder must not be used (or destroyed) afterwards.
+///
+#[no_mangle]
+pub unsafe extern "C" fn shutdown_sector_builder(
+    ptr: *mut SectorBuilder,
+    mode: FFIShutdownMode,
+) -> *mut responses::ShutdownSectorBuilderResponse {
+    let mut response: responses::ShutdownSectorBuilderResponse = Default::default();
+
+    let mode = match mode {
+        FFIShutdownMode::Drain => ShutdownMode::Drain,
+        FFIShutdownMode::CheckpointAndStop => ShutdownMode::CheckpointAndStop,
+    };
+
+    match Box::from_raw(ptr).shutdown(mode) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
 /// Writes user piece-bytes to a staged sector and returns the id of the sector
 /// to which the bytes were written.
 ///
@@ -310,6 +447,364 @@ pub unsafe extern "C" fn read_piece_from_sealed_sector(
     raw_ptr(response)
 }
 
+/// Returns the sector, seal status and position of the piece associated with
+/// the provided piece key.
+///
+#[no_mangle]
+pub unsafe extern "C" fn get_piece_info(
+    ptr: *mut SectorBuilder,
+    piece_key: *const libc::c_char,
+) -> *mut responses::GetPieceInfoResponse {
+    let mut response: responses::GetPieceInfoResponse = Default::default();
+
+    let piece_key = c_str_to_rust_str(piece_key);
+
+    match (*ptr).get_piece_info(String::from(piece_key)) {
+        Ok(info) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_id = info.sector_id;
+            response.seal_status_code = into_ffi_seal_status(info.seal_status);
+            response.offset = info.offset;
+            response.num_bytes = info.num_bytes.into();
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Returns up to limit pieces, skipping the first offset pieces. Pieces are
+/// ordered by sector id and then by position within their sector. Filters
+/// apply only if the corresponding filter_by_* flag is set; a null
+/// piece_key_prefix matches all piece keys.
+///
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn list_pieces(
+    ptr: *mut SectorBuilder,
+    offset: u64,
+    limit: u64,
+    filter_by_sector_id: bool,
+    sector_id: u64,
+    filter_by_seal_status: bool,
+    seal_status: FFISealStatus,
+    piece_key_prefix: *const libc::c_char,
+) -> *mut responses::ListPiecesResponse {
+    let mut response: responses::ListPiecesResponse = Default::default();
+
+    let filter = PieceFilter {
+        sector_id: if filter_by_sector_id {
+            Some(sector_id)
+        } else {
+            None
+        },
+        seal_status: if filter_by_seal_status {
+            Some(from_ffi_seal_status(seal_status))
+        } else {
+            None
+        },
+        piece_key_prefix: if piece_key_prefix.is_null() {
+            None
+        } else {
+            Some(c_str_to_rust_str(piece_key_prefix).to_string())
+        },
+    };
+
+    let page = (*ptr).list_pieces(filter, offset, limit);
+
+    let pieces = page
+        .pieces
+        .into_iter()
+        .map(|info| responses::FFIPieceInfo {
+            piece_key: rust_str_to_c_str(info.piece_key),
+            sector_id: info.sector_id,
+            seal_status_code: into_ffi_seal_status(info.seal_status),
+            offset: info.offset,
+            num_bytes: info.num_bytes.into(),
+        })
+        .collect::<Vec<responses::FFIPieceInfo>>();
+
+    response.status_code = FCPResponseStatus::FCPNoError;
+    response.total = page.total;
+    response.pieces_len = pieces.len();
+    response.pieces_ptr = pieces.as_ptr();
+
+    mem::forget(pieces);
+
+    raw_ptr(response)
+}
+
+fn into_ffi_seal_status(seal_status: PieceSealStatus) -> FFISealStatus {
+    match seal_status {
+        PieceSealStatus::Pending => FFISealStatus::Pending,
+        PieceSealStatus::Sealing => FFISealStatus::Sealing,
+        PieceSealStatus::Sealed => FFISealStatus::Sealed,
+        PieceSealStatus::Failed => FFISealStatus::Failed,
+    }
+}
+
+fn from_ffi_seal_status(seal_status: FFISealStatus) -> PieceSealStatus {
+    match seal_status {
+        FFISealStatus::Pending => PieceSealStatus::Pending,
+        FFISealStatus::Sealing => PieceSealStatus::Sealing,
+        FFISealStatus::Sealed => PieceSealStatus::Sealed,
+        FFISealStatus::Failed => PieceSealStatus::Failed,
+    }
+}
+
+fn into_ffi_sector_builder_config(config: &SectorBuilderConfig) -> FFISectorBuilderConfig {
+    let retry = &config.seal_retry_policy;
+
+    FFISectorBuilderConfig {
+        seal_retry_max_attempts: retry.max_attempts,
+        seal_retry_initial_backoff_secs: retry.initial_backoff.as_secs(),
+        seal_retry_backoff_multiplier: retry.backoff_multiplier,
+        seal_retry_max_backoff_secs: retry.max_backoff.as_secs(),
+        seal_retry_io_errors: retry.retriable.contains(&SealErrorClass::Io),
+        seal_retry_sector_manager_errors: retry.retriable.contains(&SealErrorClass::SectorManager),
+        seal_retry_other_errors: retry.retriable.contains(&SealErrorClass::Other),
+        scrub_interval_secs: config
+            .scrub_policy
+            .as_ref()
+            .map_or(0, |policy| policy.interval.as_secs()),
+        scrub_verify_proofs: config
+            .scrub_policy
+            .as_ref()
+            .map_or(false, |policy| policy.verify_proofs),
+        // A directory returned to the caller would have to be freed by them.
+        unseal_cache_dir: ptr::null(),
+        unseal_cache_max_bytes: config
+            .unseal_cache_config
+            .as_ref()
+            .map_or(0, |cache| cache.max_bytes),
+        sealer_queue_max_wait_secs: config.sealer_queue_max_wait.as_secs(),
+        checksum_staged_sectors: config.checksum_staged_sectors,
+    }
+}
+
+unsafe fn from_ffi_sector_builder_config(config: &FFISectorBuilderConfig) -> SectorBuilderConfig {
+    let retriable = vec![
+        (config.seal_retry_io_errors, SealErrorClass::Io),
+        (
+            config.seal_retry_sector_manager_errors,
+            SealErrorClass::SectorManager,
+        ),
+        (config.seal_retry_other_errors, SealErrorClass::Other),
+    ]
+    .into_iter()
+    .filter(|(retried, _)| *retried)
+    .map(|(_, class)| class)
+    .collect();
+
+    SectorBuilderConfig {
+        seal_retry_policy: SealRetryPolicy {
+            max_attempts: config.seal_retry_max_attempts,
+            initial_backoff: Duration::from_secs(config.seal_retry_initial_backoff_secs),
+            backoff_multiplier: config.seal_retry_backoff_multiplier,
+            max_backoff: Duration::from_secs(config.seal_retry_max_backoff_secs),
+            retriable,
+        },
+        scrub_policy: if config.scrub_interval_secs > 0 {
+            Some(ScrubPolicy {
+                interval: Duration::from_secs(config.scrub_interval_secs),
+                verify_proofs: config.scrub_verify_proofs,
+            })
+        } else {
+            None
+        },
+        unseal_cache_config: if config.unseal_cache_dir.is_null() {
+            None
+        } else {
+            Some(UnsealCacheConfig {
+                dir: PathBuf::from(c_str_to_rust_str(config.unseal_cache_dir).to_string()),
+                max_bytes: config.unseal_cache_max_bytes,
+            })
+        },
+        sealer_queue_max_wait: Duration::from_secs(config.sealer_queue_max_wait_secs),
+        checksum_staged_sectors: config.checksum_staged_sectors,
+    }
+}
+
+/// Unseals and returns num_bytes bytes of the piece associated with the
+/// provided piece key, starting offset bytes into the piece.
+///
+#[no_mangle]
+pub unsafe extern "C" fn read_piece_range(
+    ptr: *mut SectorBuilder,
+    piece_key: *const libc::c_char,
+    offset: u64,
+    num_bytes: u64,
+) -> *mut responses::ReadPieceFromSealedSectorResponse {
+    let mut response: responses::ReadPieceFromSealedSectorResponse = Default::default();
+
+    let piece_key = c_str_to_rust_str(piece_key);
+
+    match (*ptr).read_piece_range(
+        String::from(piece_key),
+        offset,
+        UnpaddedBytesAmount(num_bytes),
+    ) {
+        Ok(piece_bytes) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.data_ptr = piece_bytes.as_ptr();
+            response.data_len = piece_bytes.len();
+            mem::forget(piece_bytes);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Unseals num_bytes bytes of the piece associated with the provided piece
+/// key, starting offset bytes into the piece, and writes them to the file at
+/// output_path.
+///
+#[no_mangle]
+pub unsafe extern "C" fn read_piece_range_to_path(
+    ptr: *mut SectorBuilder,
+    piece_key: *const libc::c_char,
+    offset: u64,
+    num_bytes: u64,
+    output_path: *const libc::c_char,
+) -> *mut responses::ReadPieceRangeToPathResponse {
+    let mut response: responses::ReadPieceRangeToPathResponse = Default::default();
+
+    let piece_key = c_str_to_rust_str(piece_key);
+    let output_path = c_str_to_rust_str(output_path);
+
+    match (*ptr).read_piece_range_to_path(
+        String::from(piece_key),
+        offset,
+        UnpaddedBytesAmount(num_bytes),
+        PathBuf::from(output_path.to_string()),
+    ) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Writes the replica, merkle tree and metadata of a sealed sector to a bundle
+/// directory, from which it can be imported by another sector builder of the
+/// same prover.
+///
+#[no_mangle]
+pub unsafe extern "C" fn export_sealed_sector(
+    ptr: *mut SectorBuilder,
+    sector_id: u64,
+    bundle_dir: *const libc::c_char,
+) -> *mut responses::ExportSealedSectorResponse {
+    let mut response: responses::ExportSealedSectorResponse = Default::default();
+
+    let bundle_dir = c_str_to_rust_str(bundle_dir);
+
+    match (*ptr).export_sealed_sector(sector_id, PathBuf::from(bundle_dir.to_string())) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Verifies and imports a sealed sector from a bundle directory written by
+/// export_sealed_sector. Only bundles exported by a sector builder of the same
+/// prover id can be imported: a sector's replica and proof are bound to the
+/// prover id it was sealed with.
+///
+#[no_mangle]
+pub unsafe extern "C" fn import_sealed_sector(
+    ptr: *mut SectorBuilder,
+    bundle_dir: *const libc::c_char,
+) -> *mut responses::ImportSealedSectorResponse {
+    let mut response: responses::ImportSealedSectorResponse = Default::default();
+
+    let bundle_dir = c_str_to_rust_str(bundle_dir);
+
+    match (*ptr).import_sealed_sector(PathBuf::from(bundle_dir.to_string())) {
+        Ok(sector_id) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_id = sector_id;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Sets the seal priority of a staged sector. Seals of sectors with a higher
+/// priority are started first.
+///
+#[no_mangle]
+pub unsafe extern "C" fn set_seal_priority(
+    ptr: *mut SectorBuilder,
+    sector_id: u64,
+    priority: u8,
+) -> *mut responses::SetSealPriorityResponse {
+    let mut response: responses::SetSealPriorityResponse = Default::default();
+
+    match (*ptr).set_seal_priority(sector_id, priority) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Schedules another seal attempt for a staged sector whose seal has failed.
+///
+#[no_mangle]
+pub unsafe extern "C" fn retry_seal(
+    ptr: *mut SectorBuilder,
+    sector_id: u64,
+) -> *mut responses::RetrySealResponse {
+    let mut response: responses::RetrySealResponse = Default::default();
+
+    match (*ptr).retry_seal(sector_id) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
 /// For demo purposes. Seals all staged sectors.
 ///
 #[no_mangle]
@@ -346,6 +841,21 @@ pub unsafe extern "C" fn get_max_user_bytes_per_staged_sector(
     raw_ptr(response)
 }
 
+/// Returns SectorBuilder metrics, rendered in the Prometheus text exposition
+/// format.
+///
+#[no_mangle]
+pub unsafe extern "C" fn get_metrics(
+    ptr: *mut SectorBuilder,
+) -> *mut responses::GetMetricsResponse {
+    let mut response: responses::GetMetricsResponse = Default::default();
+
+    response.status_code = FCPResponseStatus::FCPNoError;
+    response.prometheus_text = rust_str_to_c_str((*ptr).get_metrics().to_prometheus());
+
+    raw_ptr(response)
+}
+
 /// Returns sector sealing status for the provided sector id if it exists. If
 /// we don't know about the provided sector id, produce an error.
 ///
@@ -370,7 +880,8 @@ pub unsafe extern "C" fn get_seal_status(
                     response.comm_r_star = meta.comm_r_star;
                     response.snark_proof = meta.snark_proof;
                     response.sector_id = meta.sector_id;
-                    response.sector_access = rust_str_to_c_str(meta.sector_access);
+                    response.sector_access =
+                        rust_str_to_c_str(meta.sector_access.to_legacy_string());
 
                     let pieces = meta
                         .pieces
@@ -434,11 +945,21 @@ pub unsafe extern "C" fn get_sealed_sectors(
                         comm_d: meta.comm_d,
                         comm_r: meta.comm_r,
                         comm_r_star: meta.comm_r_star,
-                        sector_access: rust_str_to_c_str(meta.sector_access.clone()),
+                        sector_access: rust_str_to_c_str(meta.sector_access.to_legacy_string()),
                         sector_id: meta.sector_id,
                         snark_proof: meta.snark_proof,
                         pieces_len: pieces.len(),
                         pieces_ptr: pieces.as_ptr(),
+                        health: match meta.health {
+                            SealedSectorHealth::Ok => FFISealedSectorHealth::Ok,
+                            SealedSectorHealth::ReplicaMissing => {
+                                FFISealedSectorHealth::ReplicaMissing
+                            }
+                            SealedSectorHealth::CommRMismatch => {
+                                FFISealedSectorHealth::CommRMismatch
+                            }
+                            SealedSectorHealth::ProofInvalid => FFISealedSectorHealth::ProofInvalid,
+                        },
                     };
 
                     mem::forget(pieces);
@@ -485,7 +1006,7 @@ pub unsafe extern "C" fn get_staged_sectors(
                         .collect::<Vec<FFIPieceMetadata>>();
 
                     let mut sector = responses::FFIStagedSectorMetadata {
-                        sector_access: rust_str_to_c_str(meta.sector_access.clone()),
+                        sector_access: rust_str_to_c_str(meta.sector_access.to_legacy_string()),
                         sector_id: meta.sector_id,
                         pieces_len: pieces.len(),
                         pieces_ptr: pieces.as_ptr(),
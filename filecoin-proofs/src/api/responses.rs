@@ -28,6 +28,63 @@ pub enum FFISealStatus {
     Sealing = 3,
 }
 
+#[repr(C)]
+#[derive(PartialEq, Debug)]
+pub enum FFIShutdownMode {
+    Drain = 0,
+    CheckpointAndStop = 1,
+}
+
+/// Settings of a SectorBuilder, as accepted by init_sector_builder_with_config
+/// and returned, with their default values, by
+/// get_default_sector_builder_config.
+#[repr(C)]
+#[derive(Debug)]
+pub struct FFISectorBuilderConfig {
+    /// Maximum number of seal attempts, including the first, before a sector
+    /// is left in the Failed state.
+    pub seal_retry_max_attempts: usize,
+    /// Delay, in seconds, before the first automatic retry of a failed seal.
+    pub seal_retry_initial_backoff_secs: u64,
+    /// Each subsequent delay is the previous one multiplied by this factor.
+    pub seal_retry_backoff_multiplier: u32,
+    /// Upper bound, in seconds, on any single delay.
+    pub seal_retry_max_backoff_secs: u64,
+    /// Whether seals which failed with I/O errors are retried automatically.
+    pub seal_retry_io_errors: bool,
+    /// Whether seals which failed with SectorManager errors are retried
+    /// automatically.
+    pub seal_retry_sector_manager_errors: bool,
+    /// Whether seals which failed with any other error are retried
+    /// automatically.
+    pub seal_retry_other_errors: bool,
+    /// Seconds between scrubbing one sealed sector and the next, or 0 not to
+    /// scrub sealed sectors.
+    pub scrub_interval_secs: u64,
+    /// Whether scrubbing re-verifies the proofs of sealed sectors.
+    pub scrub_verify_proofs: bool,
+    /// Directory in which unsealed copies of sealed sectors are cached, or
+    /// null not to cache them.
+    pub unseal_cache_dir: *const libc::c_char,
+    /// Upper bound on the total size of the cached copies.
+    pub unseal_cache_max_bytes: u64,
+    /// Seconds after which queued seals are served ahead of other seals,
+    /// regardless of priority.
+    pub sealer_queue_max_wait_secs: u64,
+    /// Whether checksums of staged sectors are recorded when they are closed
+    /// and checked before they are sealed.
+    pub checksum_staged_sectors: bool,
+}
+
+#[repr(C)]
+#[derive(PartialEq, Debug)]
+pub enum FFISealedSectorHealth {
+    Ok = 0,
+    ReplicaMissing = 1,
+    CommRMismatch = 2,
+    ProofInvalid = 3,
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 /// VerifySealResponse
 //////////////////////
@@ -155,6 +212,14 @@ pub fn err_code_and_msg(err: &Error) -> (FCPResponseStatus, *const libc::c_char)
         Some(SectorBuilderErr::IncompleteWriteError { .. }) => return (FCPReceiverError, ptr),
         Some(SectorBuilderErr::Unrecoverable(_, _)) => return (FCPReceiverError, ptr),
         Some(SectorBuilderErr::PieceNotFound(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::DuplicatePieceKey(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::RangeOutOfBounds { .. }) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::PieceChecksumMismatch { .. }) => return (FCPReceiverError, ptr),
+        Some(SectorBuilderErr::InvalidSectorState(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::DuplicateProver(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::InvalidBundle(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::Locked(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::PoStDeadlineExceeded) => return (FCPReceiverError, ptr),
         None => (),
     }
 
@@ -162,6 +227,9 @@ pub fn err_code_and_msg(err: &Error) -> (FCPResponseStatus, *const libc::c_char)
         Some(SectorManagerErr::UnclassifiedError(_)) => return (FCPUnclassifiedError, ptr),
         Some(SectorManagerErr::CallerError(_)) => return (FCPCallerError, ptr),
         Some(SectorManagerErr::ReceiverError(_)) => return (FCPReceiverError, ptr),
+        Some(SectorManagerErr::CapacityExceeded { .. }) => return (FCPCallerError, ptr),
+        Some(SectorManagerErr::InsufficientSpace { .. }) => return (FCPReceiverError, ptr),
+        Some(SectorManagerErr::SectorLocked(_)) => return (FCPCallerError, ptr),
         None => (),
     }
 
@@ -314,6 +382,310 @@ pub unsafe extern "C" fn destroy_seal_all_staged_sectors_response(
     let _ = Box::from_raw(ptr);
 }
 
+///////////////////////////////////////////////////////////////////////////////
+/// GetPieceInfoResponse
+////////////////////////
+
+#[repr(C)]
+pub struct GetPieceInfoResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    pub sector_id: u64,
+    pub seal_status_code: FFISealStatus,
+    pub offset: u64,
+    pub num_bytes: u64,
+}
+
+impl Default for GetPieceInfoResponse {
+    fn default() -> GetPieceInfoResponse {
+        GetPieceInfoResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            sector_id: 0,
+            seal_status_code: FFISealStatus::Pending,
+            offset: 0,
+            num_bytes: 0,
+        }
+    }
+}
+
+impl Drop for GetPieceInfoResponse {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg as *mut libc::c_char);
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_get_piece_info_response(ptr: *mut GetPieceInfoResponse) {
+    let _ = Box::from_raw(ptr);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// ListPiecesResponse
+//////////////////////
+
+#[repr(C)]
+pub struct FFIPieceInfo {
+    pub piece_key: *const libc::c_char,
+    pub sector_id: u64,
+    pub seal_status_code: FFISealStatus,
+    pub offset: u64,
+    pub num_bytes: u64,
+}
+
+impl Drop for FFIPieceInfo {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.piece_key as *mut libc::c_char);
+        }
+    }
+}
+
+#[repr(C)]
+pub struct ListPiecesResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    pub pieces_len: libc::size_t,
+    pub pieces_ptr: *const FFIPieceInfo,
+
+    // number of pieces matching the filter, across all pages
+    pub total: u64,
+}
+
+impl Default for ListPiecesResponse {
+    fn default() -> ListPiecesResponse {
+        ListPiecesResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            pieces_len: 0,
+            pieces_ptr: ptr::null(),
+            total: 0,
+        }
+    }
+}
+
+impl Drop for ListPiecesResponse {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg as *mut libc::c_char);
+            drop(Vec::from_raw_parts(
+                self.pieces_ptr as *mut FFIPieceInfo,
+                self.pieces_len,
+                self.pieces_len,
+            ));
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_list_pieces_response(ptr: *mut ListPiecesResponse) {
+    let _ = Box::from_raw(ptr);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// ReadPieceRangeToPathResponse
+////////////////////////////////
+
+#[repr(C)]
+pub struct ReadPieceRangeToPathResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for ReadPieceRangeToPathResponse {
+    fn default() -> ReadPieceRangeToPathResponse {
+        ReadPieceRangeToPathResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+impl Drop for ReadPieceRangeToPathResponse {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg as *mut libc::c_char);
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_read_piece_range_to_path_response(
+    ptr: *mut ReadPieceRangeToPathResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// RetrySealResponse
+/////////////////////
+
+#[repr(C)]
+pub struct RetrySealResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for RetrySealResponse {
+    fn default() -> RetrySealResponse {
+        RetrySealResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+impl Drop for RetrySealResponse {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg as *mut libc::c_char);
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_retry_seal_response(ptr: *mut RetrySealResponse) {
+    let _ = Box::from_raw(ptr);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// ExportSealedSectorResponse
+//////////////////////////////
+
+#[repr(C)]
+pub struct ExportSealedSectorResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for ExportSealedSectorResponse {
+    fn default() -> ExportSealedSectorResponse {
+        ExportSealedSectorResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+impl Drop for ExportSealedSectorResponse {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg as *mut libc::c_char);
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_export_sealed_sector_response(
+    ptr: *mut ExportSealedSectorResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// ImportSealedSectorResponse
+//////////////////////////////
+
+#[repr(C)]
+pub struct ImportSealedSectorResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    pub sector_id: u64,
+}
+
+impl Default for ImportSealedSectorResponse {
+    fn default() -> ImportSealedSectorResponse {
+        ImportSealedSectorResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            sector_id: 0,
+        }
+    }
+}
+
+impl Drop for ImportSealedSectorResponse {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg as *mut libc::c_char);
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_import_sealed_sector_response(
+    ptr: *mut ImportSealedSectorResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// SetSealPriorityResponse
+///////////////////////////
+
+#[repr(C)]
+pub struct SetSealPriorityResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for SetSealPriorityResponse {
+    fn default() -> SetSealPriorityResponse {
+        SetSealPriorityResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+impl Drop for SetSealPriorityResponse {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg as *mut libc::c_char);
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_set_seal_priority_response(ptr: *mut SetSealPriorityResponse) {
+    let _ = Box::from_raw(ptr);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// ShutdownSectorBuilderResponse
+/////////////////////////////////
+
+#[repr(C)]
+pub struct ShutdownSectorBuilderResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for ShutdownSectorBuilderResponse {
+    fn default() -> ShutdownSectorBuilderResponse {
+        ShutdownSectorBuilderResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+impl Drop for ShutdownSectorBuilderResponse {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg as *mut libc::c_char);
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_shutdown_sector_builder_response(
+    ptr: *mut ShutdownSectorBuilderResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 /// GetMaxStagedBytesPerSector
 //////////////////////////////
@@ -350,6 +722,41 @@ pub unsafe extern "C" fn destroy_get_max_user_bytes_per_staged_sector_response(
     let _ = Box::from_raw(ptr);
 }
 
+///////////////////////////////////////////////////////////////////////////////
+/// GetMetricsResponse
+//////////////////////
+
+#[repr(C)]
+pub struct GetMetricsResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    pub prometheus_text: *const libc::c_char,
+}
+
+impl Default for GetMetricsResponse {
+    fn default() -> GetMetricsResponse {
+        GetMetricsResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            prometheus_text: ptr::null(),
+        }
+    }
+}
+
+impl Drop for GetMetricsResponse {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg as *mut libc::c_char);
+            free_c_str(self.prometheus_text as *mut libc::c_char);
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_get_metrics_response(ptr: *mut GetMetricsResponse) {
+    let _ = Box::from_raw(ptr);
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 /// GetSealStatusResponse
 /////////////////////////
@@ -368,6 +775,8 @@ pub struct GetSealStatusResponse {
     pub comm_d: [u8; 32],
     pub comm_r: [u8; 32],
     pub comm_r_star: [u8; 32],
+    // the path of the sector's file, as before sectors were identified by
+    // URIs, or a URI (e.g. mem://sealed/...) if it is not held in a file
     pub sector_access: *const libc::c_char,
     pub sector_id: u64,
     pub snark_proof: [u8; API_POREP_PROOF_BYTES],
@@ -437,6 +846,8 @@ pub unsafe extern "C" fn destroy_get_seal_status_response(ptr: *mut GetSealStatu
 
 #[repr(C)]
 pub struct FFIStagedSectorMetadata {
+    // the path of the sector's file, as before sectors were identified by
+    // URIs, or a URI (e.g. mem://sealed/...) if it is not held in a file
     pub sector_access: *const libc::c_char,
     pub sector_id: u64,
     pub pieces_len: libc::size_t,
@@ -472,11 +883,14 @@ pub struct FFISealedSectorMetadata {
     pub comm_d: [u8; 32],
     pub comm_r: [u8; 32],
     pub comm_r_star: [u8; 32],
+    // the path of the sector's file, as before sectors were identified by
+    // URIs, or a URI (e.g. mem://sealed/...) if it is not held in a file
     pub sector_access: *const libc::c_char,
     pub sector_id: u64,
     pub snark_proof: [u8; API_POREP_PROOF_BYTES],
     pub pieces_len: libc::size_t,
     pub pieces_ptr: *const FFIPieceMetadata,
+    pub health: FFISealedSectorHealth,
 }
 
 impl Drop for FFISealedSectorMetadata {
@@ -24,6 +24,41 @@ pub enum SectorBuilderErr {
     #[fail(display = "no piece with key {} found", _0)]
     PieceNotFound(String),
 
+    #[fail(display = "a piece with key {} already exists", _0)]
+    DuplicatePieceKey(String),
+
+    #[fail(
+        display = "range (offset {}, {} bytes) exceeds piece {} of {} bytes",
+        offset, num_bytes, piece_key, piece_num_bytes
+    )]
+    RangeOutOfBounds {
+        piece_key: String,
+        offset: u64,
+        num_bytes: u64,
+        piece_num_bytes: u64,
+    },
+
+    #[fail(
+        display = "piece {} in staged sector {} does not match its checksum",
+        piece_key, sector_id
+    )]
+    PieceChecksumMismatch { sector_id: u64, piece_key: String },
+
+    #[fail(display = "invalid sector state: {}", _0)]
+    InvalidSectorState(String),
+
+    #[fail(display = "prover {} is already managed by this SectorBuilder", _0)]
+    DuplicateProver(String),
+
+    #[fail(display = "invalid sector bundle: {}", _0)]
+    InvalidBundle(String),
+
+    #[fail(display = "{} is in use by another SectorBuilder", _0)]
+    Locked(String),
+
+    #[fail(display = "proof-of-spacetime was not generated before its deadline")]
+    PoStDeadlineExceeded,
+
     #[fail(display = "unrecoverable error: {}", _0)]
     Unrecoverable(String, Backtrace),
 }
@@ -32,6 +67,51 @@ pub fn err_piecenotfound(piece_key: String) -> SectorBuilderErr {
     SectorBuilderErr::PieceNotFound(piece_key)
 }
 
+pub fn err_duplicate_piece_key(piece_key: String) -> SectorBuilderErr {
+    SectorBuilderErr::DuplicatePieceKey(piece_key)
+}
+
+pub fn err_range_out_of_bounds(
+    piece_key: String,
+    offset: u64,
+    num_bytes: u64,
+    piece_num_bytes: u64,
+) -> SectorBuilderErr {
+    SectorBuilderErr::RangeOutOfBounds {
+        piece_key,
+        offset,
+        num_bytes,
+        piece_num_bytes,
+    }
+}
+
+pub fn err_piece_checksum_mismatch(sector_id: u64, piece_key: String) -> SectorBuilderErr {
+    SectorBuilderErr::PieceChecksumMismatch {
+        sector_id,
+        piece_key,
+    }
+}
+
+pub fn err_invalid_state<S: Display>(msg: S) -> SectorBuilderErr {
+    SectorBuilderErr::InvalidSectorState(format!("{}", msg))
+}
+
+pub fn err_duplicate_prover(prover_id: String) -> SectorBuilderErr {
+    SectorBuilderErr::DuplicateProver(prover_id)
+}
+
+pub fn err_invalid_bundle<S: Display>(msg: S) -> SectorBuilderErr {
+    SectorBuilderErr::InvalidBundle(format!("{}", msg))
+}
+
+pub fn err_locked<S: Display>(what: S) -> SectorBuilderErr {
+    SectorBuilderErr::Locked(format!("{}", what))
+}
+
+pub fn err_post_deadline() -> SectorBuilderErr {
+    SectorBuilderErr::PoStDeadlineExceeded
+}
+
 pub fn err_unrecov<S: Display>(msg: S) -> SectorBuilderErr {
     let backtrace = failure::Backtrace::new();
     SectorBuilderErr::Unrecoverable(format!("{}", msg), backtrace)
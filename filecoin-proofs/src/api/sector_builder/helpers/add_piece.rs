@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use crate::api::sector_builder::errors::*;
+use crate::api::sector_builder::metadata::piece_checksum;
 use crate::api::sector_builder::metadata::sum_piece_bytes;
 use crate::api::sector_builder::metadata::StagedSectorMetadata;
 use crate::api::sector_builder::state::StagedState;
@@ -11,6 +12,7 @@ use sector_base::api::sector_store::SectorManager;
 
 pub fn add_piece(
     sector_store: &Arc<WrappedSectorStore>,
+    prover_id: &ProverId,
     mut staged_state: &mut StagedState,
     piece_key: String,
     piece_bytes: &[u8],
@@ -33,7 +35,7 @@ pub fn add_piece(
 
     let dest_sector_id = opt_dest_sector_id
         .ok_or(())
-        .or_else(|_| provision_new_staged_sector(sector_mgr, &mut staged_state))?;
+        .or_else(|_| provision_new_staged_sector(sector_mgr, prover_id, &mut staged_state))?;
 
     if let Some(s) = staged_state.sectors.get_mut(&dest_sector_id) {
         sector_store
@@ -55,6 +57,7 @@ pub fn add_piece(
                 s.pieces.push(metadata::PieceMetadata {
                     piece_key,
                     num_bytes: piece_bytes_len,
+                    checksum: Some(piece_checksum(piece_bytes)),
                 });
 
                 sector_id
@@ -88,6 +91,7 @@ fn compute_destination_sector_id(
 // nonce, and mutates the StagedState.
 fn provision_new_staged_sector(
     sector_manager: &SectorManager,
+    prover_id: &ProverId,
     staged_state: &mut StagedState,
 ) -> error::Result<SectorId> {
     let sector_id = {
@@ -96,13 +100,17 @@ fn provision_new_staged_sector(
         *n
     };
 
-    let access = sector_manager.new_staging_sector_access()?;
+    let access = sector_manager.new_staging_sector_access_for(prover_id, sector_id)?;
 
     let meta = StagedSectorMetadata {
         pieces: Default::default(),
         sector_access: access.clone(),
         sector_id,
         seal_status: SealStatus::Pending,
+        seal_attempts: Default::default(),
+        seal_priority: Default::default(),
+        sealed_sector_access: None,
+        seal_retry_at: None,
     };
 
     staged_state.sectors.insert(meta.sector_id, meta.clone());
@@ -114,6 +122,44 @@ fn provision_new_staged_sector(
 mod tests {
     use super::*;
     use crate::api::sector_builder::metadata::PieceMetadata;
+    use blake2::{Blake2s, Digest};
+    use sector_base::api::disk_backed_storage::{new_sector_store, ConfiguredStore};
+
+    #[test]
+    fn test_add_piece_records_checksum() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staging_dir = tempfile::tempdir().unwrap();
+
+        let sector_store = Arc::new(WrappedSectorStore {
+            inner: Box::new(new_sector_store(
+                &ConfiguredStore::Test,
+                sealed_dir.path().to_str().unwrap().to_owned(),
+                staging_dir.path().to_str().unwrap().to_owned(),
+            )),
+        });
+
+        let mut staged_state: StagedState = Default::default();
+        let piece_bytes = vec![42u8; 100];
+
+        let sector_id = add_piece(
+            &sector_store,
+            &[0; 31],
+            &mut staged_state,
+            "a".to_string(),
+            &piece_bytes,
+        )
+        .unwrap();
+
+        let pieces = &staged_state.sectors[&sector_id].pieces;
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(pieces[0].piece_key, "a");
+        assert_eq!(pieces[0].num_bytes, UnpaddedBytesAmount(100));
+
+        // the Blake2s digest of the piece's (unpadded) bytes
+        let mut expected = [0u8; 32];
+        expected.copy_from_slice(&Blake2s::digest(&piece_bytes));
+        assert_eq!(pieces[0].checksum, Some(expected));
+    }
 
     #[test]
     fn test_alpha() {
@@ -122,11 +168,13 @@ mod tests {
         sealed_sector_a.pieces.push(PieceMetadata {
             piece_key: String::from("x"),
             num_bytes: UnpaddedBytesAmount(5),
+            checksum: None,
         });
 
         sealed_sector_a.pieces.push(PieceMetadata {
             piece_key: String::from("x"),
             num_bytes: UnpaddedBytesAmount(10),
+            checksum: None,
         });
 
         let mut sealed_sector_b: StagedSectorMetadata = Default::default();
@@ -134,6 +182,7 @@ mod tests {
         sealed_sector_b.pieces.push(PieceMetadata {
             piece_key: String::from("x"),
             num_bytes: UnpaddedBytesAmount(5),
+            checksum: None,
         });
 
         let staged_sectors = vec![sealed_sector_a.clone(), sealed_sector_b.clone()];
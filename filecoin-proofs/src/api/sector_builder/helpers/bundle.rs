@@ -0,0 +1,243 @@
+use crate::api::internal;
+use crate::api::sector_builder::errors::err_invalid_bundle;
+use crate::api::sector_builder::metadata::sector_id_as_bytes;
+use crate::api::sector_builder::metadata::SealedSectorHealth;
+use crate::api::sector_builder::metadata::SealedSectorMetadata;
+use crate::api::sector_builder::ProverId;
+use crate::api::sector_builder::WrappedSectorStore;
+use crate::error;
+use sector_base::api::sector_store::LockMode;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const REPLICA_FILE: &str = "replica";
+const TREE_FILE: &str = "tree";
+
+// Describes the sealed sector whose replica accompanies it in a bundle. The
+// sector_access of the contained metadata refers to the exporting
+// SectorBuilder's storage and is replaced on import.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub prover_id: ProverId,
+    pub sector: SealedSectorMetadata,
+}
+
+// Writes the sealed sector's replica, the merkle tree persisted alongside it
+// (if any) and its metadata to a bundle directory, which is created if it does
+// not exist.
+pub fn export_bundle(
+    sector_store: &Arc<WrappedSectorStore>,
+    prover_id: &ProverId,
+    sealed_sector: &SealedSectorMetadata,
+    bundle_dir: &Path,
+) -> error::Result<()> {
+    let manager = sector_store.inner.manager();
+
+    let _lock = manager.lock_sector(&sealed_sector.sector_access, LockMode::Shared)?;
+
+    let sealed_path = manager.local_path(&sealed_sector.sector_access)?;
+
+    fs::create_dir_all(bundle_dir)?;
+
+    fs::copy(sealed_path, bundle_dir.join(REPLICA_FILE))?;
+
+    // A tree left in the bundle directory by an earlier export of another
+    // sector must not accompany this one.
+    match manager.sector_tree_path(&sealed_sector.sector_access)? {
+        Some(tree_path) => {
+            fs::copy(tree_path, bundle_dir.join(TREE_FILE))?;
+        }
+        None => {
+            if bundle_dir.join(TREE_FILE).exists() {
+                fs::remove_file(bundle_dir.join(TREE_FILE))?;
+            }
+        }
+    }
+
+    let manifest = BundleManifest {
+        prover_id: *prover_id,
+        sector: sealed_sector.clone(),
+    };
+
+    let writer = BufWriter::new(File::create(bundle_dir.join(MANIFEST_FILE))?);
+    serde_json::to_writer_pretty(writer, &manifest)?;
+
+    Ok(())
+}
+
+pub fn read_manifest(bundle_dir: &Path) -> error::Result<BundleManifest> {
+    let file = File::open(bundle_dir.join(MANIFEST_FILE)).map_err(|err| {
+        err_invalid_bundle(format!(
+            "could not open manifest in {}: {}",
+            bundle_dir.display(),
+            err
+        ))
+    })?;
+
+    serde_json::from_reader(file).map_err(|err| {
+        err_invalid_bundle(format!(
+            "could not parse manifest in {}: {}",
+            bundle_dir.display(),
+            err
+        ))
+        .into()
+    })
+}
+
+// Copies a bundle's replica into the sector store and checks it against the
+// manifest: the merkle root of the copied replica must equal comm_r and the
+// proof must verify for the importing prover, so only bundles exported under
+// the same prover id can be imported. A bundled merkle tree is restored
+// alongside the replica; it is checked against comm_r whenever it is opened.
+// Returns the metadata with which the sector should be added to the importing
+// SectorBuilder's sealed state. The copied replica (and tree) are removed if
+// the bundle does not check out.
+pub fn import_bundle(
+    sector_store: &Arc<WrappedSectorStore>,
+    prover_id: &ProverId,
+    manifest: &BundleManifest,
+    bundle_dir: &Path,
+) -> error::Result<SealedSectorMetadata> {
+    let manager = sector_store.inner.manager();
+
+    let sealed_sector_access = manager
+        .new_sealed_sector_access_for(prover_id, manifest.sector.sector_id)
+        .map_err(failure::Error::from)?;
+
+    let result = manager
+        .local_path(&sealed_sector_access)
+        .map_err(failure::Error::from)
+        .and_then(|sealed_path| {
+            let _lock = manager.lock_sector(&sealed_sector_access, LockMode::Exclusive)?;
+
+            fs::copy(bundle_dir.join(REPLICA_FILE), &sealed_path)?;
+
+            verify_replica(sector_store, prover_id, &manifest.sector, &sealed_path)?;
+
+            let tree_path = bundle_dir.join(TREE_FILE);
+
+            if tree_path.exists() {
+                manager.write_sector_tree(&sealed_sector_access, &fs::read(tree_path)?)?;
+            }
+
+            Ok(())
+        });
+
+    if result.is_err() {
+        let _ = manager.delete_sealed_sector_access(&sealed_sector_access);
+    }
+
+    // The replica has either been copied in full or removed, so the space set
+    // aside for it is no longer needed. As when sealing, a failure to release
+    // the reservation does not hide why the import failed.
+    let released = manager.release_reservation(&sealed_sector_access);
+
+    result?;
+    released?;
+
+    Ok(SealedSectorMetadata {
+        sector_access: sealed_sector_access,
+        health: SealedSectorHealth::Ok,
+        last_scrubbed_at: 0,
+        ..manifest.sector.clone()
+    })
+}
+
+fn verify_replica(
+    sector_store: &Arc<WrappedSectorStore>,
+    prover_id: &ProverId,
+    sealed_sector: &SealedSectorMetadata,
+    sealed_path: &Path,
+) -> error::Result<()> {
+    let config = (*sector_store.inner).config();
+
+    let comm_r = internal::compute_comm_r(config, sealed_path)?;
+
+    if comm_r != sealed_sector.comm_r {
+        return Err(err_invalid_bundle(format!(
+            "replica of sector {} does not match its comm_r",
+            sealed_sector.sector_id
+        ))
+        .into());
+    }
+
+    let is_valid = internal::verify_seal(
+        config,
+        sealed_sector.comm_r,
+        sealed_sector.comm_d,
+        sealed_sector.comm_r_star,
+        prover_id,
+        &sector_id_as_bytes(sealed_sector.sector_id)?,
+        &sealed_sector.snark_proof,
+    )?;
+
+    if !is_valid {
+        return Err(err_invalid_bundle(format!(
+            "proof of sector {} does not verify",
+            sealed_sector.sector_id
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sector_base::api::disk_backed_storage::{new_sector_store, ConfiguredStore};
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staging_dir = tempfile::tempdir().unwrap();
+        let bundle_dir = tempfile::tempdir().unwrap();
+
+        let sector_store = Arc::new(WrappedSectorStore {
+            inner: Box::new(new_sector_store(
+                &ConfiguredStore::Test,
+                sealed_dir.path().to_str().unwrap().to_owned(),
+                staging_dir.path().to_str().unwrap().to_owned(),
+            )),
+        });
+
+        let manager = sector_store.inner.manager();
+        let sector_access = manager.new_sealed_sector_access_for(&[0; 31], 42).unwrap();
+        fs::write(
+            manager.local_path(&sector_access).unwrap(),
+            b"replica bytes",
+        )
+        .unwrap();
+        manager
+            .write_sector_tree(&sector_access, b"tree bytes")
+            .unwrap();
+
+        let sealed_sector = SealedSectorMetadata {
+            sector_id: 42,
+            sector_access,
+            comm_r: [7; 32],
+            snark_proof: [9; 384],
+            ..Default::default()
+        };
+
+        export_bundle(&sector_store, &[3; 31], &sealed_sector, bundle_dir.path()).unwrap();
+
+        let manifest = read_manifest(bundle_dir.path()).unwrap();
+
+        assert_eq!(manifest.prover_id, [3; 31]);
+        assert_eq!(manifest.sector, sealed_sector);
+        assert_eq!(
+            fs::read(bundle_dir.path().join(REPLICA_FILE)).unwrap(),
+            b"replica bytes".to_vec()
+        );
+        assert_eq!(
+            fs::read(bundle_dir.path().join(TREE_FILE)).unwrap(),
+            b"tree bytes".to_vec()
+        );
+
+        assert!(read_manifest(sealed_dir.path()).is_err());
+    }
+}
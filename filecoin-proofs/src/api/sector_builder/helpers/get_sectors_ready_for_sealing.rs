@@ -61,6 +61,7 @@ mod tests {
                 pieces: vec![PieceMetadata {
                     piece_key: format!("{}", sector_id),
                     num_bytes: UnpaddedBytesAmount(num_bytes),
+                    checksum: None,
                 }],
                 seal_status,
                 ..Default::default()
@@ -1,6 +1,10 @@
 pub mod add_piece;
+pub mod bundle;
 pub mod get_seal_status;
 pub mod get_sectors_ready_for_sealing;
+pub mod pieces;
+pub mod reconcile;
 pub mod retrieve_piece;
+pub mod scrub;
 pub mod seal;
 pub mod snapshots;
@@ -0,0 +1,313 @@
+use crate::api::sector_builder::metadata::PieceMetadata;
+use crate::api::sector_builder::metadata::SealStatus;
+use crate::api::sector_builder::state::{SealedState, StagedState};
+use crate::api::sector_builder::SectorId;
+use sector_base::api::bytes_amount::UnpaddedBytesAmount;
+use std::collections::HashMap;
+
+// Maps each piece key to the id of the (staged or sealed) sector containing
+// the piece. A piece keeps its sector id when its sector is sealed.
+pub type PieceIndex = HashMap<String, SectorId>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PieceSealStatus {
+    Pending,
+    Sealing,
+    Sealed,
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PieceInfo {
+    pub piece_key: String,
+    pub sector_id: SectorId,
+    pub seal_status: PieceSealStatus,
+
+    // Position of the piece's first byte within its sector's unsealed bytes.
+    pub offset: u64,
+    pub num_bytes: UnpaddedBytesAmount,
+}
+
+// Restricts the pieces returned by list_pieces. Unset fields match all pieces.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PieceFilter {
+    pub sector_id: Option<SectorId>,
+    pub seal_status: Option<PieceSealStatus>,
+    pub piece_key_prefix: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PiecePage {
+    pub pieces: Vec<PieceInfo>,
+
+    // Number of pieces matching the filter, across all pages.
+    pub total: u64,
+}
+
+pub fn build_piece_index(staged_state: &StagedState, sealed_state: &SealedState) -> PieceIndex {
+    let staged = staged_state
+        .sectors
+        .values()
+        .map(|s| (s.sector_id, &s.pieces));
+
+    let sealed = sealed_state
+        .sectors
+        .values()
+        .map(|s| (s.sector_id, &s.pieces));
+
+    staged
+        .chain(sealed)
+        .flat_map(|(sector_id, pieces)| {
+            pieces
+                .iter()
+                .map(move |piece| (piece.piece_key.clone(), sector_id))
+        })
+        .collect()
+}
+
+pub fn get_piece_info(
+    staged_state: &StagedState,
+    sealed_state: &SealedState,
+    piece_index: &PieceIndex,
+    piece_key: &str,
+) -> Option<PieceInfo> {
+    let sector_id = piece_index.get(piece_key)?;
+
+    sector_pieces(staged_state, sealed_state, *sector_id)
+        .into_iter()
+        .find(|info| info.piece_key == piece_key)
+}
+
+// Returns up to limit pieces matching the filter, skipping the first offset
+// matches. Pieces are ordered by sector id and then by position within their
+// sector, so pages are stable as long as no pieces are added.
+pub fn list_pieces(
+    staged_state: &StagedState,
+    sealed_state: &SealedState,
+    filter: &PieceFilter,
+    offset: u64,
+    limit: u64,
+) -> PiecePage {
+    let mut sector_ids: Vec<SectorId> = staged_state
+        .sectors
+        .keys()
+        .chain(sealed_state.sectors.keys())
+        .filter(|sector_id| filter.sector_id.map_or(true, |id| id == **sector_id))
+        .cloned()
+        .collect();
+
+    sector_ids.sort();
+    sector_ids.dedup();
+
+    let matching: Vec<PieceInfo> = sector_ids
+        .into_iter()
+        .flat_map(|sector_id| sector_pieces(staged_state, sealed_state, sector_id))
+        .filter(|info| filter.seal_status.map_or(true, |s| s == info.seal_status))
+        .filter(|info| {
+            filter
+                .piece_key_prefix
+                .as_ref()
+                .map_or(true, |prefix| info.piece_key.starts_with(prefix.as_str()))
+        })
+        .collect();
+
+    let total = matching.len() as u64;
+
+    let pieces = matching
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect();
+
+    PiecePage { pieces, total }
+}
+
+// Returns information about every piece in the sector, or nothing if there
+// is no such sector. Sealed state takes precedence over staged state.
+fn sector_pieces(
+    staged_state: &StagedState,
+    sealed_state: &SealedState,
+    sector_id: SectorId,
+) -> Vec<PieceInfo> {
+    if let Some(sealed) = sealed_state.sectors.get(&sector_id) {
+        return piece_infos(sector_id, PieceSealStatus::Sealed, &sealed.pieces);
+    }
+
+    if let Some(staged) = staged_state.sectors.get(&sector_id) {
+        let seal_status = match staged.seal_status {
+            SealStatus::Pending => PieceSealStatus::Pending,
+            SealStatus::Sealing => PieceSealStatus::Sealing,
+            SealStatus::Sealed(_) => PieceSealStatus::Sealed,
+            SealStatus::Failed(_) => PieceSealStatus::Failed,
+        };
+
+        return piece_infos(sector_id, seal_status, &staged.pieces);
+    }
+
+    Vec::new()
+}
+
+fn piece_infos(
+    sector_id: SectorId,
+    seal_status: PieceSealStatus,
+    pieces: &[PieceMetadata],
+) -> Vec<PieceInfo> {
+    let mut offset = 0;
+
+    pieces
+        .iter()
+        .map(|piece| {
+            let info = PieceInfo {
+                piece_key: piece.piece_key.clone(),
+                sector_id,
+                seal_status,
+                offset,
+                num_bytes: piece.num_bytes,
+            };
+
+            offset += u64::from(piece.num_bytes);
+
+            info
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::sector_builder::metadata::{SealedSectorMetadata, StagedSectorMetadata};
+
+    fn pieces(keys_and_sizes: &[(&str, u64)]) -> Vec<PieceMetadata> {
+        keys_and_sizes
+            .iter()
+            .map(|(key, num_bytes)| PieceMetadata {
+                piece_key: key.to_string(),
+                num_bytes: UnpaddedBytesAmount(*num_bytes),
+                checksum: None,
+            })
+            .collect()
+    }
+
+    fn setup() -> (StagedState, SealedState) {
+        let mut staged_state: StagedState = Default::default();
+        let mut sealed_state: SealedState = Default::default();
+
+        staged_state.sectors.insert(
+            2,
+            StagedSectorMetadata {
+                sector_id: 2,
+                pieces: pieces(&[("b1", 5)]),
+                seal_status: SealStatus::Pending,
+                ..Default::default()
+            },
+        );
+
+        staged_state.sectors.insert(
+            3,
+            StagedSectorMetadata {
+                sector_id: 3,
+                pieces: pieces(&[("c1", 7), ("a3", 1)]),
+                seal_status: SealStatus::Failed("nope".to_string()),
+                ..Default::default()
+            },
+        );
+
+        sealed_state.sectors.insert(
+            1,
+            SealedSectorMetadata {
+                sector_id: 1,
+                pieces: pieces(&[("a1", 10), ("a2", 20)]),
+                ..Default::default()
+            },
+        );
+
+        (staged_state, sealed_state)
+    }
+
+    #[test]
+    fn test_get_piece_info() {
+        let (staged_state, sealed_state) = setup();
+        let index = build_piece_index(&staged_state, &sealed_state);
+
+        assert_eq!(index.len(), 5);
+
+        assert_eq!(
+            get_piece_info(&staged_state, &sealed_state, &index, "a2"),
+            Some(PieceInfo {
+                piece_key: "a2".to_string(),
+                sector_id: 1,
+                seal_status: PieceSealStatus::Sealed,
+                offset: 10,
+                num_bytes: UnpaddedBytesAmount(20),
+            })
+        );
+
+        assert_eq!(
+            get_piece_info(&staged_state, &sealed_state, &index, "a3")
+                .map(|info| (info.seal_status, info.offset)),
+            Some((PieceSealStatus::Failed, 7))
+        );
+
+        assert_eq!(
+            get_piece_info(&staged_state, &sealed_state, &index, "zz"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_list_pieces() {
+        let (staged_state, sealed_state) = setup();
+
+        let keys = |page: PiecePage| -> (Vec<String>, u64) {
+            (
+                page.pieces.into_iter().map(|info| info.piece_key).collect(),
+                page.total,
+            )
+        };
+
+        let all: PieceFilter = Default::default();
+
+        assert_eq!(
+            keys(list_pieces(&staged_state, &sealed_state, &all, 0, 100)),
+            (
+                vec!["a1", "a2", "b1", "c1", "a3"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                5
+            )
+        );
+
+        assert_eq!(
+            keys(list_pieces(&staged_state, &sealed_state, &all, 1, 2)),
+            (vec!["a2".to_string(), "b1".to_string()], 5)
+        );
+
+        let prefixed = PieceFilter {
+            piece_key_prefix: Some("a".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            keys(list_pieces(&staged_state, &sealed_state, &prefixed, 2, 10)),
+            (vec!["a3".to_string()], 3)
+        );
+
+        let failed_in_3 = PieceFilter {
+            sector_id: Some(3),
+            seal_status: Some(PieceSealStatus::Failed),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            keys(list_pieces(
+                &staged_state,
+                &sealed_state,
+                &failed_in_3,
+                0,
+                10
+            )),
+            (vec!["c1".to_string(), "a3".to_string()], 2)
+        );
+    }
+}
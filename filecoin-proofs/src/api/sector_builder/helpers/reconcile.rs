@@ -0,0 +1,149 @@
+use crate::api::sector_builder::metadata::SealStatus;
+use crate::api::sector_builder::metadata::SealedSectorHealth;
+use crate::api::sector_builder::state::SealedState;
+use crate::api::sector_builder::state::StagedState;
+use crate::api::sector_builder::SectorId;
+use sector_base::api::sector_access::SectorAccess;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Reconciliation {
+    // Staged sectors whose seal was interrupted and which must be sent to a
+    // sealer again.
+    pub to_reseal: Vec<SectorId>,
+
+    // Staged sectors whose seal was interrupted but whose staged sector access
+    // no longer exists. These have been marked as failed.
+    pub missing_staged: Vec<SectorId>,
+
+    // Sealed sectors whose replica no longer exists. These have been marked
+    // as ReplicaMissing.
+    pub missing_replicas: Vec<SectorId>,
+}
+
+// Reconciles state loaded from a snapshot with reality. No sealer is working
+// on a sector after a restart, so any staged sector which reports Sealing was
+// interrupted. Sealed sectors are checked for the presence of their replica.
+// The access_exists function reports whether a sector access is present.
+pub fn reconcile<F: Fn(&SectorAccess) -> bool>(
+    staged_state: &mut StagedState,
+    sealed_state: &mut SealedState,
+    access_exists: F,
+) -> Reconciliation {
+    let mut result: Reconciliation = Default::default();
+
+    for sector in staged_state.sectors.values_mut() {
+        if sector.seal_status != SealStatus::Sealing {
+            continue;
+        }
+
+        if access_exists(&sector.sector_access) {
+            result.to_reseal.push(sector.sector_id);
+        } else {
+            sector.seal_status = SealStatus::Failed(format!(
+                "seal was interrupted and staged sector access {} no longer exists",
+                sector.sector_access
+            ));
+            result.missing_staged.push(sector.sector_id);
+        }
+    }
+
+    for sector in sealed_state.sectors.values_mut() {
+        if access_exists(&sector.sector_access) {
+            // The replica may have reappeared, e.g. after a disk was remounted.
+            if sector.health == SealedSectorHealth::ReplicaMissing {
+                sector.health = SealedSectorHealth::Ok;
+            }
+        } else {
+            sector.health = SealedSectorHealth::ReplicaMissing;
+            result.missing_replicas.push(sector.sector_id);
+        }
+    }
+
+    result.to_reseal.sort();
+    result.missing_staged.sort();
+    result.missing_replicas.sort();
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::sector_builder::metadata::{SealedSectorMetadata, StagedSectorMetadata};
+
+    fn staged(sector_id: SectorId, seal_status: SealStatus) -> StagedSectorMetadata {
+        StagedSectorMetadata {
+            sector_id,
+            sector_access: SectorAccess::new("test", &format!("staged-{}", sector_id)),
+            seal_status,
+            ..Default::default()
+        }
+    }
+
+    fn sealed(sector_id: SectorId, health: SealedSectorHealth) -> SealedSectorMetadata {
+        SealedSectorMetadata {
+            sector_id,
+            sector_access: SectorAccess::new("test", &format!("sealed-{}", sector_id)),
+            health,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let mut staged_state: StagedState = Default::default();
+        let mut sealed_state: SealedState = Default::default();
+
+        staged_state
+            .sectors
+            .insert(1, staged(1, SealStatus::Pending));
+        staged_state
+            .sectors
+            .insert(2, staged(2, SealStatus::Sealing));
+        staged_state
+            .sectors
+            .insert(3, staged(3, SealStatus::Sealing));
+        staged_state
+            .sectors
+            .insert(4, staged(4, SealStatus::Failed("x".to_string())));
+
+        sealed_state
+            .sectors
+            .insert(5, sealed(5, SealedSectorHealth::Ok));
+        sealed_state
+            .sectors
+            .insert(6, sealed(6, SealedSectorHealth::Ok));
+        sealed_state
+            .sectors
+            .insert(7, sealed(7, SealedSectorHealth::ReplicaMissing));
+
+        let missing = ["staged-3", "sealed-6"];
+
+        let result = reconcile(&mut staged_state, &mut sealed_state, |access| {
+            !missing.contains(&access.path())
+        });
+
+        assert_eq!(
+            result,
+            Reconciliation {
+                to_reseal: vec![2],
+                missing_staged: vec![3],
+                missing_replicas: vec![6],
+            }
+        );
+
+        assert_eq!(staged_state.sectors[&1].seal_status, SealStatus::Pending);
+        assert_eq!(staged_state.sectors[&2].seal_status, SealStatus::Sealing);
+        match staged_state.sectors[&3].seal_status {
+            SealStatus::Failed(_) => (),
+            _ => panic!("should have been SealStatus::Failed"),
+        }
+
+        assert_eq!(sealed_state.sectors[&5].health, SealedSectorHealth::Ok);
+        assert_eq!(
+            sealed_state.sectors[&6].health,
+            SealedSectorHealth::ReplicaMissing
+        );
+        assert_eq!(sealed_state.sectors[&7].health, SealedSectorHealth::Ok);
+    }
+}
@@ -1,20 +1,194 @@
 use crate::api::internal;
+use crate::api::sector_builder::errors::err_range_out_of_bounds;
 use crate::api::sector_builder::errors::err_unrecov;
 use crate::api::sector_builder::metadata::sector_id_as_bytes;
 use crate::api::sector_builder::metadata::SealedSectorMetadata;
+use crate::api::sector_builder::unseal_cache::UnsealCache;
 use crate::api::sector_builder::WrappedSectorStore;
 use crate::error;
+use crate::metrics::METRICS;
 use sector_base::api::bytes_amount::UnpaddedBytesAmount;
-use std::path::PathBuf;
+use sector_base::api::sector_store::LockMode;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+// A range of bytes within a piece, relative to the start of the piece.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PieceRange {
+    pub piece_key: String,
+    pub offset: u64,
+    pub num_bytes: UnpaddedBytesAmount,
+
+    // If set, the unsealed bytes are written to this path instead of being
+    // returned.
+    pub output_path: Option<PathBuf>,
+}
+
 // Unseals and returns the piece-bytes for the first sector found containing
 // a piece with matching key.
 pub fn retrieve_piece<'a>(
     sector_store: &Arc<WrappedSectorStore>,
+    unseal_cache: Option<&UnsealCache>,
     sealed_sector: &SealedSectorMetadata,
     prover_id: &[u8; 31],
     piece_key: &'a str,
+) -> error::Result<Vec<u8>> {
+    let (start_offset, num_bytes) = find_piece(sealed_sector, piece_key)?;
+
+    let cached = open_cached_sector(sector_store, unseal_cache, sealed_sector, prover_id)?;
+    if let Some(file) = cached {
+        return read_range(file, start_offset, num_bytes);
+    }
+
+    unseal_to_vec(
+        sector_store,
+        sealed_sector,
+        prover_id,
+        start_offset,
+        num_bytes,
+    )
+}
+
+// Unseals a range of bytes from a piece. The bytes are returned, or written
+// to the range's output path (in which case an empty vector is returned).
+pub fn retrieve_piece_range(
+    sector_store: &Arc<WrappedSectorStore>,
+    unseal_cache: Option<&UnsealCache>,
+    sealed_sector: &SealedSectorMetadata,
+    prover_id: &[u8; 31],
+    range: &PieceRange,
+) -> error::Result<Vec<u8>> {
+    let (piece_start, piece_num_bytes) = find_piece(sealed_sector, &range.piece_key)?;
+
+    let start_offset = absolute_offset(piece_start, piece_num_bytes, range)?;
+
+    let cached = open_cached_sector(sector_store, unseal_cache, sealed_sector, prover_id)?;
+    if let Some(file) = cached {
+        return match &range.output_path {
+            Some(output_path) => {
+                copy_range(file, start_offset, range.num_bytes, output_path)?;
+                Ok(Vec::new())
+            }
+            None => read_range(file, start_offset, range.num_bytes),
+        };
+    }
+
+    if let Some(output_path) = &range.output_path {
+        unseal_to_path(
+            sector_store,
+            sealed_sector,
+            prover_id,
+            start_offset,
+            range.num_bytes,
+            output_path,
+        )?;
+
+        Ok(Vec::new())
+    } else {
+        unseal_to_vec(
+            sector_store,
+            sealed_sector,
+            prover_id,
+            start_offset,
+            range.num_bytes,
+        )
+    }
+}
+
+// Opens the cached unsealed copy of the sealed sector, unsealing the whole
+// sector into the cache first if it isn't there. Returns None if there is no
+// cache or if the sector is too large to be cached.
+fn open_cached_sector(
+    sector_store: &Arc<WrappedSectorStore>,
+    unseal_cache: Option<&UnsealCache>,
+    sealed_sector: &SealedSectorMetadata,
+    prover_id: &[u8; 31],
+) -> error::Result<Option<File>> {
+    let cache = match unseal_cache {
+        Some(cache) => cache,
+        None => return Ok(None),
+    };
+
+    if let Some(file) = cache.get(prover_id, sealed_sector.sector_id) {
+        METRICS.record_unseal_cache_lookup(true);
+        return Ok(Some(file));
+    }
+
+    METRICS.record_unseal_cache_lookup(false);
+
+    let num_bytes = sealed_sector
+        .pieces
+        .iter()
+        .fold(UnpaddedBytesAmount(0), |acc, p| acc + p.num_bytes);
+
+    if !cache.fits(u64::from(num_bytes)) {
+        return Ok(None);
+    }
+
+    let temp_path = cache.temp_path();
+
+    if let Err(err) = unseal_to_path(
+        sector_store,
+        sealed_sector,
+        prover_id,
+        0,
+        num_bytes,
+        &temp_path,
+    ) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+
+    cache
+        .insert(prover_id, sealed_sector.sector_id, &temp_path)
+        .map(Some)
+}
+
+fn read_range(
+    mut file: File,
+    start_offset: u64,
+    num_bytes: UnpaddedBytesAmount,
+) -> error::Result<Vec<u8>> {
+    let mut buf = vec![0; u64::from(num_bytes) as usize];
+
+    file.seek(SeekFrom::Start(start_offset))?;
+    file.read_exact(&mut buf)?;
+
+    Ok(buf)
+}
+
+fn copy_range(
+    mut file: File,
+    start_offset: u64,
+    num_bytes: UnpaddedBytesAmount,
+    output_path: &Path,
+) -> error::Result<()> {
+    file.seek(SeekFrom::Start(start_offset))?;
+
+    let mut output = File::create(output_path)?;
+    let num_bytes_copied = io::copy(&mut file.take(u64::from(num_bytes)), &mut output)?;
+
+    if num_bytes_copied != u64::from(num_bytes) {
+        let s = format!(
+            "expected to copy {} bytes, but copied {} bytes",
+            u64::from(num_bytes),
+            num_bytes_copied
+        );
+
+        return Err(err_unrecov(s).into());
+    }
+
+    Ok(())
+}
+
+fn unseal_to_vec(
+    sector_store: &Arc<WrappedSectorStore>,
+    sealed_sector: &SealedSectorMetadata,
+    prover_id: &[u8; 31],
+    start_offset: u64,
+    num_bytes: UnpaddedBytesAmount,
 ) -> error::Result<Vec<u8>> {
     let staging_sector_access = sector_store
         .inner
@@ -22,45 +196,70 @@ pub fn retrieve_piece<'a>(
         .new_staging_sector_access()
         .map_err(failure::Error::from)?;
 
-    let result = retrieve_piece_aux(
-        sector_store,
-        sealed_sector,
-        prover_id,
-        piece_key,
-        &staging_sector_access,
-    );
+    let result = sector_store
+        .inner
+        .manager()
+        .local_path(&staging_sector_access)
+        .map_err(failure::Error::from)
+        .and_then(|staging_path| {
+            // released before the staging sector is read and deleted
+            let _lock = sector_store
+                .inner
+                .manager()
+                .lock_sector(&staging_sector_access, LockMode::Exclusive)?;
 
-    if result.is_ok() {
-        sector_store
-            .inner
-            .manager()
-            .delete_staging_sector_access(&staging_sector_access)?;
-    }
+            unseal_to_path(
+                sector_store,
+                sealed_sector,
+                prover_id,
+                start_offset,
+                num_bytes,
+                &staging_path,
+            )
+        })
+        .and_then(|num_bytes_unsealed| {
+            sector_store
+                .inner
+                .manager()
+                .read_raw(&staging_sector_access, 0, num_bytes_unsealed)
+                .map_err(failure::Error::from)
+        });
 
-    let (_, bytes) = result?;
+    // The staging sector (and its reservation) is deleted whether or not
+    // unsealing succeeded. A failure to delete it is reported only if
+    // unsealing succeeded, so that it cannot hide why unsealing failed.
+    let deleted = sector_store
+        .inner
+        .manager()
+        .delete_staging_sector_access(&staging_sector_access);
+
+    let bytes = result?;
+
+    deleted?;
 
     Ok(bytes)
 }
 
-fn retrieve_piece_aux<'a>(
+fn unseal_to_path(
     sector_store: &Arc<WrappedSectorStore>,
     sealed_sector: &SealedSectorMetadata,
     prover_id: &[u8; 31],
-    piece_key: &'a str,
-    staging_sector_access: &'a str,
-) -> error::Result<(UnpaddedBytesAmount, Vec<u8>)> {
-    let (start_offset, num_bytes) = piece_pos(&sealed_sector, piece_key).ok_or_else(|| {
-        let msg = format!(
-            "piece {} not found in sector {}",
-            piece_key, &sealed_sector.sector_id
-        );
-        err_unrecov(msg)
-    })?;
+    start_offset: u64,
+    num_bytes: UnpaddedBytesAmount,
+    output_path: &Path,
+) -> error::Result<UnpaddedBytesAmount> {
+    let manager = sector_store.inner.manager();
+
+    // Shared, so that the sector can be unsealed by several workers at once
+    // but not (e.g.) re-imported by another process meanwhile.
+    let _lock = manager.lock_sector(&sealed_sector.sector_access, LockMode::Shared)?;
+
+    let sealed_path = manager.local_path(&sealed_sector.sector_access)?;
 
     let num_bytes_unsealed = internal::get_unsealed_range(
         (*sector_store.inner).config(),
-        &PathBuf::from(sealed_sector.sector_access.clone()),
-        &PathBuf::from(staging_sector_access),
+        &sealed_path,
+        &output_path.to_path_buf(),
         prover_id,
         &sector_id_as_bytes(sealed_sector.sector_id)?,
         start_offset,
@@ -77,13 +276,42 @@ fn retrieve_piece_aux<'a>(
         return Err(err_unrecov(s).into());
     }
 
-    let piece_bytes = sector_store.inner.manager().read_raw(
-        &staging_sector_access.to_string(),
-        0,
-        num_bytes_unsealed,
-    )?;
+    Ok(num_bytes_unsealed)
+}
 
-    Ok((num_bytes_unsealed, piece_bytes))
+fn find_piece(
+    sealed_sector: &SealedSectorMetadata,
+    piece_key: &str,
+) -> error::Result<(u64, UnpaddedBytesAmount)> {
+    piece_pos(&sealed_sector, piece_key).ok_or_else(|| {
+        let msg = format!(
+            "piece {} not found in sector {}",
+            piece_key, &sealed_sector.sector_id
+        );
+        err_unrecov(msg).into()
+    })
+}
+
+// Translates a range relative to the start of a piece into an offset relative
+// to the start of the sector. Produces an error if the range extends beyond
+// the end of the piece.
+fn absolute_offset(
+    piece_start: u64,
+    piece_num_bytes: UnpaddedBytesAmount,
+    range: &PieceRange,
+) -> error::Result<u64> {
+    let range_end = range.offset.checked_add(u64::from(range.num_bytes));
+
+    match range_end {
+        Some(end) if end <= u64::from(piece_num_bytes) => Ok(piece_start + range.offset),
+        _ => Err(err_range_out_of_bounds(
+            range.piece_key.clone(),
+            range.offset,
+            u64::from(range.num_bytes),
+            u64::from(piece_num_bytes),
+        )
+        .into()),
+    }
 }
 
 // Returns a tuple of piece bytes-offset and number-of-bytes in piece if the
@@ -128,16 +356,19 @@ mod tests {
         sealed_sector.pieces.push(PieceMetadata {
             piece_key: String::from("x"),
             num_bytes: UnpaddedBytesAmount(5),
+            checksum: None,
         });
 
         sealed_sector.pieces.push(PieceMetadata {
             piece_key: String::from("y"),
             num_bytes: UnpaddedBytesAmount(30),
+            checksum: None,
         });
 
         sealed_sector.pieces.push(PieceMetadata {
             piece_key: String::from("z"),
             num_bytes: UnpaddedBytesAmount(100),
+            checksum: None,
         });
 
         match piece_pos(&sealed_sector, "x") {
@@ -155,4 +386,33 @@ mod tests {
             None => panic!(),
         }
     }
+
+    #[test]
+    fn test_absolute_offset() {
+        let range = |offset, num_bytes| PieceRange {
+            piece_key: String::from("y"),
+            offset,
+            num_bytes: UnpaddedBytesAmount(num_bytes),
+            output_path: None,
+        };
+
+        let piece_num_bytes = UnpaddedBytesAmount(30);
+
+        assert_eq!(
+            absolute_offset(5, piece_num_bytes, &range(0, 30)).unwrap(),
+            5
+        );
+        assert_eq!(
+            absolute_offset(5, piece_num_bytes, &range(10, 20)).unwrap(),
+            15
+        );
+        assert_eq!(
+            absolute_offset(5, piece_num_bytes, &range(30, 0)).unwrap(),
+            35
+        );
+
+        assert!(absolute_offset(5, piece_num_bytes, &range(10, 21)).is_err());
+        assert!(absolute_offset(5, piece_num_bytes, &range(31, 0)).is_err());
+        assert!(absolute_offset(5, piece_num_bytes, &range(u64::max_value(), 1)).is_err());
+    }
 }
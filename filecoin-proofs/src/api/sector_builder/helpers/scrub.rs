@@ -0,0 +1,95 @@
+use crate::api::internal;
+use crate::api::sector_builder::metadata::sector_id_as_bytes;
+use crate::api::sector_builder::metadata::SealedSectorHealth;
+use crate::api::sector_builder::metadata::SealedSectorMetadata;
+use crate::api::sector_builder::state::SealedState;
+use crate::api::sector_builder::SectorId;
+use crate::api::sector_builder::WrappedSectorStore;
+use crate::error;
+use sector_base::api::sector_store::LockMode;
+use std::sync::Arc;
+
+// Checks the integrity of a sealed sector's replica by rebuilding its merkle
+// tree and comparing the root with the stored comm_r. If verify_proof is set,
+// the stored snark_proof is verified, too. The replica is locked while it is
+// checked, so that another process sharing its root cannot replace or delete
+// it meanwhile.
+pub fn scrub(
+    sector_store: &Arc<WrappedSectorStore>,
+    prover_id: &[u8; 31],
+    sealed_sector: &SealedSectorMetadata,
+    verify_proof: bool,
+) -> error::Result<SealedSectorHealth> {
+    let manager = sector_store.inner.manager();
+
+    if !manager.sector_exists(&sealed_sector.sector_access)? {
+        return Ok(SealedSectorHealth::ReplicaMissing);
+    }
+
+    let _lock = manager.lock_sector(&sealed_sector.sector_access, LockMode::Shared)?;
+
+    let sealed_path = manager.local_path(&sealed_sector.sector_access)?;
+
+    let comm_r = internal::compute_comm_r((*sector_store.inner).config(), &sealed_path)?;
+
+    if comm_r != sealed_sector.comm_r {
+        return Ok(SealedSectorHealth::CommRMismatch);
+    }
+
+    if verify_proof {
+        let is_valid = internal::verify_seal(
+            (*sector_store.inner).config(),
+            sealed_sector.comm_r,
+            sealed_sector.comm_d,
+            sealed_sector.comm_r_star,
+            prover_id,
+            &sector_id_as_bytes(sealed_sector.sector_id)?,
+            &sealed_sector.snark_proof,
+        )?;
+
+        if !is_valid {
+            return Ok(SealedSectorHealth::ProofInvalid);
+        }
+    }
+
+    Ok(SealedSectorHealth::Ok)
+}
+
+// Returns the id of the sealed sector which has gone the longest without being
+// scrubbed, or None if there are no sealed sectors.
+pub fn next_scrub_candidate(sealed_state: &SealedState) -> Option<SectorId> {
+    sealed_state
+        .sectors
+        .values()
+        .min_by_key(|sector| (sector.last_scrubbed_at, sector.sector_id))
+        .map(|sector| sector.sector_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_scrub_candidate() {
+        let mut sealed_state: SealedState = Default::default();
+
+        assert_eq!(next_scrub_candidate(&sealed_state), None);
+
+        for (sector_id, last_scrubbed_at) in &[(1, 300), (2, 100), (3, 100), (4, 200)] {
+            sealed_state.sectors.insert(
+                *sector_id,
+                SealedSectorMetadata {
+                    sector_id: *sector_id,
+                    last_scrubbed_at: *last_scrubbed_at,
+                    ..Default::default()
+                },
+            );
+        }
+
+        assert_eq!(next_scrub_candidate(&sealed_state), Some(2));
+
+        sealed_state.sectors.get_mut(&2).unwrap().last_scrubbed_at = 400;
+
+        assert_eq!(next_scrub_candidate(&sealed_state), Some(3));
+    }
+}
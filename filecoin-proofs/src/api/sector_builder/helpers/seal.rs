@@ -1,40 +1,61 @@
 use crate::api::internal::seal as seal_internal;
 use crate::api::internal::SealOutput;
+use crate::api::sector_builder::errors::err_piece_checksum_mismatch;
 use crate::api::sector_builder::metadata::sector_id_as_bytes;
 use crate::api::sector_builder::metadata::SealedSectorMetadata;
 use crate::api::sector_builder::metadata::StagedSectorMetadata;
 use crate::api::sector_builder::WrappedSectorStore;
 use crate::error;
-use std::path::PathBuf;
+use blake2::{Blake2s, Digest};
+use sector_base::api::sector_access::SectorAccess;
+use sector_base::api::sector_store::LockMode;
+use sector_base::io::fr32::Fr32Reader;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
 use std::sync::Arc;
 
+// Seals the staged sector into the sealed sector identified by
+// sealed_sector_access, which the scheduler provisioned when it enqueued the
+// seal.
 pub fn seal(
     sector_store: &Arc<WrappedSectorStore>,
     prover_id: &[u8; 31],
     staged_sector: StagedSectorMetadata,
+    sealed_sector_access: SectorAccess,
 ) -> error::Result<SealedSectorMetadata> {
-    // Provision a new sealed sector access through the manager.
-    let sealed_sector_access = sector_store
-        .inner
-        .manager()
-        .new_sealed_sector_access()
-        .map_err(failure::Error::from)?;
+    let manager = sector_store.inner.manager();
 
     // Run the FPS seal operation. This call will block for a long time, so make
-    // sure you're not holding any locks.
+    // sure you're not holding any (in-process) locks.
+    let result = seal_locked(
+        sector_store,
+        prover_id,
+        &staged_sector,
+        &sealed_sector_access,
+    );
 
+    // A failed seal leaves a partial replica (and perhaps its merkle tree),
+    // which are deleted along with the sealed sector's reservation, so that a
+    // retried seal provisions a sealed sector of the same name in their place.
+    // A failure to delete them is not reported, so that it cannot hide why
+    // sealing failed.
     let SealOutput {
         comm_r,
         comm_d,
         comm_r_star,
         snark_proof,
-    } = seal_internal(
-        (*sector_store.inner).config(),
-        &PathBuf::from(staged_sector.sector_access.clone()),
-        &PathBuf::from(sealed_sector_access.clone()),
-        prover_id,
-        &sector_id_as_bytes(staged_sector.sector_id)?,
-    )?;
+    } = result.map_err(|err| {
+        let _ = manager.delete_sealed_sector_access(&sealed_sector_access);
+        err
+    })?;
+
+    // The temporary files written while sealing are gone and the sealed
+    // sector will not grow any further.
+    manager.release_reservation(&sealed_sector_access)?;
+
+    // Once sealed, the staged sector receives no more data.
+    manager.release_reservation(&staged_sector.sector_access)?;
 
     let newly_sealed_sector = SealedSectorMetadata {
         sector_id: staged_sector.sector_id,
@@ -44,7 +65,236 @@ pub fn seal(
         comm_r,
         comm_d,
         snark_proof,
+        health: Default::default(),
+        last_scrubbed_at: 0,
     };
 
     Ok(newly_sealed_sector)
 }
+
+// Seals while holding sector locks, so that another process sharing the
+// sector roots can neither write to the staged sector nor touch the sealed
+// sector until sealing is done.
+fn seal_locked(
+    sector_store: &Arc<WrappedSectorStore>,
+    prover_id: &[u8; 31],
+    staged_sector: &StagedSectorMetadata,
+    sealed_sector_access: &SectorAccess,
+) -> error::Result<SealOutput> {
+    let manager = sector_store.inner.manager();
+
+    let _staged_lock = manager.lock_sector(&staged_sector.sector_access, LockMode::Shared)?;
+    let _sealed_lock = manager.lock_sector(sealed_sector_access, LockMode::Exclusive)?;
+
+    // The sealer reads the staged sector's local file, bypassing any checksum
+    // kept by its root, so the sector is first checked against the checksum
+    // recorded when it was last closed, e.g. by an earlier attempt to seal it.
+    manager.verify_sector(&staged_sector.sector_access)?;
+
+    // The staged sector receives no more data, so its writes are made durable
+    // (and, on checksumming roots, its checksum recorded) before it is read.
+    manager.close_sector(&staged_sector.sector_access)?;
+
+    let staged_path = manager.local_path(&staged_sector.sector_access)?;
+    let sealed_path = manager.local_path(sealed_sector_access)?;
+
+    verify_pieces(&staged_path, staged_sector)?;
+
+    let mut tree_bytes = Vec::new();
+
+    let output = seal_internal(
+        (*sector_store.inner).config(),
+        &staged_path,
+        &sealed_path,
+        &mut tree_bytes,
+        prover_id,
+        &sector_id_as_bytes(staged_sector.sector_id)?,
+    )?;
+
+    // The replica's merkle tree is kept alongside it, in the space reserved
+    // for sealing, and deleted with it.
+    manager.write_sector_tree(sealed_sector_access, &tree_bytes)?;
+
+    Ok(output)
+}
+
+// Checks each piece of the staged sector held at staged_path against the
+// checksum recorded when it was added, so that a sector corrupted while it
+// waited to be sealed is not sealed (and proven) as though it were intact.
+// Produces an error naming the first piece which does not match, or within
+// which the staged sector ends. Pieces are streamed through the hasher rather
+// than read into memory, since a piece may be as large as the sector.
+fn verify_pieces(staged_path: &Path, staged_sector: &StagedSectorMetadata) -> error::Result<()> {
+    let mut reader = Fr32Reader::new(BufReader::new(File::open(staged_path)?));
+
+    for piece in &staged_sector.pieces {
+        let mut hasher = Blake2s::new();
+
+        let num_bytes_read = io::copy(
+            &mut (&mut reader).take(u64::from(piece.num_bytes)),
+            &mut hasher,
+        )?;
+
+        let intact = num_bytes_read == u64::from(piece.num_bytes)
+            && match piece.checksum {
+                Some(checksum) => checksum[..] == hasher.result()[..],
+                None => true,
+            };
+
+        if !intact {
+            return Err(err_piece_checksum_mismatch(
+                staged_sector.sector_id,
+                piece.piece_key.clone(),
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::sector_builder::errors::SectorBuilderErr;
+    use crate::api::sector_builder::metadata::{piece_checksum, PieceMetadata};
+    use sector_base::api::bytes_amount::UnpaddedBytesAmount;
+    use sector_base::api::disk_backed_storage::{new_sector_store, ConfiguredStore};
+    use std::fs::OpenOptions;
+    use std::io::{Seek, SeekFrom, Write};
+
+    #[test]
+    fn test_seal_failure_deletes_sealed_sector() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staging_dir = tempfile::tempdir().unwrap();
+
+        let sector_store = Arc::new(WrappedSectorStore {
+            inner: Box::new(new_sector_store(
+                &ConfiguredStore::Test,
+                sealed_dir.path().to_str().unwrap().to_owned(),
+                staging_dir.path().to_str().unwrap().to_owned(),
+            )),
+        });
+
+        let manager = sector_store.inner.manager();
+
+        let mut staged_sector = StagedSectorMetadata {
+            sector_id: 1,
+            sector_access: manager.new_staging_sector_access_for(&[0; 31], 1).unwrap(),
+            ..Default::default()
+        };
+
+        manager
+            .write_and_preprocess(&staged_sector.sector_access, &[1; 100])
+            .unwrap();
+
+        // a mismatched checksum fails the seal before the replica is written
+        staged_sector.pieces.push(PieceMetadata {
+            piece_key: "a".to_string(),
+            num_bytes: UnpaddedBytesAmount(100),
+            checksum: Some(piece_checksum(&[2; 100])),
+        });
+
+        let sealed_sector_access = manager.new_sealed_sector_access_for(&[0; 31], 1).unwrap();
+        manager
+            .write_sector_tree(&sealed_sector_access, &[0; 32])
+            .unwrap();
+
+        assert!(seal(
+            &sector_store,
+            &[0; 31],
+            staged_sector,
+            sealed_sector_access.clone()
+        )
+        .is_err());
+
+        assert!(!manager.sector_exists(&sealed_sector_access).unwrap());
+        assert_eq!(
+            manager.sector_tree_path(&sealed_sector_access).unwrap(),
+            None
+        );
+
+        // a retried seal is provisioned a sealed sector of the same name
+        assert_eq!(
+            manager.new_sealed_sector_access_for(&[0; 31], 1).unwrap(),
+            sealed_sector_access
+        );
+    }
+
+    #[test]
+    fn test_verify_pieces() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staging_dir = tempfile::tempdir().unwrap();
+
+        let sector_store = new_sector_store(
+            &ConfiguredStore::Test,
+            sealed_dir.path().to_str().unwrap().to_owned(),
+            staging_dir.path().to_str().unwrap().to_owned(),
+        );
+
+        let manager = sector_store.manager();
+        let sector_access = manager.new_staging_sector_access_for(&[0; 31], 1).unwrap();
+
+        let mut staged_sector = StagedSectorMetadata {
+            sector_id: 1,
+            sector_access,
+            ..Default::default()
+        };
+
+        for (piece_key, piece_bytes) in &[("a", vec![1u8; 100]), ("b", vec![2u8; 50])] {
+            manager
+                .write_and_preprocess(&staged_sector.sector_access, piece_bytes)
+                .unwrap();
+
+            staged_sector.pieces.push(PieceMetadata {
+                piece_key: piece_key.to_string(),
+                num_bytes: UnpaddedBytesAmount(piece_bytes.len() as u64),
+                checksum: Some(piece_checksum(piece_bytes)),
+            });
+        }
+
+        let staged_path = manager.local_path(&staged_sector.sector_access).unwrap();
+
+        verify_pieces(&staged_path, &staged_sector).unwrap();
+
+        // flip a bit of piece b, which starts 100 unpadded bytes in
+        {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&staged_path)
+                .unwrap();
+
+            let mut byte = [0u8; 1];
+            file.seek(SeekFrom::Start(140)).unwrap();
+            file.read_exact(&mut byte).unwrap();
+            file.seek(SeekFrom::Start(140)).unwrap();
+            file.write_all(&[byte[0] ^ 1]).unwrap();
+        }
+
+        let err = verify_pieces(&staged_path, &staged_sector).unwrap_err();
+        match err.downcast_ref() {
+            Some(SectorBuilderErr::PieceChecksumMismatch {
+                sector_id: 1,
+                piece_key,
+            }) => assert_eq!(piece_key, "b"),
+            _ => panic!("unexpected error: {:?}", err),
+        }
+
+        // pieces added before checksums were recorded are not verified
+        staged_sector.pieces[1].checksum = None;
+        verify_pieces(&staged_path, &staged_sector).unwrap();
+
+        // unless the staged sector ends within them
+        staged_sector.pieces[1].num_bytes = UnpaddedBytesAmount(1000);
+
+        let err = verify_pieces(&staged_path, &staged_sector).unwrap_err();
+        match err.downcast_ref() {
+            Some(SectorBuilderErr::PieceChecksumMismatch {
+                sector_id: 1,
+                piece_key,
+            }) => assert_eq!(piece_key, "b"),
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    }
+}
@@ -1,24 +1,52 @@
+use crate::api::sector_builder::errors::err_locked;
 use crate::api::sector_builder::kv_store::KeyValueStore;
 use crate::error::Result;
 use blake2::{Blake2b, Digest};
+use fs2::FileExt;
 use std::fs::{self, File, OpenOptions};
 use std::io::{ErrorKind, Read, Write};
 use std::path::{Path, PathBuf};
 
 const FATAL_NOCREATE: &str = "[KeyValueStore#put] could not create path";
 
+// The file in the root directory on which the store holds an exclusive lock.
+const LOCK_FILE: &str = ".lock";
+
 // FileSystemKvs is a file system-backed key/value store, mostly lifted from
 // sile/ekvsb
 pub struct FileSystemKvs {
     root_dir: PathBuf,
+
+    // holds the lock on the root directory for as long as the store lives
+    _lock: File,
 }
 
 impl FileSystemKvs {
+    // Initializes a store in root_dir, which no other store (e.g. one used by
+    // a SectorBuilder in another process) may use until this one is dropped.
+    // Produces a Locked error if another store is using it.
     pub fn initialize<P: AsRef<Path>>(root_dir: P) -> Result<Self> {
         fs::create_dir_all(&root_dir)?;
 
+        let lock = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(root_dir.as_ref().join(LOCK_FILE))?;
+
+        match lock.try_lock_exclusive() {
+            Ok(()) => (),
+            Err(ref err) if err.kind() == fs2::lock_contended_error().kind() => {
+                Err(err_locked(format!(
+                    "metadata directory {}",
+                    root_dir.as_ref().display()
+                )))?;
+            }
+            Err(err) => Err(err)?,
+        }
+
         Ok(FileSystemKvs {
             root_dir: root_dir.as_ref().to_path_buf(),
+            _lock: lock,
         })
     }
 
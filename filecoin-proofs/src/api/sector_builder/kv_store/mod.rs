@@ -9,6 +9,7 @@ pub trait KeyValueStore {
 
 #[cfg(test)]
 mod tests {
+    use crate::api::sector_builder::errors::SectorBuilderErr;
     use crate::api::sector_builder::kv_store::fs::FileSystemKvs;
     use crate::api::sector_builder::kv_store::KeyValueStore;
 
@@ -29,4 +30,25 @@ mod tests {
         let opt = db.get(k_a).unwrap();
         assert_eq!(format!("{:x?}", opt.unwrap()), format!("{:x?}", v_a));
     }
+
+    #[test]
+    fn test_exclusive() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        let db = FileSystemKvs::initialize(metadata_dir.path()).unwrap();
+        db.put(b"key", b"value").unwrap();
+
+        match FileSystemKvs::initialize(metadata_dir.path()) {
+            Err(err) => match err.downcast_ref() {
+                Some(SectorBuilderErr::Locked(_)) => (),
+                _ => panic!("unexpected error: {:?}", err),
+            },
+            Ok(_) => panic!("metadata directory was not locked"),
+        }
+
+        drop(db);
+
+        let db = FileSystemKvs::initialize(metadata_dir.path()).unwrap();
+        assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+    }
 }
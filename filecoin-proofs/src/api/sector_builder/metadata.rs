@@ -1,24 +1,50 @@
+use crate::api::sector_builder::retry::SealErrorClass;
 use crate::api::sector_builder::SectorId;
 use crate::error;
 use crate::serde_big_array::BigArray;
+use blake2::{Blake2s, Digest};
 use byteorder::LittleEndian;
 use byteorder::WriteBytesExt;
 use sector_base::api::bytes_amount::UnpaddedBytesAmount;
+use sector_base::api::sector_access::SectorAccess;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct StagedSectorMetadata {
     pub sector_id: SectorId,
-    pub sector_access: String,
+    pub sector_access: SectorAccess,
     pub pieces: Vec<PieceMetadata>,
     pub seal_status: SealStatus,
+
+    // Failed seal attempts, oldest first. Defaults to empty for snapshots
+    // written before attempts were recorded.
+    #[serde(default)]
+    pub seal_attempts: Vec<SealAttempt>,
+
+    // Seals of sectors with a higher priority are started first. Defaults to
+    // zero for snapshots written before priorities existed.
+    #[serde(default)]
+    pub seal_priority: u8,
+
+    // The sealed sector provisioned for the sector's seal while it is being
+    // sealed, so that a seal interrupted by a restart is resumed into it
+    // rather than leaving it behind. Defaults to none for snapshots written
+    // before it was recorded.
+    #[serde(default)]
+    pub sealed_sector_access: Option<SectorAccess>,
+
+    // Seconds since the Unix epoch at which the sector's failed seal is
+    // retried automatically, if the retry policy retries it. Defaults to none
+    // for snapshots written before retries were recorded.
+    #[serde(default)]
+    pub seal_retry_at: Option<u64>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SealedSectorMetadata {
     pub sector_id: SectorId,
-    pub sector_access: String,
+    pub sector_access: SectorAccess,
     pub pieces: Vec<PieceMetadata>,
     pub comm_r_star: [u8; 32],
     pub comm_r: [u8; 32],
@@ -26,12 +52,46 @@ pub struct SealedSectorMetadata {
 
     #[serde(with = "BigArray")]
     pub snark_proof: [u8; 384],
+
+    #[serde(default)]
+    pub health: SealedSectorHealth,
+
+    // Seconds since the Unix epoch at which the replica was last scrubbed,
+    // or 0 if it has never been scrubbed.
+    #[serde(default)]
+    pub last_scrubbed_at: u64,
+}
+
+// Result of the most recent check of a sealed sector's replica.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum SealedSectorHealth {
+    Ok,
+    // The replica referenced by sector_access could not be found.
+    ReplicaMissing,
+    // The merkle root of the replica does not match the stored comm_r.
+    CommRMismatch,
+    // The stored snark_proof does not verify.
+    ProofInvalid,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct PieceMetadata {
     pub piece_key: String,
     pub num_bytes: UnpaddedBytesAmount,
+
+    // Checksum of the piece's bytes (see piece_checksum), which is verified
+    // before the piece's sector is sealed. None for pieces added before
+    // checksums were recorded, which are not verified.
+    #[serde(default)]
+    pub checksum: Option<[u8; 32]>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct SealAttempt {
+    // Seconds since the Unix epoch at which the attempt failed.
+    pub failed_at: u64,
+    pub error_class: SealErrorClass,
+    pub error: String,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
@@ -51,6 +111,8 @@ impl PartialEq for SealedSectorMetadata {
             && self.comm_r == other.comm_r
             && self.comm_d == other.comm_d
             && self.snark_proof.iter().eq(other.snark_proof.iter())
+            && self.health == other.health
+            && self.last_scrubbed_at == other.last_scrubbed_at
     }
 }
 
@@ -61,13 +123,17 @@ impl Default for StagedSectorMetadata {
             sector_access: Default::default(),
             pieces: Default::default(),
             seal_status: SealStatus::Pending,
+            seal_attempts: Default::default(),
+            seal_priority: Default::default(),
+            sealed_sector_access: None,
+            seal_retry_at: None,
         }
     }
 }
 
 impl fmt::Debug for SealedSectorMetadata {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "SealedSectorMetadata {{ sector_id: {}, sector_access: {}, pieces: {:?}, comm_r_star: {:?}, comm_r: {:?}, comm_d: {:?} }}", self.sector_id, self.sector_access, self.pieces, self.comm_r_star, self.comm_r, self.comm_d)
+        write!(f, "SealedSectorMetadata {{ sector_id: {}, sector_access: {}, pieces: {:?}, comm_r_star: {:?}, comm_r: {:?}, comm_d: {:?}, health: {:?}, last_scrubbed_at: {} }}", self.sector_id, self.sector_access, self.pieces, self.comm_r_star, self.comm_r, self.comm_d, self.health, self.last_scrubbed_at)
     }
 }
 
@@ -81,16 +147,32 @@ impl Default for SealedSectorMetadata {
             comm_r: Default::default(),
             comm_d: Default::default(),
             snark_proof: [0; 384],
+            health: Default::default(),
+            last_scrubbed_at: 0,
         }
     }
 }
 
+impl Default for SealedSectorHealth {
+    fn default() -> SealedSectorHealth {
+        SealedSectorHealth::Ok
+    }
+}
+
 pub fn sum_piece_bytes(s: &StagedSectorMetadata) -> UnpaddedBytesAmount {
     s.pieces
         .iter()
         .fold(UnpaddedBytesAmount(0), |acc, x| acc + x.num_bytes)
 }
 
+// Returns the checksum recorded for a piece with the provided bytes.
+pub fn piece_checksum(piece_bytes: &[u8]) -> [u8; 32] {
+    let mut checksum = [0u8; 32];
+    checksum.copy_from_slice(&Blake2s::digest(piece_bytes));
+
+    checksum
+}
+
 pub fn sector_id_as_bytes(sector_id: SectorId) -> error::Result<[u8; 31]> {
     // Transmute a u64 sector id to a zero-padded byte array.
     let mut sector_id_as_bytes = [0u8; 31];
@@ -1,55 +1,172 @@
 use slog::*;
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::api::internal::PoStOutput;
+use crate::api::sector_builder::errors::err_duplicate_prover;
+use crate::api::sector_builder::errors::err_post_deadline;
+use crate::api::sector_builder::errors::err_unrecov;
 use crate::api::sector_builder::errors::SectorBuilderErr;
+pub use crate::api::sector_builder::helpers::pieces::{
+    PieceFilter, PieceInfo, PiecePage, PieceSealStatus,
+};
+use crate::api::sector_builder::helpers::retrieve_piece::PieceRange;
 use crate::api::sector_builder::kv_store::fs::FileSystemKvs;
 use crate::api::sector_builder::kv_store::KeyValueStore;
 use crate::api::sector_builder::metadata::*;
+use crate::api::sector_builder::post_worker::{PoStWorker, PoStWorkerInput};
+use crate::api::sector_builder::retry::SealRetryPolicy;
 use crate::api::sector_builder::scheduler::Request;
 use crate::api::sector_builder::scheduler::Scheduler;
+use crate::api::sector_builder::scrubber::ScrubPolicy;
+use crate::api::sector_builder::scrubber::ScrubberWorker;
 use crate::api::sector_builder::sealer::*;
+use crate::api::sector_builder::sealer_queue::SealerQueue;
+use crate::api::sector_builder::unseal_cache::UnsealCache;
+use crate::api::sector_builder::unseal_cache::UnsealCacheConfig;
 use crate::error::ExpectWithBacktrace;
 use crate::error::Result;
+use crate::metrics::MetricsSnapshot;
 use crate::FCP_LOG;
 use sector_base::api::bytes_amount::UnpaddedBytesAmount;
+use sector_base::api::disk_backed_storage::new_checksummed_sector_store;
 use sector_base::api::disk_backed_storage::new_sector_store;
 use sector_base::api::disk_backed_storage::ConfiguredStore;
+use sector_base::api::placement::PlacementPolicy;
 use sector_base::api::sector_store::SectorStore;
 
 pub mod errors;
 mod helpers;
 mod kv_store;
 pub mod metadata;
+mod post_worker;
+pub mod retry;
 mod scheduler;
+pub mod scrubber;
 mod sealer;
+mod sealer_queue;
 mod state;
+pub mod unseal_cache;
 
 const NUM_SEAL_WORKERS: usize = 2;
 
+const NUM_POST_WORKERS: usize = 2;
+
+// Time allowed for a proof-of-spacetime requested through generate_post,
+// including time spent waiting for a free PoSt worker.
+const DEFAULT_POST_TIMEOUT_SECS: u64 = 30 * 60;
+
+// By default, queued seals which have waited this long are served ahead of
+// other seals, regardless of priority.
+pub const DEFAULT_SEALER_QUEUE_MAX_WAIT_SECS: u64 = 60 * 60;
+
 const FATAL_NOSEND_TASK: &str = "[run_blocking] could not send";
 const FATAL_NORECV_TASK: &str = "[run_blocking] could not recv";
+const FATAL_NOLOCK_PROVERS: &str = "could not acquire provers lock";
+const FATAL_NOLOCK_SEALERS: &str = "could not acquire sealers lock";
+const FATAL_NOLOCK_POST: &str = "could not acquire PoSt workers lock";
 
 pub type SectorId = u64;
 
-pub struct SectorBuilder {
+pub type ProverId = [u8; 31];
+
+// Settings of a SectorBuilder which callers need not provide. The defaults
+// retry failed seals, neither scrub sealed sectors nor cache unsealed copies
+// of them, and do not checksum staged sectors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectorBuilderConfig {
+    // How failed seals are retried.
+    pub seal_retry_policy: SealRetryPolicy,
+
+    // If set, sealed sectors are periodically checked for corruption.
+    pub scrub_policy: Option<ScrubPolicy>,
+
+    // If set, unsealed copies of sealed sectors are cached for retrievals.
+    pub unseal_cache_config: Option<UnsealCacheConfig>,
+
+    // Queued seals which have waited this long are served ahead of other
+    // seals, regardless of priority.
+    pub sealer_queue_max_wait: Duration,
+
+    // If set, staged sectors are checksummed when closed and checked when
+    // next read or sealed.
+    pub checksum_staged_sectors: bool,
+}
+
+impl Default for SectorBuilderConfig {
+    fn default() -> SectorBuilderConfig {
+        SectorBuilderConfig {
+            seal_retry_policy: Default::default(),
+            scrub_policy: None,
+            unseal_cache_config: None,
+            sealer_queue_max_wait: Duration::from_secs(DEFAULT_SEALER_QUEUE_MAX_WAIT_SECS),
+            checksum_staged_sectors: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShutdownMode {
+    // Wait for the prover's queued and in-progress seals to finish, then
+    // persist a final snapshot and stop.
+    Drain,
+
+    // Withdraw the prover's queued seals, persist a final snapshot and stop
+    // without waiting for seals in progress. Sectors whose seals did not
+    // finish are left in the Sealing state and are resealed on restart.
+    CheckpointAndStop,
+}
+
+// Resources shared by every prover managed through a SectorBuilder. Dropped,
+// and its sealers shut down, when the last SectorBuilder handle goes away.
+struct SharedResources {
     // Prevents FFI consumers from queueing behind long-running seal operations.
-    sealers_tx: mpsc::Sender<SealerInput>,
+    sealer_queue: Arc<SealerQueue>,
 
     // For additional seal concurrency, add more workers here.
-    sealers: Vec<SealerWorker>,
+    sealers: Mutex<Vec<SealerWorker>>,
+
+    // PoSts are generated by their own workers so that they never wait behind
+    // seals and do not block the main worker while proving.
+    post_input_tx: Mutex<mpsc::Sender<PoStWorkerInput>>,
+    post_workers: Mutex<Vec<PoStWorker>>,
+
+    kv_store: Arc<WrappedKeyValueStore>,
+    sector_store: Arc<WrappedSectorStore>,
+
+    // Provers with a live SectorBuilder handle.
+    provers: Mutex<HashSet<ProverId>>,
+
+    max_num_staged_sectors: u8,
+    seal_retry_policy: SealRetryPolicy,
+    scrub_policy: Option<ScrubPolicy>,
+}
+
+// A SectorBuilder manages the staged and sealed sectors of a single prover.
+// Additional provers can be added with add_prover, which returns a handle
+// with its own, isolated state which shares seal workers with this one.
+pub struct SectorBuilder {
+    shared: Arc<SharedResources>,
+
+    prover_id: ProverId,
 
     // The main worker's queue.
     scheduler_tx: mpsc::SyncSender<Request>,
 
     // The main worker. Owns all mutable state for the SectorBuilder.
     scheduler: Scheduler,
+
+    // Periodically checks sealed replicas for corruption, if configured.
+    scrubber: Option<ScrubberWorker>,
 }
 
 impl SectorBuilder {
     // Initialize and return a SectorBuilder from metadata persisted to disk if
     // it exists. Otherwise, initialize and return a fresh SectorBuilder. The
     // metadata key is equal to the prover_id.
+    #[allow(clippy::too_many_arguments)]
     pub fn init_from_metadata<S: Into<String>>(
         sector_store_config: &ConfiguredStore,
         last_committed_sector_id: SectorId,
@@ -58,6 +175,7 @@ impl SectorBuilder {
         sealed_sector_dir: S,
         staged_sector_dir: S,
         max_num_staged_sectors: u8,
+        config: SectorBuilderConfig,
     ) -> Result<SectorBuilder> {
         let kv_store = Arc::new(WrappedKeyValueStore {
             inner: Box::new(FileSystemKvs::initialize(metadata_dir.into())?),
@@ -66,46 +184,190 @@ impl SectorBuilder {
         // Initialize a SectorStore and wrap it in an Arc so we can access it
         // from multiple threads. Our implementation assumes that the
         // SectorStore is safe for concurrent access.
-        let sector_store = Arc::new(WrappedSectorStore {
-            inner: Box::new(new_sector_store(
+        let sector_store = if config.checksum_staged_sectors {
+            new_checksummed_sector_store(
+                sector_store_config,
+                vec![sealed_sector_dir.into()],
+                vec![staged_sector_dir.into()],
+                PlacementPolicy::RoundRobin,
+            )
+        } else {
+            new_sector_store(
                 sector_store_config,
                 sealed_sector_dir.into(),
                 staged_sector_dir.into(),
-            )),
+            )
+        };
+        let sector_store = Arc::new(WrappedSectorStore {
+            inner: Box::new(sector_store),
         });
 
-        // Configure the main worker's rendezvous channel.
-        let (main_tx, main_rx) = mpsc::sync_channel(0);
+        // Unsealed copies of sealed sectors are shared by all seal workers.
+        let unseal_cache = match config.unseal_cache_config {
+            Some(config) => Some(Arc::new(UnsealCache::new(config)?)),
+            None => None,
+        };
+
+        // Configure seal queue workers and their queue.
+        let sealer_queue = Arc::new(SealerQueue::new(config.sealer_queue_max_wait));
+
+        let seal_workers = (0..NUM_SEAL_WORKERS)
+            .map(|n| {
+                SealerWorker::start(
+                    n,
+                    sealer_queue.clone(),
+                    sector_store.clone(),
+                    unseal_cache.clone(),
+                )
+            })
+            .collect();
+
+        // Configure PoSt workers and their queue.
+        let (post_input_tx, post_input_rx) = mpsc::channel();
+        let post_input_rx = Arc::new(Mutex::new(post_input_rx));
+
+        let post_workers = (0..NUM_POST_WORKERS)
+            .map(|n| PoStWorker::start(n, post_input_rx.clone()))
+            .collect();
+
+        let shared = Arc::new(SharedResources {
+            sealer_queue,
+            sealers: Mutex::new(seal_workers),
+            post_input_tx: Mutex::new(post_input_tx),
+            post_workers: Mutex::new(post_workers),
+            kv_store,
+            sector_store,
+            provers: Default::default(),
+            max_num_staged_sectors,
+            seal_retry_policy: config.seal_retry_policy,
+            scrub_policy: config.scrub_policy,
+        });
+
+        SectorBuilder::start(shared, prover_id, last_committed_sector_id)
+    }
+
+    // Returns a handle through which the sectors of another prover can be
+    // managed. The new prover's staged and sealed state (and its metadata
+    // snapshot) are isolated from this one's, but seal workers, the sector
+    // store and the parameter cache are shared. Produces an error if the
+    // prover is already managed by a live handle.
+    pub fn add_prover(
+        &self,
+        prover_id: ProverId,
+        last_committed_sector_id: SectorId,
+    ) -> Result<SectorBuilder> {
+        SectorBuilder::start(self.shared.clone(), prover_id, last_committed_sector_id)
+    }
+
+    // Stops this handle's workers in the manner described by mode and
+    // persists a final metadata snapshot. Sealers shared with other provers
+    // keep running until the last handle is gone.
+    pub fn shutdown(mut self, mode: ShutdownMode) -> Result<()> {
+        self.stop(mode)
+    }
+
+    fn stop(&mut self, mode: ShutdownMode) -> Result<()> {
+        // Already stopped, e.g. by shutdown before being dropped.
+        let scheduler_thread = match self.scheduler.thread.take() {
+            Some(thread) => thread,
+            None => return Ok(()),
+        };
 
-        // Configure seal queue workers and channels.
-        let (seal_tx, seal_workers) = {
-            let (tx, rx) = mpsc::channel();
-            let rx = Arc::new(Mutex::new(rx));
+        // Stop the scrubber first so that it does not issue more requests to
+        // the main worker.
+        if let Some(scrubber) = &self.scrubber {
+            let _ = scrubber
+                .shutdown_tx
+                .send(())
+                .map_err(|err| println!("err sending Shutdown to scrubber: {:?}", err));
+        }
 
-            let workers = (0..NUM_SEAL_WORKERS)
-                .map(|n| SealerWorker::start(n, rx.clone(), sector_store.clone(), prover_id))
-                .collect();
+        // Shut down main worker, which persists a final snapshot before it
+        // returns. Sealers are shared with other provers and are shut down
+        // when the last handle is dropped.
+        let (tx, rx) = mpsc::sync_channel(0);
 
-            (tx, workers)
+        let result = match self.scheduler_tx.send(Request::Shutdown(mode, tx)) {
+            Ok(_) => rx.recv().unwrap_or_else(|err| {
+                Err(err_unrecov(format!("could not recv from scheduler: {:?}", err)).into())
+            }),
+            Err(err) => Err(err_unrecov(format!("could not send to scheduler: {:?}", err)).into()),
         };
 
+        // Wait for worker threads to return.
+        let _ = scheduler_thread
+            .join()
+            .map_err(|err| println!("err joining scheduler thread: {:?}", err));
+
+        if let Some(thread) = self.scrubber.as_mut().and_then(|s| s.thread.take()) {
+            let _ = thread
+                .join()
+                .map_err(|err| println!("err joining scrubber thread: {:?}", err));
+        }
+
+        if let Ok(mut provers) = self.shared.provers.lock() {
+            provers.remove(&self.prover_id);
+        }
+
+        log_unrecov(result)
+    }
+
+    // Returns the prover whose sectors are managed through this handle.
+    pub fn get_prover_id(&self) -> ProverId {
+        self.prover_id
+    }
+
+    fn start(
+        shared: Arc<SharedResources>,
+        prover_id: ProverId,
+        last_committed_sector_id: SectorId,
+    ) -> Result<SectorBuilder> {
+        if !shared
+            .provers
+            .lock()
+            .expects(FATAL_NOLOCK_PROVERS)
+            .insert(prover_id)
+        {
+            return Err(err_duplicate_prover(format!("{:x?}", &prover_id[..])).into());
+        }
+
+        // Configure the main worker's rendezvous channel.
+        let (main_tx, main_rx) = mpsc::sync_channel(0);
+
         // Configure main worker.
         let main_worker = Scheduler::start_with_metadata(
             main_rx,
             main_tx.clone(),
-            seal_tx.clone(),
-            kv_store.clone(),
-            sector_store.clone(),
+            shared.sealer_queue.clone(),
+            shared
+                .post_input_tx
+                .lock()
+                .expects(FATAL_NOLOCK_POST)
+                .clone(),
+            shared.kv_store.clone(),
+            shared.sector_store.clone(),
             last_committed_sector_id,
-            max_num_staged_sectors,
+            shared.max_num_staged_sectors,
             prover_id,
+            shared.seal_retry_policy.clone(),
         );
 
+        // Configure the scrubber, which reports to the main worker.
+        let scrubber = shared.scrub_policy.clone().map(|policy| {
+            ScrubberWorker::start(
+                policy,
+                main_tx.clone(),
+                shared.sector_store.clone(),
+                prover_id,
+            )
+        });
+
         Ok(SectorBuilder {
+            shared,
+            prover_id,
             scheduler_tx: main_tx,
             scheduler: main_worker,
-            sealers_tx: seal_tx,
-            sealers: seal_workers,
+            scrubber,
         })
     }
 
@@ -115,6 +377,12 @@ impl SectorBuilder {
         self.run_blocking(Request::GetMaxUserBytesPerStagedSector)
     }
 
+    // Returns counters and timings for this SectorBuilder, including
+    // process-wide seal, unseal and PoSt durations.
+    pub fn get_metrics(&self) -> MetricsSnapshot {
+        self.run_blocking(Request::GetMetrics)
+    }
+
     // Stages user piece-bytes for sealing. Note that add_piece calls are
     // processed sequentially to make bin packing easier.
     pub fn add_piece(&self, piece_key: String, piece_bytes: &[u8]) -> Result<SectorId> {
@@ -134,6 +402,87 @@ impl SectorBuilder {
         log_unrecov(self.run_blocking(|tx| Request::RetrievePiece(piece_key, tx)))
     }
 
+    // Returns the sector, seal status and position of the referenced piece.
+    // Produces an error if this sector builder has no such piece.
+    pub fn get_piece_info(&self, piece_key: String) -> Result<PieceInfo> {
+        log_unrecov(self.run_blocking(|tx| Request::GetPieceInfo(piece_key, tx)))
+    }
+
+    // Returns up to limit pieces matching the filter, skipping the first
+    // offset matches, along with the total number of matching pieces.
+    pub fn list_pieces(&self, filter: PieceFilter, offset: u64, limit: u64) -> PiecePage {
+        self.run_blocking(|tx| Request::ListPieces(filter, offset, limit, tx))
+    }
+
+    // Unseals num_bytes of the referenced piece, starting offset bytes into
+    // the piece, and returns them. Produces an error if the range extends
+    // beyond the end of the piece.
+    pub fn read_piece_range(
+        &self,
+        piece_key: String,
+        offset: u64,
+        num_bytes: UnpaddedBytesAmount,
+    ) -> Result<Vec<u8>> {
+        let range = PieceRange {
+            piece_key,
+            offset,
+            num_bytes,
+            output_path: None,
+        };
+
+        log_unrecov(self.run_blocking(|tx| Request::RetrievePieceRange(range, tx)))
+    }
+
+    // Like read_piece_range, but writes the unsealed bytes to the provided
+    // path instead of returning them.
+    pub fn read_piece_range_to_path(
+        &self,
+        piece_key: String,
+        offset: u64,
+        num_bytes: UnpaddedBytesAmount,
+        output_path: PathBuf,
+    ) -> Result<()> {
+        let range = PieceRange {
+            piece_key,
+            offset,
+            num_bytes,
+            output_path: Some(output_path),
+        };
+
+        log_unrecov(self.run_blocking(|tx| Request::RetrievePieceRange(range, tx))).map(|_| ())
+    }
+
+    // Schedules another seal attempt for a sector whose seal has failed.
+    // Produces an error if the sector is unknown or has not failed.
+    pub fn retry_seal(&self, sector_id: SectorId) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| Request::RetrySeal(sector_id, tx)))
+    }
+
+    // Writes the sealed sector's replica, merkle tree (if one was persisted)
+    // and metadata to a bundle directory, from which another SectorBuilder of
+    // the same prover can import it.
+    pub fn export_sealed_sector(&self, sector_id: SectorId, bundle_dir: PathBuf) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| Request::ExportSealedSector(sector_id, bundle_dir, tx)))
+    }
+
+    // Imports a sealed sector from a bundle written by export_sealed_sector.
+    // Only bundles exported under this builder's prover id can be imported,
+    // since a sector's replica and proof are bound to the prover id it was
+    // sealed with; other bundles are rejected. The replica is copied into
+    // this builder's sector store and accepted only if its merkle root
+    // matches comm_r and its proof verifies. A bundled merkle tree is
+    // restored alongside it. Returns the id of the imported sector.
+    pub fn import_sealed_sector(&self, bundle_dir: PathBuf) -> Result<SectorId> {
+        log_unrecov(self.run_blocking(|tx| Request::ImportSealedSector(bundle_dir, tx)))
+    }
+
+    // Sets the seal priority of a staged sector. Seals of higher-priority
+    // sectors are started first, though unseals are always served ahead of
+    // seals. Produces an error if the sector is not staged.
+    pub fn set_seal_priority(&self, sector_id: SectorId, priority: u8) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| Request::SetSealPriority(sector_id, priority, tx)))
+    }
+
     // For demo purposes. Schedules sealing of all staged sectors.
     pub fn seal_all_staged_sectors(&self) -> Result<()> {
         log_unrecov(self.run_blocking(Request::SealAllStagedSectors))
@@ -155,11 +504,45 @@ impl SectorBuilder {
         comm_rs: &[[u8; 32]],
         challenge_seed: &[u8; 32],
     ) -> Result<PoStOutput> {
-        log_unrecov(
-            self.run_blocking(|tx| Request::GeneratePoSt(Vec::from(comm_rs), *challenge_seed, tx)),
+        self.generate_post_with_timeout(
+            comm_rs,
+            challenge_seed,
+            Duration::from_secs(DEFAULT_POST_TIMEOUT_SECS),
         )
     }
 
+    // Generates a proof-of-spacetime, blocking the calling thread for at most
+    // timeout. Produces an error if the proof is not ready by then, in which
+    // case any proof generated later is discarded.
+    pub fn generate_post_with_timeout(
+        &self,
+        comm_rs: &[[u8; 32]],
+        challenge_seed: &[u8; 32],
+        timeout: Duration,
+    ) -> Result<PoStOutput> {
+        let deadline = Instant::now() + timeout;
+
+        let (tx, rx) = mpsc::sync_channel(0);
+
+        self.scheduler_tx
+            .clone()
+            .send(Request::GeneratePoSt(
+                Vec::from(comm_rs),
+                *challenge_seed,
+                deadline,
+                tx,
+            ))
+            .expects(FATAL_NOSEND_TASK);
+
+        let result = match rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(err_post_deadline().into()),
+            Err(err) => Err(err_unrecov(format!("could not recv PoSt: {:?}", err)).into()),
+        };
+
+        log_unrecov(result)
+    }
+
     // Run a task, blocking on the return channel.
     fn run_blocking<T, F: FnOnce(mpsc::SyncSender<T>) -> Request>(&self, with_sender: F) -> T {
         let (tx, rx) = mpsc::sync_channel(0);
@@ -175,35 +558,46 @@ impl SectorBuilder {
 
 impl Drop for SectorBuilder {
     fn drop(&mut self) {
-        // Shut down main worker and sealers, too.
+        // A SectorBuilder which is dropped without having been shut down
+        // explicitly does not wait for its seals.
         let _ = self
-            .scheduler_tx
-            .send(Request::Shutdown)
-            .map_err(|err| println!("err sending Shutdown to scheduler: {:?}", err));
-
-        for _ in &mut self.sealers {
-            let _ = self
-                .sealers_tx
-                .send(SealerInput::Shutdown)
-                .map_err(|err| println!("err sending Shutdown to sealer: {:?}", err));
-        }
+            .stop(ShutdownMode::CheckpointAndStop)
+            .map_err(|err| println!("err shutting down SectorBuilder: {:?}", err));
+    }
+}
 
-        // Wait for worker threads to return.
-        let scheduler_thread = &mut self.scheduler.thread;
+impl Drop for SharedResources {
+    fn drop(&mut self) {
+        let sealers = self.sealers.get_mut().expects(FATAL_NOLOCK_SEALERS);
 
-        if let Some(thread) = scheduler_thread.take() {
-            let _ = thread
-                .join()
-                .map_err(|err| println!("err joining scheduler thread: {:?}", err));
+        for _ in sealers.iter() {
+            self.sealer_queue.push(SealerInput::Shutdown);
         }
 
-        for worker in &mut self.sealers {
+        for worker in sealers.iter_mut() {
             if let Some(thread) = worker.thread.take() {
                 let _ = thread
                     .join()
                     .map_err(|err| println!("err joining sealer thread: {:?}", err));
             }
         }
+
+        let post_workers = self.post_workers.get_mut().expects(FATAL_NOLOCK_POST);
+        let post_input_tx = self.post_input_tx.get_mut().expects(FATAL_NOLOCK_POST);
+
+        for _ in post_workers.iter() {
+            let _ = post_input_tx
+                .send(PoStWorkerInput::Shutdown)
+                .map_err(|err| println!("err sending Shutdown to PoSt worker: {:?}", err));
+        }
+
+        for worker in post_workers.iter_mut() {
+            if let Some(thread) = worker.thread.take() {
+                let _ = thread
+                    .join()
+                    .map_err(|err| println!("err joining PoSt worker thread: {:?}", err));
+            }
+        }
     }
 }
 
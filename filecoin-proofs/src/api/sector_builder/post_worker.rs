@@ -0,0 +1,138 @@
+use crate::api::internal;
+use crate::api::internal::PoStInput;
+use crate::api::internal::PoStOutput;
+use crate::api::sector_builder::errors::err_post_deadline;
+use crate::api::sector_builder::WrappedSectorStore;
+use crate::error::ExpectWithBacktrace;
+use crate::error::Result;
+use crate::metrics::METRICS;
+use crate::FCP_LOG;
+use sector_base::api::bytes_amount::PaddedBytesAmount;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+const FATAL_NOLOCK: &str = "error acquiring task lock";
+const FATAL_RCVTSK: &str = "error receiving task";
+const FATAL_SNDRLT: &str = "error sending result";
+
+pub struct PoStWorker {
+    pub id: usize,
+    pub thread: Option<thread::JoinHandle<()>>,
+}
+
+pub struct PoStTask {
+    // Holds the sealed sectors being proven.
+    pub sector_store: Arc<WrappedSectorStore>,
+
+    pub sector_bytes: PaddedBytesAmount,
+    pub input: PoStInput,
+
+    // Instant by which the caller needs the proof. A task which is still
+    // queued at its deadline is abandoned without being proved.
+    pub deadline: Instant,
+
+    pub return_channel: mpsc::SyncSender<Result<PoStOutput>>,
+}
+
+pub enum PoStWorkerInput {
+    GeneratePoSt(Box<PoStTask>),
+    Shutdown,
+}
+
+impl PoStWorker {
+    pub fn start(
+        id: usize,
+        post_input_rx: Arc<Mutex<mpsc::Receiver<PoStWorkerInput>>>,
+    ) -> PoStWorker {
+        let thread = thread::spawn(move || loop {
+            // Acquire a lock on the rx end of the channel, get a task,
+            // relinquish the lock and return the task. The receiver is mutexed
+            // for coordinating reads across multiple worker-threads.
+            let task = {
+                let rx = post_input_rx.lock().expects(FATAL_NOLOCK);
+                rx.recv().expects(FATAL_RCVTSK)
+            };
+
+            match task {
+                PoStWorkerInput::GeneratePoSt(task) => {
+                    let result = if Instant::now() >= task.deadline {
+                        Err(err_post_deadline().into())
+                    } else {
+                        let start = Instant::now();
+                        let output = internal::generate_post(
+                            task.sector_bytes,
+                            task.input,
+                            task.sector_store.inner.manager(),
+                        );
+                        METRICS.record_post(start.elapsed());
+                        output
+                    };
+
+                    // The caller stops waiting once the deadline has passed,
+                    // in which case the result is discarded.
+                    if task.return_channel.send(result).is_err() {
+                        warn!(FCP_LOG, "{}", FATAL_SNDRLT; "post_worker" => id);
+                    }
+                }
+                PoStWorkerInput::Shutdown => break,
+            }
+        });
+
+        PoStWorker {
+            id,
+            thread: Some(thread),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::sector_builder::errors::SectorBuilderErr;
+    use sector_base::api::disk_backed_storage::{new_sector_store, ConfiguredStore};
+    use std::time::Duration;
+
+    #[test]
+    fn test_expired_task_not_proved() {
+        let (tx, rx) = mpsc::channel();
+        let worker = PoStWorker::start(0, Arc::new(Mutex::new(rx)));
+
+        let (return_tx, return_rx) = mpsc::sync_channel(0);
+
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staging_dir = tempfile::tempdir().unwrap();
+
+        let task = PoStTask {
+            sector_store: Arc::new(WrappedSectorStore {
+                inner: Box::new(new_sector_store(
+                    &ConfiguredStore::Test,
+                    sealed_dir.path().to_str().unwrap().to_owned(),
+                    staging_dir.path().to_str().unwrap().to_owned(),
+                )),
+            }),
+            sector_bytes: PaddedBytesAmount(1024),
+            input: PoStInput {
+                challenge_seed: [0; 32],
+                input_parts: Vec::new(),
+            },
+            deadline: Instant::now() - Duration::from_secs(1),
+            return_channel: return_tx,
+        };
+
+        tx.send(PoStWorkerInput::GeneratePoSt(Box::new(task)))
+            .unwrap();
+
+        match return_rx.recv().unwrap() {
+            Err(err) => match err.downcast_ref() {
+                Some(SectorBuilderErr::PoStDeadlineExceeded) => (),
+                _ => panic!("unexpected error: {}", err),
+            },
+            Ok(_) => panic!("expected PoStDeadlineExceeded"),
+        }
+
+        tx.send(PoStWorkerInput::Shutdown).unwrap();
+        worker.thread.unwrap().join().unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+use sector_base::api::errors::SectorManagerErr;
+use std::io;
+use std::time::Duration;
+
+// Broad classes of errors produced while sealing. Used by the retry policy to
+// decide whether a failed seal is worth another attempt.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum SealErrorClass {
+    // Reading the staged sector or writing the replica failed.
+    Io,
+    // The SectorManager could not provision or access a sector.
+    SectorManager,
+    // Anything else, e.g. a proof which failed to generate.
+    Other,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SealRetryPolicy {
+    // Maximum number of seal attempts (including the first) before a sector
+    // is left in the Failed state. Explicit calls to retry_seal are not
+    // subject to this limit.
+    pub max_attempts: usize,
+
+    // Delay before the first automatic retry.
+    pub initial_backoff: Duration,
+
+    // Each subsequent delay is the previous one multiplied by this factor.
+    pub backoff_multiplier: u32,
+
+    // Upper bound on any single delay.
+    pub max_backoff: Duration,
+
+    // Only failures belonging to one of these classes are retried
+    // automatically.
+    pub retriable: Vec<SealErrorClass>,
+}
+
+impl Default for SealRetryPolicy {
+    fn default() -> SealRetryPolicy {
+        SealRetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(30),
+            backoff_multiplier: 2,
+            max_backoff: Duration::from_secs(30 * 60),
+            retriable: vec![SealErrorClass::Io, SealErrorClass::SectorManager],
+        }
+    }
+}
+
+impl SealRetryPolicy {
+    // A policy which never retries automatically.
+    pub fn never() -> SealRetryPolicy {
+        SealRetryPolicy {
+            max_attempts: 1,
+            retriable: Default::default(),
+            ..Default::default()
+        }
+    }
+
+    // Returns the delay after which a sector whose seal has failed
+    // num_failed_attempts times (most recently with an error of the provided
+    // class) should be retried, or None if it should not be retried.
+    pub fn next_retry_delay(
+        &self,
+        error_class: SealErrorClass,
+        num_failed_attempts: usize,
+    ) -> Option<Duration> {
+        if num_failed_attempts == 0
+            || num_failed_attempts >= self.max_attempts
+            || !self.retriable.contains(&error_class)
+        {
+            return None;
+        }
+
+        let mut delay = self.initial_backoff;
+
+        for _ in 1..num_failed_attempts {
+            delay = delay
+                .checked_mul(self.backoff_multiplier)
+                .unwrap_or(self.max_backoff);
+
+            if delay >= self.max_backoff {
+                break;
+            }
+        }
+
+        Some(std::cmp::min(delay, self.max_backoff))
+    }
+}
+
+pub fn classify_seal_error(err: &failure::Error) -> SealErrorClass {
+    if err.downcast_ref::<io::Error>().is_some() {
+        SealErrorClass::Io
+    } else if err.downcast_ref::<SectorManagerErr>().is_some() {
+        SealErrorClass::SectorManager
+    } else {
+        SealErrorClass::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = SealRetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(10),
+            backoff_multiplier: 3,
+            max_backoff: Duration::from_secs(60),
+            retriable: vec![SealErrorClass::Io],
+        };
+
+        assert_eq!(policy.next_retry_delay(SealErrorClass::Io, 0), None);
+        assert_eq!(
+            policy.next_retry_delay(SealErrorClass::Io, 1),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            policy.next_retry_delay(SealErrorClass::Io, 2),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            policy.next_retry_delay(SealErrorClass::Io, 3),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            policy.next_retry_delay(SealErrorClass::Io, 4),
+            Some(Duration::from_secs(60))
+        );
+
+        // attempts exhausted
+        assert_eq!(policy.next_retry_delay(SealErrorClass::Io, 5), None);
+
+        // not a retriable class
+        assert_eq!(policy.next_retry_delay(SealErrorClass::Other, 1), None);
+
+        // never retries
+        assert_eq!(
+            SealRetryPolicy::never().next_retry_delay(SealErrorClass::Io, 1),
+            None
+        );
+    }
+
+    #[test]
+    fn test_classify() {
+        let err: failure::Error = io::Error::new(io::ErrorKind::Other, "disk on fire").into();
+        assert_eq!(classify_seal_error(&err), SealErrorClass::Io);
+
+        let err: failure::Error = SectorManagerErr::ReceiverError("nope".to_string()).into();
+        assert_eq!(classify_seal_error(&err), SealErrorClass::SectorManager);
+
+        let err: failure::Error = format_err!("proof did not verify");
+        assert_eq!(classify_seal_error(&err), SealErrorClass::Other);
+    }
+}
@@ -1,38 +1,62 @@
-use crate::api::internal;
 use crate::api::internal::PoStInput;
 use crate::api::internal::PoStInputPart;
 use crate::api::internal::PoStOutput;
+use crate::api::sector_builder::errors::err_duplicate_piece_key;
+use crate::api::sector_builder::errors::err_invalid_bundle;
+use crate::api::sector_builder::errors::err_invalid_state;
 use crate::api::sector_builder::errors::err_piecenotfound;
 use crate::api::sector_builder::errors::err_unrecov;
 use crate::api::sector_builder::helpers::add_piece::add_piece;
+use crate::api::sector_builder::helpers::bundle::read_manifest;
 use crate::api::sector_builder::helpers::get_seal_status::get_seal_status;
 use crate::api::sector_builder::helpers::get_sectors_ready_for_sealing::get_sectors_ready_for_sealing;
+use crate::api::sector_builder::helpers::pieces::{
+    build_piece_index, get_piece_info, list_pieces, PieceFilter, PieceIndex, PieceInfo, PiecePage,
+};
+use crate::api::sector_builder::helpers::reconcile::reconcile;
+use crate::api::sector_builder::helpers::retrieve_piece::PieceRange;
+use crate::api::sector_builder::helpers::scrub::next_scrub_candidate;
 use crate::api::sector_builder::helpers::snapshots::load_snapshot;
 use crate::api::sector_builder::helpers::snapshots::make_snapshot;
 use crate::api::sector_builder::helpers::snapshots::persist_snapshot;
+use crate::api::sector_builder::metadata::sum_piece_bytes;
+use crate::api::sector_builder::metadata::SealAttempt;
 use crate::api::sector_builder::metadata::SealStatus;
+use crate::api::sector_builder::metadata::SealedSectorHealth;
 use crate::api::sector_builder::metadata::SealedSectorMetadata;
 use crate::api::sector_builder::metadata::StagedSectorMetadata;
+use crate::api::sector_builder::post_worker::{PoStTask, PoStWorkerInput};
+use crate::api::sector_builder::retry::classify_seal_error;
+use crate::api::sector_builder::retry::SealRetryPolicy;
 use crate::api::sector_builder::sealer::SealerInput;
+use crate::api::sector_builder::sealer_queue::SealerQueue;
 use crate::api::sector_builder::state::SectorBuilderState;
 use crate::api::sector_builder::state::StagedState;
 use crate::api::sector_builder::SectorId;
+use crate::api::sector_builder::ShutdownMode;
 use crate::api::sector_builder::WrappedKeyValueStore;
 use crate::api::sector_builder::WrappedSectorStore;
 use crate::error::ExpectWithBacktrace;
 use crate::error::Result;
+use crate::metrics::MetricsSnapshot;
+use crate::metrics::METRICS;
+use crate::FCP_LOG;
 use sector_base::api::bytes_amount::UnpaddedBytesAmount;
+use sector_base::api::sector_access::SectorAccess;
+use sector_base::api::sector_store::SectorManager;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 const FATAL_NOLOAD: &str = "could not load snapshot";
 const FATAL_NORECV: &str = "could not receive task";
 const FATAL_NOSEND: &str = "could not send";
 const FATAL_SECMAP: &str = "insert failed";
 const FATAL_SNPSHT: &str = "could not snapshot";
-const FATAL_SLRSND: &str = "could not send to sealer";
+const FATAL_PSTSND: &str = "could not send to PoSt worker";
 const FATAL_HUNGUP: &str = "could not send to ret channel";
 const FATAL_NOSECT: &str = "could not find sector";
 
@@ -49,13 +73,28 @@ pub enum Request {
     GeneratePoSt(
         Vec<[u8; 32]>,
         [u8; 32],
+        Instant,
         mpsc::SyncSender<Result<PoStOutput>>,
     ),
     RetrievePiece(String, mpsc::SyncSender<Result<Vec<u8>>>),
+    RetrievePieceRange(PieceRange, mpsc::SyncSender<Result<Vec<u8>>>),
     SealAllStagedSectors(mpsc::SyncSender<Result<()>>),
     GetMaxUserBytesPerStagedSector(mpsc::SyncSender<UnpaddedBytesAmount>),
+    GetMetrics(mpsc::SyncSender<MetricsSnapshot>),
     HandleSealResult(SectorId, Box<Result<SealedSectorMetadata>>),
-    Shutdown,
+    RetrySeal(SectorId, mpsc::SyncSender<Result<()>>),
+    SetSealPriority(SectorId, u8, mpsc::SyncSender<Result<()>>),
+    GetPieceInfo(String, mpsc::SyncSender<Result<PieceInfo>>),
+    ListPieces(PieceFilter, u64, u64, mpsc::SyncSender<PiecePage>),
+    ExportSealedSector(SectorId, PathBuf, mpsc::SyncSender<Result<()>>),
+    ImportSealedSector(PathBuf, mpsc::SyncSender<Result<SectorId>>),
+    HandleImportResult(
+        Box<Result<SealedSectorMetadata>>,
+        mpsc::SyncSender<Result<SectorId>>,
+    ),
+    GetScrubCandidate(mpsc::SyncSender<Option<SealedSectorMetadata>>),
+    HandleScrubResult(SectorId, Box<Result<SealedSectorHealth>>),
+    Shutdown(ShutdownMode, mpsc::SyncSender<Result<()>>),
 }
 
 impl Scheduler {
@@ -63,12 +102,14 @@ impl Scheduler {
     pub fn start_with_metadata(
         scheduler_input_rx: mpsc::Receiver<Request>,
         scheduler_input_tx: mpsc::SyncSender<Request>,
-        sealer_input_tx: mpsc::Sender<SealerInput>,
+        sealer_queue: Arc<SealerQueue>,
+        post_input_tx: mpsc::Sender<PoStWorkerInput>,
         kv_store: Arc<WrappedKeyValueStore>,
         sector_store: Arc<WrappedSectorStore>,
         last_committed_sector_id: SectorId,
         max_num_staged_sectors: u8,
         prover_id: [u8; 31],
+        seal_retry_policy: SealRetryPolicy,
     ) -> Scheduler {
         let thread = thread::spawn(move || {
             // Build the scheduler's initial state. If available, we
@@ -89,21 +130,42 @@ impl Scheduler {
                 })
             };
 
+            let piece_index = build_piece_index(&state.staged, &state.sealed);
+
             let max_user_bytes_per_staged_sector =
                 sector_store.inner.config().max_unsealed_bytes_per_sector();
 
             let mut m = SectorMetadataManager {
                 kv_store,
                 sector_store,
+                piece_index,
                 state,
-                sealer_input_tx,
+                sealer_queue,
+                post_input_tx,
                 scheduler_input_tx: scheduler_input_tx.clone(),
                 max_num_staged_sectors,
                 max_user_bytes_per_staged_sector,
+                seal_retry_policy,
             };
 
+            // Sealers do not survive a restart. Re-enqueue any work which was
+            // interrupted and check that sealed replicas are still present.
+            m.recover_interrupted_work().expects(FATAL_SNPSHT);
+
+            // Set while draining, i.e. waiting for in-flight seals to finish
+            // before stopping.
+            let mut drain_tx: Option<mpsc::SyncSender<Result<()>>> = None;
+
             loop {
-                let task = scheduler_input_rx.recv().expects(FATAL_NORECV);
+                // Automatic seal retries are started by the scheduler itself,
+                // which waits for requests only until the next one is due.
+                let task = match m.start_due_seal_retries() {
+                    Some(timeout) => match scheduler_input_rx.recv_timeout(timeout) {
+                        Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                        received => received.expects(FATAL_NORECV),
+                    },
+                    None => scheduler_input_rx.recv().expects(FATAL_NORECV),
+                };
 
                 // Dispatch to the appropriate task-handler.
                 match task {
@@ -114,6 +176,14 @@ impl Scheduler {
                         tx.send(m.get_seal_status(sector_id)).expects(FATAL_NOSEND);
                     }
                     Request::RetrievePiece(piece_key, tx) => m.retrieve_piece(piece_key, tx),
+                    Request::RetrievePieceRange(range, tx) => m.retrieve_piece_range(range, tx),
+                    Request::GetPieceInfo(piece_key, tx) => {
+                        tx.send(m.get_piece_info(piece_key)).expects(FATAL_NOSEND);
+                    }
+                    Request::ListPieces(filter, offset, limit, tx) => {
+                        tx.send(m.list_pieces(&filter, offset, limit))
+                            .expects(FATAL_NOSEND);
+                    }
                     Request::GetSealedSectors(tx) => {
                         tx.send(m.get_sealed_sectors()).expects(FATAL_NOSEND);
                     }
@@ -123,16 +193,55 @@ impl Scheduler {
                     Request::GetMaxUserBytesPerStagedSector(tx) => {
                         tx.send(m.max_user_bytes()).expects(FATAL_NOSEND);
                     }
+                    Request::GetMetrics(tx) => {
+                        tx.send(m.get_metrics()).expects(FATAL_NOSEND);
+                    }
                     Request::SealAllStagedSectors(tx) => {
                         tx.send(m.seal_all_staged_sectors()).expects(FATAL_NOSEND);
                     }
                     Request::HandleSealResult(sector_id, result) => {
                         m.handle_seal_result(sector_id, *result);
                     }
-                    Request::GeneratePoSt(comm_rs, chg_seed, tx) => {
-                        m.generate_post(&comm_rs, &chg_seed, tx)
+                    Request::RetrySeal(sector_id, tx) => {
+                        tx.send(m.retry_seal(sector_id)).expects(FATAL_NOSEND);
+                    }
+                    Request::SetSealPriority(sector_id, priority, tx) => {
+                        tx.send(m.set_seal_priority(sector_id, priority))
+                            .expects(FATAL_NOSEND);
+                    }
+                    Request::ExportSealedSector(sector_id, bundle_dir, tx) => {
+                        m.export_sealed_sector(sector_id, bundle_dir, tx)
+                    }
+                    Request::ImportSealedSector(bundle_dir, tx) => {
+                        m.import_sealed_sector(bundle_dir, tx)
+                    }
+                    Request::HandleImportResult(result, tx) => {
+                        m.handle_import_result(*result, tx);
+                    }
+                    Request::GetScrubCandidate(tx) => {
+                        tx.send(m.get_scrub_candidate()).expects(FATAL_NOSEND);
+                    }
+                    Request::HandleScrubResult(sector_id, result) => {
+                        m.handle_scrub_result(sector_id, *result);
+                    }
+                    Request::GeneratePoSt(comm_rs, chg_seed, deadline, tx) => {
+                        m.generate_post(&comm_rs, &chg_seed, deadline, tx)
+                    }
+                    Request::Shutdown(ShutdownMode::Drain, tx) => drain_tx = Some(tx),
+                    Request::Shutdown(ShutdownMode::CheckpointAndStop, tx) => {
+                        m.withdraw_queued_seals();
+                        tx.send(m.checkpoint()).expects(FATAL_NOSEND);
+                        break;
+                    }
+                }
+
+                if let Some(tx) = drain_tx.take() {
+                    if m.has_seals_in_flight() {
+                        drain_tx = Some(tx);
+                    } else {
+                        tx.send(m.checkpoint()).expects(FATAL_NOSEND);
+                        break;
                     }
-                    Request::Shutdown => break,
                 }
             }
         });
@@ -151,10 +260,13 @@ pub struct SectorMetadataManager {
     kv_store: Arc<WrappedKeyValueStore>,
     sector_store: Arc<WrappedSectorStore>,
     state: SectorBuilderState,
-    sealer_input_tx: mpsc::Sender<SealerInput>,
+    sealer_queue: Arc<SealerQueue>,
+    post_input_tx: mpsc::Sender<PoStWorkerInput>,
+    piece_index: PieceIndex,
     scheduler_input_tx: mpsc::SyncSender<Request>,
     max_num_staged_sectors: u8,
     max_user_bytes_per_staged_sector: UnpaddedBytesAmount,
+    seal_retry_policy: SealRetryPolicy,
 }
 
 impl SectorMetadataManager {
@@ -162,19 +274,19 @@ impl SectorMetadataManager {
         &self,
         comm_rs: &[[u8; 32]],
         challenge_seed: &[u8; 32],
+        deadline: Instant,
         return_channel: mpsc::SyncSender<Result<PoStOutput>>,
     ) {
-        // reduce our sealed sector state-map to a mapping of comm_r to sealed
-        // sector access (AKA path to sealed sector file)
-        let comm_r_to_sector_access: HashMap<[u8; 32], String> = self
+        // reduce our sealed sector state-map to a mapping of comm_r to the
+        // sealed sector's access
+        let comm_r_to_sealed_access: HashMap<[u8; 32], SectorAccess> = self
             .state
             .sealed
             .sectors
             .values()
             .fold(HashMap::new(), |mut acc, item| {
-                let v = item.sector_access.clone();
-                let k = item.comm_r;
-                acc.entry(k).or_insert(v);
+                acc.entry(item.comm_r)
+                    .or_insert_with(|| item.sector_access.clone());
                 acc
             });
 
@@ -184,7 +296,7 @@ impl SectorMetadataManager {
         // which does not correspond to any sealed sector metadata
         for comm_r in comm_rs {
             input_parts.push(PoStInputPart {
-                sealed_sector_access: comm_r_to_sector_access.get(comm_r).cloned(),
+                sealed_sector_access: comm_r_to_sealed_access.get(comm_r).cloned(),
                 comm_r: *comm_r,
             });
         }
@@ -192,16 +304,22 @@ impl SectorMetadataManager {
         let mut seed = [0; 32];
         seed.copy_from_slice(challenge_seed);
 
-        let output = internal::generate_post(
-            self.sector_store.inner.config().sector_bytes(),
-            PoStInput {
+        // Proving happens on the PoSt workers, which reply to the caller
+        // directly, so that the scheduler can serve other requests meanwhile.
+        let task = PoStTask {
+            sector_store: self.sector_store.clone(),
+            sector_bytes: self.sector_store.inner.config().sector_bytes(),
+            input: PoStInput {
                 challenge_seed: *challenge_seed,
                 input_parts,
             },
-        );
+            deadline,
+            return_channel,
+        };
 
-        // TODO: Where should this work be scheduled? New worker type?
-        return_channel.send(output).expects(FATAL_HUNGUP);
+        self.post_input_tx
+            .send(PoStWorkerInput::GeneratePoSt(Box::new(task)))
+            .expects(FATAL_PSTSND);
     }
 
     // Unseals the sector containing the referenced piece and returns its
@@ -212,21 +330,18 @@ impl SectorMetadataManager {
         piece_key: String,
         return_channel: mpsc::SyncSender<Result<Vec<u8>>>,
     ) {
-        let opt_sealed_sector = self.state.sealed.sectors.values().find(|sector| {
-            sector
-                .pieces
-                .iter()
-                .any(|piece| piece.piece_key == piece_key)
-        });
-
-        if let Some(sealed_sector) = opt_sealed_sector {
+        if let Some(sealed_sector) = self.find_sealed_sector_with_piece(&piece_key) {
             let sealed_sector = Box::new(sealed_sector.clone());
-            let task = SealerInput::Unseal(piece_key, sealed_sector, return_channel);
+            let task = SealerInput::Unseal(
+                self.state.prover_id,
+                piece_key,
+                sealed_sector,
+                return_channel,
+            );
 
-            self.sealer_input_tx
-                .clone()
-                .send(task)
-                .expects(FATAL_SLRSND);
+            METRICS.unseal_enqueued();
+
+            self.sealer_queue.push(task);
         } else {
             return_channel
                 .send(Err(err_piecenotfound(piece_key.to_string()).into()))
@@ -234,6 +349,62 @@ impl SectorMetadataManager {
         }
     }
 
+    // Unseals a range of bytes from the referenced piece. Produces an error if
+    // this sector builder does not have a sealed sector containing the
+    // referenced piece or if the range extends beyond the end of the piece.
+    pub fn retrieve_piece_range(
+        &self,
+        range: PieceRange,
+        return_channel: mpsc::SyncSender<Result<Vec<u8>>>,
+    ) {
+        if let Some(sealed_sector) = self.find_sealed_sector_with_piece(&range.piece_key) {
+            let sealed_sector = Box::new(sealed_sector.clone());
+            let task = SealerInput::UnsealRange(
+                self.state.prover_id,
+                range,
+                sealed_sector,
+                return_channel,
+            );
+
+            METRICS.unseal_enqueued();
+
+            self.sealer_queue.push(task);
+        } else {
+            return_channel
+                .send(Err(err_piecenotfound(range.piece_key).into()))
+                .expects(FATAL_HUNGUP);
+        }
+    }
+
+    fn find_sealed_sector_with_piece(&self, piece_key: &str) -> Option<&SealedSectorMetadata> {
+        self.piece_index
+            .get(piece_key)
+            .and_then(|sector_id| self.state.sealed.sectors.get(sector_id))
+    }
+
+    // Returns the location and seal status of the referenced piece. Produces
+    // an error if this sector builder has no such piece.
+    pub fn get_piece_info(&self, piece_key: String) -> Result<PieceInfo> {
+        get_piece_info(
+            &self.state.staged,
+            &self.state.sealed,
+            &self.piece_index,
+            &piece_key,
+        )
+        .ok_or_else(|| err_piecenotfound(piece_key).into())
+    }
+
+    // Returns a page of the pieces matching the provided filter.
+    pub fn list_pieces(&self, filter: &PieceFilter, offset: u64, limit: u64) -> PiecePage {
+        list_pieces(
+            &self.state.staged,
+            &self.state.sealed,
+            filter,
+            offset,
+            limit,
+        )
+    }
+
     // Returns sealing status for the sector with specified id. If no sealed or
     // staged sector exists with the provided id, produce an error.
     pub fn get_seal_status(&self, sector_id: SectorId) -> Result<SealStatus> {
@@ -242,14 +413,22 @@ impl SectorMetadataManager {
 
     // Write the piece to storage, obtaining the sector id with which the
     // piece-bytes are now associated.
+    // Produces an error if a piece with the same key has already been added.
     pub fn add_piece(&mut self, piece_key: String, piece_bytes: &[u8]) -> Result<u64> {
+        if self.piece_index.contains_key(&piece_key) {
+            return Err(err_duplicate_piece_key(piece_key).into());
+        }
+
         let destination_sector_id = add_piece(
             &self.sector_store,
+            &self.state.prover_id,
             &mut self.state.staged,
-            piece_key,
+            piece_key.clone(),
             piece_bytes,
         )?;
 
+        self.piece_index.insert(piece_key, destination_sector_id);
+
         self.check_and_schedule(false)?;
         self.checkpoint()?;
 
@@ -258,8 +437,11 @@ impl SectorMetadataManager {
 
     // For demo purposes. Schedules sealing of all staged sectors.
     pub fn seal_all_staged_sectors(&mut self) -> Result<()> {
-        self.check_and_schedule(true)?;
-        self.checkpoint()
+        // Sectors scheduled before any failure to schedule another are
+        // checkpointed regardless.
+        let scheduled = self.check_and_schedule(true);
+        self.checkpoint()?;
+        scheduled
     }
 
     // Produces a vector containing metadata for all sealed sectors that this
@@ -280,6 +462,27 @@ impl SectorMetadataManager {
         self.max_user_bytes_per_staged_sector
     }
 
+    // Produces a snapshot of the process-wide metrics, augmented with sector
+    // counts and staged bytes derived from this SectorBuilder's state.
+    pub fn get_metrics(&self) -> MetricsSnapshot {
+        let mut snapshot = METRICS.snapshot();
+
+        for sector in self.state.staged.sectors.values() {
+            snapshot.staged_bytes += u64::from(sum_piece_bytes(sector));
+
+            match sector.seal_status {
+                SealStatus::Pending => snapshot.sectors.pending += 1,
+                SealStatus::Sealing => snapshot.sectors.sealing += 1,
+                SealStatus::Failed(_) => snapshot.sectors.failed += 1,
+                SealStatus::Sealed(_) => snapshot.sectors.sealed += 1,
+            }
+        }
+
+        snapshot.sectors.sealed += self.state.sealed.sectors.len() as u64;
+
+        snapshot
+    }
+
     // Update metadata to reflect the sealing results.
     pub fn handle_seal_result(
         &mut self,
@@ -292,10 +495,28 @@ impl SectorMetadataManager {
             let staged_state = &mut self.state.staged;
             let sealed_state = &mut self.state.sealed;
 
-            if result.is_err() {
+            if let Err(err) = result {
                 if let Some(staged_sector) = staged_state.sectors.get_mut(&sector_id) {
-                    staged_sector.seal_status =
-                        SealStatus::Failed(format!("{}", err_unrecov(result.unwrap_err())));
+                    let error_class = classify_seal_error(&err);
+                    let error = format!("{}", err_unrecov(err));
+
+                    staged_sector.seal_attempts.push(SealAttempt {
+                        failed_at: unix_now(),
+                        error_class,
+                        error: error.clone(),
+                    });
+                    staged_sector.seal_status = SealStatus::Failed(error);
+
+                    // The failed seal deleted the sealed sector provisioned
+                    // for it.
+                    staged_sector.sealed_sector_access = None;
+
+                    let num_failed_attempts = staged_sector.seal_attempts.len();
+
+                    staged_sector.seal_retry_at = self
+                        .seal_retry_policy
+                        .next_retry_delay(error_class, num_failed_attempts)
+                        .map(|delay| unix_now() + delay.as_secs());
                 };
             } else {
                 // Remove the staged sector from the state map.
@@ -311,39 +532,476 @@ impl SectorMetadataManager {
         self.checkpoint().expects(FATAL_SNPSHT);
     }
 
+    // Schedules another seal attempt for a sector whose seal has failed. An
+    // explicit retry is not subject to the retry policy's attempt limit.
+    pub fn retry_seal(&mut self, sector_id: SectorId) -> Result<()> {
+        self.reschedule_failed_seal(sector_id)?;
+        self.checkpoint()
+    }
+
+    // Sets the seal priority of a staged sector. If the sector is already
+    // waiting for a sealer, its place in the queue is updated. Produces an
+    // error if a sealer has already started (or finished) sealing it.
+    pub fn set_seal_priority(&mut self, sector_id: SectorId, priority: u8) -> Result<()> {
+        let sector = self
+            .state
+            .staged
+            .sectors
+            .get_mut(&sector_id)
+            .ok_or_else(|| err_invalid_state(format!("no staged sector with id {}", sector_id)))?;
+
+        match sector.seal_status {
+            SealStatus::Sealing => {
+                let queued =
+                    self.sealer_queue
+                        .set_seal_priority(&self.state.prover_id, sector_id, priority);
+
+                if !queued {
+                    return Err(err_invalid_state(format!(
+                        "sector {} is already being sealed",
+                        sector_id
+                    ))
+                    .into());
+                }
+            }
+            SealStatus::Sealed(_) => {
+                return Err(
+                    err_invalid_state(format!("sector {} is already sealed", sector_id)).into(),
+                );
+            }
+            SealStatus::Pending | SealStatus::Failed(_) => (),
+        }
+
+        sector.seal_priority = priority;
+
+        self.checkpoint()
+    }
+
+    // Retries the failed seals whose retry policy backoff has elapsed and
+    // returns the time remaining until the next retry is due, if any. Retry
+    // times are kept in the sectors' metadata, so retries which were pending
+    // when the scheduler stopped are made once it restarts.
+    pub fn start_due_seal_retries(&mut self) -> Option<Duration> {
+        let now = unix_now();
+
+        let due: Vec<SectorId> = self
+            .state
+            .staged
+            .sectors
+            .values()
+            .filter(|sector| sector.seal_retry_at.map_or(false, |at| at <= now))
+            .map(|sector| sector.sector_id)
+            .collect();
+
+        for sector_id in &due {
+            self.state
+                .staged
+                .sectors
+                .get_mut(sector_id)
+                .expects(FATAL_NOSECT)
+                .seal_retry_at = None;
+
+            // A retry which cannot be scheduled (e.g. for lack of space) leaves
+            // the sector failed, to be retried explicitly.
+            if let Err(err) = self.reschedule_failed_seal(*sector_id) {
+                let err_string = format!("{}", err);
+                warn!(FCP_LOG, "could not retry seal"; "sector_id" => sector_id, "error" => err_string);
+            }
+        }
+
+        if !due.is_empty() {
+            self.checkpoint().expects(FATAL_SNPSHT);
+        }
+
+        self.state
+            .staged
+            .sectors
+            .values()
+            .filter_map(|sector| sector.seal_retry_at)
+            .min()
+            .map(|at| Duration::from_secs(at.saturating_sub(now)))
+    }
+
+    fn reschedule_failed_seal(&mut self, sector_id: SectorId) -> Result<()> {
+        let sector =
+            self.state.staged.sectors.get(&sector_id).ok_or_else(|| {
+                err_invalid_state(format!("no staged sector with id {}", sector_id))
+            })?;
+
+        match sector.seal_status {
+            SealStatus::Failed(_) => (),
+            _ => {
+                return Err(err_invalid_state(format!(
+                    "sector {} has not failed to seal",
+                    sector_id
+                ))
+                .into());
+            }
+        }
+
+        self.schedule_seal(sector_id)
+    }
+
+    // Provisions the sealed sector into which the staged sector will be sealed,
+    // marks the staged sector as Sealing and sends it to the sealers. Produces
+    // an error, leaving the staged sector untouched, if the sealed sector
+    // cannot be provisioned, e.g. because no root has room for it and the
+    // temporary files written while sealing it. A seal which has been sent to
+    // the sealers therefore cannot run out of space.
+    fn schedule_seal(&mut self, sector_id: SectorId) -> Result<()> {
+        let sealed_sector_access = self
+            .sector_store
+            .inner
+            .manager()
+            .new_sealed_sector_access_for(&self.state.prover_id, sector_id)?;
+
+        self.enqueue_seal(sector_id, sealed_sector_access);
+
+        Ok(())
+    }
+
+    // Sends an interrupted seal to the sealers again, into the sealed sector
+    // provisioned for it before the restart if that still exists and its
+    // reservation can be restored, and otherwise (deleting any partial replica
+    // left behind) into a newly provisioned sealed sector.
+    fn resume_seal(&mut self, sector_id: SectorId) -> Result<()> {
+        let sector_store = self.sector_store.clone();
+        let manager = sector_store.inner.manager();
+
+        let sealed_sector_access = self
+            .state
+            .staged
+            .sectors
+            .get(&sector_id)
+            .expects(FATAL_NOSECT)
+            .sealed_sector_access
+            .clone();
+
+        if let Some(access) = sealed_sector_access {
+            if manager.sector_exists(&access).unwrap_or(false) {
+                if manager.restore_reservation(&access).is_ok() {
+                    self.enqueue_seal(sector_id, access);
+                    return Ok(());
+                }
+
+                delete_abandoned_sealed_sector(manager, sector_id, &access);
+            }
+        }
+
+        self.schedule_seal(sector_id)
+    }
+
+    // Marks the staged sector as Sealing into the provided, provisioned sealed
+    // sector, which is recorded so that it can be found after a restart, and
+    // sends the seal to the sealers.
+    fn enqueue_seal(&mut self, sector_id: SectorId, sealed_sector_access: SectorAccess) {
+        let sector = self
+            .state
+            .staged
+            .sectors
+            .get_mut(&sector_id)
+            .expects(FATAL_NOSECT);
+
+        sector.seal_status = SealStatus::Sealing;
+        sector.sealed_sector_access = Some(sealed_sector_access.clone());
+        sector.seal_retry_at = None;
+
+        METRICS.seal_enqueued();
+
+        self.sealer_queue.push(SealerInput::Seal(
+            self.state.prover_id,
+            sector.clone(),
+            sealed_sector_access,
+            self.scheduler_input_tx.clone(),
+        ));
+    }
+
+    // Writes a bundle containing the sealed sector's replica and metadata to
+    // the provided directory. The sector remains sealed in this builder.
+    pub fn export_sealed_sector(
+        &self,
+        sector_id: SectorId,
+        bundle_dir: PathBuf,
+        return_channel: mpsc::SyncSender<Result<()>>,
+    ) {
+        if let Some(sealed_sector) = self.state.sealed.sectors.get(&sector_id) {
+            self.sealer_queue.push(SealerInput::ExportSector(
+                self.state.prover_id,
+                Box::new(sealed_sector.clone()),
+                bundle_dir,
+                return_channel,
+            ));
+        } else {
+            return_channel
+                .send(Err(err_invalid_state(format!(
+                    "no sealed sector with id {}",
+                    sector_id
+                ))
+                .into()))
+                .expects(FATAL_HUNGUP);
+        }
+    }
+
+    // Schedules verification of a bundle exported by another SectorBuilder.
+    // The bundle must have been sealed by this builder's prover and its
+    // sector id must not already be in use.
+    pub fn import_sealed_sector(
+        &self,
+        bundle_dir: PathBuf,
+        return_channel: mpsc::SyncSender<Result<SectorId>>,
+    ) {
+        let manifest = read_manifest(&bundle_dir).and_then(|manifest| {
+            if manifest.prover_id != self.state.prover_id {
+                return Err(err_invalid_bundle(format!(
+                    "sector {} was sealed by another prover",
+                    manifest.sector.sector_id
+                ))
+                .into());
+            }
+
+            self.check_importable(&manifest.sector)?;
+
+            Ok(manifest)
+        });
+
+        match manifest {
+            Ok(manifest) => self.sealer_queue.push(SealerInput::ImportSector(
+                self.state.prover_id,
+                Box::new(manifest),
+                bundle_dir,
+                self.scheduler_input_tx.clone(),
+                return_channel,
+            )),
+            Err(err) => return_channel.send(Err(err)).expects(FATAL_HUNGUP),
+        }
+    }
+
+    // Adds a verified, imported sector to the sealed state. The sector id and
+    // piece keys are checked again, as another import of the same sector may
+    // have completed while this one was being verified.
+    pub fn handle_import_result(
+        &mut self,
+        result: Result<SealedSectorMetadata>,
+        return_channel: mpsc::SyncSender<Result<SectorId>>,
+    ) {
+        let result = result.and_then(|sealed_sector| {
+            let sector_id = sealed_sector.sector_id;
+
+            if let Err(err) = self.check_importable(&sealed_sector) {
+                let _ = self
+                    .sector_store
+                    .inner
+                    .manager()
+                    .delete_sealed_sector_access(&sealed_sector.sector_access);
+
+                return Err(err);
+            }
+
+            // Sector ids allocated from now on must not collide with the
+            // imported sector's.
+            let nonce = &mut self.state.staged.sector_id_nonce;
+            *nonce = std::cmp::max(*nonce, sector_id);
+
+            for piece in &sealed_sector.pieces {
+                self.piece_index.insert(piece.piece_key.clone(), sector_id);
+            }
+
+            self.state.sealed.sectors.insert(sector_id, sealed_sector);
+
+            self.checkpoint()?;
+
+            Ok(sector_id)
+        });
+
+        return_channel.send(result).expects(FATAL_HUNGUP);
+    }
+
+    // An imported sector must not reuse a sector id or piece key known to
+    // this builder.
+    fn check_importable(&self, sealed_sector: &SealedSectorMetadata) -> Result<()> {
+        let sector_id = sealed_sector.sector_id;
+
+        if self.state.staged.sectors.contains_key(&sector_id)
+            || self.state.sealed.sectors.contains_key(&sector_id)
+        {
+            return Err(err_invalid_state(format!("sector {} already exists", sector_id)).into());
+        }
+
+        for piece in &sealed_sector.pieces {
+            if self.piece_index.contains_key(&piece.piece_key) {
+                return Err(err_duplicate_piece_key(piece.piece_key.clone()).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the sealed sector which has gone the longest without being
+    // scrubbed.
+    pub fn get_scrub_candidate(&self) -> Option<SealedSectorMetadata> {
+        next_scrub_candidate(&self.state.sealed)
+            .and_then(|sector_id| self.state.sealed.sectors.get(&sector_id))
+            .cloned()
+    }
+
+    // Records the outcome of scrubbing a sealed sector. A sector which could
+    // not be checked keeps its previous health, but is still marked as
+    // scrubbed so that the scrubber moves on to other sectors.
+    pub fn handle_scrub_result(&mut self, sector_id: SectorId, result: Result<SealedSectorHealth>) {
+        if let Some(sealed_sector) = self.state.sealed.sectors.get_mut(&sector_id) {
+            match result {
+                Ok(health) => {
+                    if health != SealedSectorHealth::Ok {
+                        let health_string = format!("{:?}", health);
+                        warn!(FCP_LOG, "scrub detected damaged replica"; "sector_id" => sector_id, "health" => health_string);
+                    }
+
+                    sealed_sector.health = health;
+                }
+                Err(err) => {
+                    let err_string = format!("{}", err);
+                    error!(FCP_LOG, "could not scrub sealed sector"; "sector_id" => sector_id, "error" => err_string);
+                }
+            }
+
+            sealed_sector.last_scrubbed_at = unix_now();
+        }
+
+        self.checkpoint().expects(FATAL_SNPSHT);
+    }
+
+    // Reconciles state loaded from a snapshot with the sector store, sending
+    // interrupted seals to the sealers again.
+    fn recover_interrupted_work(&mut self) -> Result<()> {
+        let manager = self.sector_store.inner.manager();
+
+        // A sector which cannot be checked, e.g. because its root is no longer
+        // configured, is treated as missing.
+        let recovered = reconcile(&mut self.state.staged, &mut self.state.sealed, |access| {
+            manager.sector_exists(access).unwrap_or(false)
+        });
+
+        for sector_id in &recovered.missing_staged {
+            warn!(FCP_LOG, "staged sector missing after restart"; "sector_id" => sector_id);
+        }
+
+        // Reservations do not survive a restart. Staged sectors keep theirs
+        // until they have been sealed, so the space set aside for them is
+        // restored; interrupted seals provision, and reserve, new sealed
+        // sectors when they are resumed below.
+        for sector in self.state.staged.sectors.values() {
+            if !manager
+                .sector_exists(&sector.sector_access)
+                .unwrap_or(false)
+            {
+                continue;
+            }
+
+            if let Err(err) = manager.restore_reservation(&sector.sector_access) {
+                let err_string = format!("{}", err);
+                warn!(FCP_LOG, "could not restore reservation of staged sector"; "sector_id" => sector.sector_id, "error" => err_string);
+            }
+        }
+
+        for sector_id in &recovered.missing_replicas {
+            warn!(FCP_LOG, "sealed replica missing after restart"; "sector_id" => sector_id);
+        }
+
+        // Sealed sectors recorded for seals which will not be resumed, e.g.
+        // because their staged sector is missing, are deleted.
+        for sector in self.state.staged.sectors.values_mut() {
+            if sector.seal_status == SealStatus::Sealing {
+                continue;
+            }
+
+            if let Some(access) = sector.sealed_sector_access.take() {
+                if manager.sector_exists(&access).unwrap_or(false) {
+                    delete_abandoned_sealed_sector(manager, sector.sector_id, &access);
+                }
+            }
+        }
+
+        for sector_id in recovered.to_reseal {
+            info!(FCP_LOG, "resuming interrupted seal"; "sector_id" => sector_id);
+
+            // A seal which cannot be resumed for now is scheduled again, like
+            // any other pending sector, once there is room for it.
+            if let Err(err) = self.resume_seal(sector_id) {
+                let err_string = format!("{}", err);
+                warn!(FCP_LOG, "could not resume interrupted seal"; "sector_id" => sector_id, "error" => err_string);
+
+                let sector = self
+                    .state
+                    .staged
+                    .sectors
+                    .get_mut(&sector_id)
+                    .expects(FATAL_NOSECT);
+
+                sector.seal_status = SealStatus::Pending;
+                sector.sealed_sector_access = None;
+            }
+        }
+
+        self.checkpoint()
+    }
+
     // Check for sectors which should no longer receive new user piece-bytes and
-    // schedule them for sealing.
+    // schedule them for sealing. Sectors which cannot be sealed for lack of
+    // space stay pending; this is an error only if sealing was requested
+    // explicitly.
     fn check_and_schedule(&mut self, seal_all_staged_sectors: bool) -> Result<()> {
-        let staged_state = &mut self.state.staged;
-
         let to_be_sealed = get_sectors_ready_for_sealing(
-            staged_state,
+            &self.state.staged,
             self.max_user_bytes_per_staged_sector,
             self.max_num_staged_sectors,
             seal_all_staged_sectors,
         );
 
-        // Mark the to-be-sealed sectors as no longer accepting data and then
-        // schedule sealing.
         for sector_id in to_be_sealed {
-            let mut sector = staged_state
-                .sectors
-                .get_mut(&sector_id)
-                .expects(FATAL_NOSECT);
-            sector.seal_status = SealStatus::Sealing;
-
-            self.sealer_input_tx
-                .clone()
-                .send(SealerInput::Seal(
-                    sector.clone(),
-                    self.scheduler_input_tx.clone(),
-                ))
-                .expects(FATAL_SLRSND);
+            if let Err(err) = self.schedule_seal(sector_id) {
+                if seal_all_staged_sectors {
+                    return Err(err);
+                }
+
+                let err_string = format!("{}", err);
+                warn!(FCP_LOG, "could not schedule seal"; "sector_id" => sector_id, "error" => err_string);
+
+                break;
+            }
         }
 
         Ok(())
     }
 
+    // Returns true if any of this prover's sectors are queued for, or being,
+    // sealed.
+    fn has_seals_in_flight(&self) -> bool {
+        self.state
+            .staged
+            .sectors
+            .values()
+            .any(|sector| sector.seal_status == SealStatus::Sealing)
+    }
+
+    // Removes this prover's seals from the sealer queue. Their sectors are
+    // left in the Sealing state and are resealed, into newly provisioned
+    // sealed sectors, on restart, so the still empty sealed sectors provisioned
+    // for the withdrawn seals are deleted. Seals which a sealer has already
+    // started cannot be withdrawn; their results are discarded if they arrive
+    // after the scheduler has stopped.
+    fn withdraw_queued_seals(&self) {
+        let manager = self.sector_store.inner.manager();
+
+        for sealed_sector_access in self.sealer_queue.withdraw_seals(&self.state.prover_id) {
+            METRICS.seal_dequeued();
+
+            if let Err(err) = manager.delete_sealed_sector_access(&sealed_sector_access) {
+                let err_string = format!("{}", err);
+                warn!(FCP_LOG, "could not delete sealed sector of withdrawn seal"; "sector_access" => format!("{}", sealed_sector_access), "error" => err_string);
+            }
+        }
+    }
+
     // Create and persist metadata snapshot.
     fn checkpoint(&self) -> Result<()> {
         let snapshot = make_snapshot(
@@ -356,3 +1014,24 @@ impl SectorMetadataManager {
         Ok(())
     }
 }
+
+// Deletes the sealed sector provisioned for a seal which will not complete into
+// it, logging (rather than producing) any failure, which leaves the sector in
+// place for an operator to remove.
+fn delete_abandoned_sealed_sector(
+    manager: &SectorManager,
+    sector_id: SectorId,
+    sealed_sector_access: &SectorAccess,
+) {
+    if let Err(err) = manager.delete_sealed_sector_access(sealed_sector_access) {
+        let err_string = format!("{}", err);
+        warn!(FCP_LOG, "could not delete abandoned sealed sector"; "sector_id" => sector_id, "sector_access" => format!("{}", sealed_sector_access), "error" => err_string);
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
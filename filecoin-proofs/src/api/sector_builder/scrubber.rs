@@ -0,0 +1,73 @@
+use crate::api::sector_builder::helpers::scrub::scrub;
+use crate::api::sector_builder::scheduler::Request;
+use crate::api::sector_builder::WrappedSectorStore;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScrubPolicy {
+    // Time to wait between scrubbing one sealed sector and the next.
+    pub interval: Duration,
+
+    // If set, the stored snark_proof is re-verified in addition to the
+    // replica's comm_r.
+    pub verify_proofs: bool,
+}
+
+pub struct ScrubberWorker {
+    pub thread: Option<thread::JoinHandle<()>>,
+    pub shutdown_tx: mpsc::Sender<()>,
+}
+
+impl ScrubberWorker {
+    // Starts a worker which, once per interval, asks the scheduler for the
+    // sealed sector which has gone the longest without being scrubbed, checks
+    // its replica and reports the result back to the scheduler.
+    pub fn start(
+        policy: ScrubPolicy,
+        scheduler_tx: mpsc::SyncSender<Request>,
+        sector_store: Arc<WrappedSectorStore>,
+        prover_id: [u8; 31],
+    ) -> ScrubberWorker {
+        let (shutdown_tx, shutdown_rx) = mpsc::channel();
+
+        let thread = thread::spawn(move || loop {
+            match shutdown_rx.recv_timeout(policy.interval) {
+                Err(mpsc::RecvTimeoutError::Timeout) => (),
+                _ => break,
+            }
+
+            // The scheduler may have shut down while we were waiting, in which
+            // case there's nothing more to do.
+            let (tx, rx) = mpsc::sync_channel(0);
+            if scheduler_tx.send(Request::GetScrubCandidate(tx)).is_err() {
+                break;
+            }
+
+            let sealed_sector = match rx.recv() {
+                Ok(Some(sealed_sector)) => sealed_sector,
+                Ok(None) => continue,
+                Err(_) => break,
+            };
+
+            let result = scrub(
+                &sector_store,
+                &prover_id,
+                &sealed_sector,
+                policy.verify_proofs,
+            );
+
+            let task = Request::HandleScrubResult(sealed_sector.sector_id, Box::new(result));
+            if scheduler_tx.send(task).is_err() {
+                break;
+            }
+        });
+
+        ScrubberWorker {
+            thread: Some(thread),
+            shutdown_tx,
+        }
+    }
+}
@@ -1,17 +1,26 @@
-use crate::api::sector_builder::helpers::retrieve_piece::retrieve_piece;
+use crate::api::sector_builder::helpers::bundle::{export_bundle, import_bundle, BundleManifest};
+use crate::api::sector_builder::helpers::retrieve_piece::{
+    retrieve_piece, retrieve_piece_range, PieceRange,
+};
 use crate::api::sector_builder::helpers::seal::seal;
 use crate::api::sector_builder::metadata::SealedSectorMetadata;
 use crate::api::sector_builder::metadata::StagedSectorMetadata;
 use crate::api::sector_builder::scheduler::Request;
+use crate::api::sector_builder::sealer_queue::SealerQueue;
+use crate::api::sector_builder::unseal_cache::UnsealCache;
+use crate::api::sector_builder::ProverId;
+use crate::api::sector_builder::SectorId;
 use crate::api::sector_builder::WrappedSectorStore;
-use crate::error::ExpectWithBacktrace;
 use crate::error::Result;
+use crate::metrics::METRICS;
+use crate::FCP_LOG;
+use sector_base::api::sector_access::SectorAccess;
+use std::path::PathBuf;
 use std::sync::mpsc;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::thread;
+use std::time::Instant;
 
-const FATAL_NOLOCK: &str = "error acquiring task lock";
-const FATAL_RCVTSK: &str = "error receiving seal task";
 const FATAL_SNDTSK: &str = "error sending task";
 const FATAL_SNDRLT: &str = "error sending result";
 
@@ -20,50 +29,141 @@ pub struct SealerWorker {
     pub thread: Option<thread::JoinHandle<()>>,
 }
 
+// Sealers are shared by all provers managed through a SectorBuilder, so each
+// task identifies the prover on whose behalf it is performed. A seal carries
+// the access of the sealed sector provisioned for it.
 pub enum SealerInput {
-    Seal(StagedSectorMetadata, mpsc::SyncSender<Request>),
+    Seal(
+        ProverId,
+        StagedSectorMetadata,
+        SectorAccess,
+        mpsc::SyncSender<Request>,
+    ),
     Unseal(
+        ProverId,
         String,
         Box<SealedSectorMetadata>,
         mpsc::SyncSender<Result<Vec<u8>>>,
     ),
+    UnsealRange(
+        ProverId,
+        PieceRange,
+        Box<SealedSectorMetadata>,
+        mpsc::SyncSender<Result<Vec<u8>>>,
+    ),
+    ExportSector(
+        ProverId,
+        Box<SealedSectorMetadata>,
+        PathBuf,
+        mpsc::SyncSender<Result<()>>,
+    ),
+    ImportSector(
+        ProverId,
+        Box<BundleManifest>,
+        PathBuf,
+        mpsc::SyncSender<Request>,
+        mpsc::SyncSender<Result<SectorId>>,
+    ),
     Shutdown,
 }
 
 impl SealerWorker {
     pub fn start(
         id: usize,
-        seal_task_rx: Arc<Mutex<mpsc::Receiver<SealerInput>>>,
+        seal_queue: Arc<SealerQueue>,
         sector_store: Arc<WrappedSectorStore>,
-        prover_id: [u8; 31],
+        unseal_cache: Option<Arc<UnsealCache>>,
     ) -> SealerWorker {
         let thread = thread::spawn(move || loop {
-            // Acquire a lock on the rx end of the channel, get a task,
-            // relinquish the lock and return the task. The receiver is mutexed
-            // for coordinating reads across multiple worker-threads.
-            let task = {
-                let rx = seal_task_rx.lock().expects(FATAL_NOLOCK);
-                rx.recv().expects(FATAL_RCVTSK)
-            };
+            // Block until the queue has a task for us. The queue decides which
+            // of the waiting tasks is served next.
+            let task = seal_queue.pop();
 
             // Dispatch to the appropriate task-handler.
             match task {
-                SealerInput::Seal(staged_sector, return_channel) => {
+                SealerInput::Seal(
+                    prover_id,
+                    staged_sector,
+                    sealed_sector_access,
+                    return_channel,
+                ) => {
+                    METRICS.seal_dequeued();
+
                     let sector_id = staged_sector.sector_id;
-                    let result = seal(&sector_store.clone(), &prover_id, staged_sector);
+                    let start = Instant::now();
+                    let result = seal(
+                        &sector_store.clone(),
+                        &prover_id,
+                        staged_sector,
+                        sealed_sector_access,
+                    );
+                    METRICS.record_seal(start.elapsed(), result.is_ok());
+
                     let task = Request::HandleSealResult(sector_id, Box::new(result));
 
-                    return_channel.send(task).expects(FATAL_SNDTSK);
+                    // The prover's scheduler may have shut down while we were
+                    // sealing. Its sector will be resealed on restart.
+                    if return_channel.send(task).is_err() {
+                        warn!(FCP_LOG, "{}", FATAL_SNDTSK; "sector_id" => sector_id);
+                    }
                 }
-                SealerInput::Unseal(piece_key, sealed_sector, return_channel) => {
+                SealerInput::Unseal(prover_id, piece_key, sealed_sector, return_channel) => {
+                    METRICS.unseal_dequeued();
+
+                    let start = Instant::now();
                     let result = retrieve_piece(
                         &sector_store.clone(),
+                        unseal_cache.as_ref().map(|c| &**c),
                         &sealed_sector,
                         &prover_id,
                         &piece_key,
                     );
+                    METRICS.record_unseal(start.elapsed());
+
+                    if return_channel.send(result).is_err() {
+                        warn!(FCP_LOG, "{}", FATAL_SNDRLT; "piece_key" => piece_key);
+                    }
+                }
+                SealerInput::UnsealRange(prover_id, range, sealed_sector, return_channel) => {
+                    METRICS.unseal_dequeued();
+
+                    let start = Instant::now();
+                    let result = retrieve_piece_range(
+                        &sector_store.clone(),
+                        unseal_cache.as_ref().map(|c| &**c),
+                        &sealed_sector,
+                        &prover_id,
+                        &range,
+                    );
+                    METRICS.record_unseal(start.elapsed());
+
+                    if return_channel.send(result).is_err() {
+                        warn!(FCP_LOG, "{}", FATAL_SNDRLT; "piece_key" => range.piece_key);
+                    }
+                }
+                SealerInput::ExportSector(prover_id, sealed_sector, bundle_dir, return_channel) => {
+                    let result =
+                        export_bundle(&sector_store, &prover_id, &sealed_sector, &bundle_dir);
+
+                    if return_channel.send(result).is_err() {
+                        warn!(FCP_LOG, "{}", FATAL_SNDRLT; "sector_id" => sealed_sector.sector_id);
+                    }
+                }
+                SealerInput::ImportSector(
+                    prover_id,
+                    manifest,
+                    bundle_dir,
+                    scheduler_channel,
+                    return_channel,
+                ) => {
+                    let sector_id = manifest.sector.sector_id;
+                    let result = import_bundle(&sector_store, &prover_id, &manifest, &bundle_dir);
+
+                    let task = Request::HandleImportResult(Box::new(result), return_channel);
 
-                    return_channel.send(result).expects(FATAL_SNDRLT);
+                    if scheduler_channel.send(task).is_err() {
+                        warn!(FCP_LOG, "{}", FATAL_SNDTSK; "sector_id" => sector_id);
+                    }
                 }
                 SealerInput::Shutdown => break,
             }
@@ -0,0 +1,292 @@
+use crate::api::sector_builder::sealer::SealerInput;
+use crate::api::sector_builder::ProverId;
+use crate::api::sector_builder::SectorId;
+use crate::error::ExpectWithBacktrace;
+use sector_base::api::sector_access::SectorAccess;
+use std::cmp::Reverse;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+const FATAL_NOLOCK: &str = "error acquiring sealer queue lock";
+
+// Work waiting for a SealerWorker. Unseals are served ahead of seals and
+// seals are served in order of their sector's seal priority. A seal which has
+// waited longer than max_wait is served ahead of other seals, oldest first, so
+// that a steady stream of high-priority seals cannot starve low-priority ones.
+// Seals never preempt unseals, however long they have waited.
+pub struct SealerQueue {
+    state: Mutex<QueueState>,
+    available: Condvar,
+    max_wait: Duration,
+}
+
+#[derive(Default)]
+struct QueueState {
+    tasks: Vec<QueuedTask>,
+    nonce: u64,
+}
+
+struct QueuedTask {
+    input: SealerInput,
+    enqueued_at: Instant,
+    seq: u64,
+}
+
+impl SealerQueue {
+    pub fn new(max_wait: Duration) -> SealerQueue {
+        SealerQueue {
+            state: Default::default(),
+            available: Condvar::new(),
+            max_wait,
+        }
+    }
+
+    pub fn push(&self, input: SealerInput) {
+        let mut state = self.state.lock().expects(FATAL_NOLOCK);
+
+        state.nonce += 1;
+        let seq = state.nonce;
+
+        state.tasks.push(QueuedTask {
+            input,
+            enqueued_at: Instant::now(),
+            seq,
+        });
+
+        self.available.notify_one();
+    }
+
+    // Blocks until a task is available and returns the one which should be
+    // served next.
+    pub fn pop(&self) -> SealerInput {
+        let mut state = self.state.lock().expects(FATAL_NOLOCK);
+
+        loop {
+            if let Some(idx) = next_task(&state.tasks, Instant::now(), self.max_wait) {
+                return state.tasks.swap_remove(idx).input;
+            }
+
+            state = self.available.wait(state).expects(FATAL_NOLOCK);
+        }
+    }
+
+    // Updates the priority of a queued seal. Returns false, having no effect,
+    // if the sector is not waiting to be sealed.
+    pub fn set_seal_priority(
+        &self,
+        prover_id: &ProverId,
+        sector_id: SectorId,
+        priority: u8,
+    ) -> bool {
+        let mut state = self.state.lock().expects(FATAL_NOLOCK);
+
+        let mut found = false;
+
+        for task in state.tasks.iter_mut() {
+            if let SealerInput::Seal(ref p, ref mut staged_sector, ..) = task.input {
+                if p == prover_id && staged_sector.sector_id == sector_id {
+                    staged_sector.seal_priority = priority;
+                    found = true;
+                }
+            }
+        }
+
+        found
+    }
+
+    // Removes all of the prover's queued seals and returns the sealed sectors
+    // which had been provisioned for them.
+    pub fn withdraw_seals(&self, prover_id: &ProverId) -> Vec<SectorAccess> {
+        let mut state = self.state.lock().expects(FATAL_NOLOCK);
+
+        let (withdrawn, kept): (Vec<QueuedTask>, Vec<QueuedTask>) =
+            state.tasks.drain(..).partition(|task| match task.input {
+                SealerInput::Seal(ref p, ..) => p == prover_id,
+                _ => false,
+            });
+
+        state.tasks = kept;
+
+        withdrawn
+            .into_iter()
+            .filter_map(|task| match task.input {
+                SealerInput::Seal(_, _, sealed_sector_access, _) => Some(sealed_sector_access),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// Returns the index of the task which should be served next, if any.
+fn next_task(tasks: &[QueuedTask], now: Instant, max_wait: Duration) -> Option<usize> {
+    tasks
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, task)| rank(task, now, max_wait))
+        .map(|(idx, _)| idx)
+}
+
+// Tasks with a lower rank are served first. Exports and imports are served
+// after unseals but ahead of seals. Seals which have waited longer than
+// max_wait are served ahead of other seals, oldest first. Shutdown is served
+// only once no other work remains.
+fn rank(task: &QueuedTask, now: Instant, max_wait: Duration) -> (u8, u8, Reverse<u8>, u64) {
+    match task.input {
+        SealerInput::Unseal(..) | SealerInput::UnsealRange(..) => (0, 0, Reverse(0), task.seq),
+        SealerInput::ExportSector(..) | SealerInput::ImportSector(..) => {
+            (1, 0, Reverse(0), task.seq)
+        }
+        SealerInput::Seal(_, ref staged_sector, ..) => {
+            if now.duration_since(task.enqueued_at) >= max_wait {
+                (2, 0, Reverse(0), task.seq)
+            } else {
+                (2, 1, Reverse(staged_sector.seal_priority), task.seq)
+            }
+        }
+        SealerInput::Shutdown => (3, 0, Reverse(0), task.seq),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::sector_builder::metadata::{SealedSectorMetadata, StagedSectorMetadata};
+    use std::sync::mpsc;
+
+    fn seal(sector_id: SectorId, seal_priority: u8) -> SealerInput {
+        let (tx, _) = mpsc::sync_channel(0);
+
+        SealerInput::Seal(
+            [0; 31],
+            StagedSectorMetadata {
+                sector_id,
+                seal_priority,
+                ..Default::default()
+            },
+            Default::default(),
+            tx,
+        )
+    }
+
+    fn unseal(piece_key: &str) -> SealerInput {
+        let (tx, _) = mpsc::sync_channel(0);
+
+        SealerInput::Unseal(
+            [0; 31],
+            piece_key.to_string(),
+            Box::new(SealedSectorMetadata::default()),
+            tx,
+        )
+    }
+
+    fn describe(input: &SealerInput) -> String {
+        match input {
+            SealerInput::Seal(_, staged_sector, ..) => format!("seal {}", staged_sector.sector_id),
+            SealerInput::Unseal(_, piece_key, _, _) => format!("unseal {}", piece_key),
+            SealerInput::UnsealRange(_, range, _, _) => format!("unseal {}", range.piece_key),
+            SealerInput::ExportSector(_, sealed_sector, _, _) => {
+                format!("export {}", sealed_sector.sector_id)
+            }
+            SealerInput::ImportSector(_, manifest, _, _, _) => {
+                format!("import {}", manifest.sector.sector_id)
+            }
+            SealerInput::Shutdown => "shutdown".to_string(),
+        }
+    }
+
+    fn drain(tasks: &mut Vec<QueuedTask>, now: Instant, max_wait: Duration) -> Vec<String> {
+        let mut order = Vec::new();
+
+        while let Some(idx) = next_task(tasks, now, max_wait) {
+            order.push(describe(&tasks.swap_remove(idx).input));
+        }
+
+        order
+    }
+
+    fn queued(inputs: Vec<(SealerInput, Instant)>) -> Vec<QueuedTask> {
+        inputs
+            .into_iter()
+            .enumerate()
+            .map(|(n, (input, enqueued_at))| QueuedTask {
+                input,
+                enqueued_at,
+                seq: n as u64,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unseals_before_seals_by_priority() {
+        let t0 = Instant::now();
+
+        let mut tasks = queued(vec![
+            (SealerInput::Shutdown, t0),
+            (seal(1, 0), t0),
+            (seal(2, 5), t0),
+            (unseal("a"), t0),
+            (seal(3, 5), t0),
+            (unseal("b"), t0),
+        ]);
+
+        assert_eq!(
+            drain(&mut tasks, t0, Duration::from_secs(60)),
+            vec!["unseal a", "unseal b", "seal 2", "seal 3", "seal 1", "shutdown"]
+        );
+    }
+
+    #[test]
+    fn test_starved_seals_served_before_other_seals() {
+        let t0 = Instant::now();
+        let t1 = t0 + Duration::from_secs(60);
+        let now = t0 + Duration::from_secs(90);
+
+        let mut tasks = queued(vec![(seal(1, 0), t0), (seal(2, 9), t1), (seal(3, 0), t0)]);
+
+        assert_eq!(
+            drain(&mut tasks, now, Duration::from_secs(60)),
+            vec!["seal 1", "seal 3", "seal 2"]
+        );
+    }
+
+    #[test]
+    fn test_starved_seals_never_preempt_unseals() {
+        let t0 = Instant::now();
+        let t1 = t0 + Duration::from_secs(60);
+        let now = t0 + Duration::from_secs(90);
+
+        let mut tasks = queued(vec![(seal(1, 0), t0), (unseal("a"), t1), (seal(2, 9), t1)]);
+
+        assert_eq!(
+            drain(&mut tasks, now, Duration::from_secs(60)),
+            vec!["unseal a", "seal 1", "seal 2"]
+        );
+    }
+
+    #[test]
+    fn test_set_seal_priority() {
+        let queue = SealerQueue::new(Duration::from_secs(60));
+
+        queue.push(seal(1, 0));
+        queue.push(seal(2, 0));
+        assert!(queue.set_seal_priority(&[0; 31], 2, 1));
+        assert!(!queue.set_seal_priority(&[0; 31], 3, 1));
+
+        assert_eq!(describe(&queue.pop()), "seal 2");
+        assert_eq!(describe(&queue.pop()), "seal 1");
+    }
+
+    #[test]
+    fn test_withdraw_seals() {
+        let queue = SealerQueue::new(Duration::from_secs(60));
+
+        queue.push(seal(1, 0));
+        queue.push(unseal("a"));
+        queue.push(seal(2, 0));
+
+        assert_eq!(queue.withdraw_seals(&[1; 31]).len(), 0);
+        assert_eq!(queue.withdraw_seals(&[0; 31]).len(), 2);
+
+        assert_eq!(describe(&queue.pop()), "unseal a");
+    }
+}
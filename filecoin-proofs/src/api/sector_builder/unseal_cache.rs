@@ -0,0 +1,276 @@
+use crate::api::sector_builder::ProverId;
+use crate::api::sector_builder::SectorId;
+use crate::error;
+use crate::error::ExpectWithBacktrace;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const FATAL_NOLOCK: &str = "error acquiring unseal cache lock";
+
+// Subdirectory of the configured directory in which the cache keeps its files.
+const CACHE_SUBDIR: &str = "unseal-cache";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnsealCacheConfig {
+    // Directory in which unsealed copies of sealed sectors are kept, in a
+    // subdirectory owned by the cache. Copies left there by a previous cache
+    // are discarded when the cache is created; other files are left alone.
+    pub dir: PathBuf,
+
+    // Upper bound on the total size of all cached copies. Once reached, the
+    // least recently used copies are evicted to make room for new ones.
+    pub max_bytes: u64,
+}
+
+// An on-disk cache of unsealed sector copies, shared by all sealer workers.
+// Retrievals which hit the cache are served from the copy instead of
+// unsealing the replica again.
+pub struct UnsealCache {
+    config: UnsealCacheConfig,
+    dir: PathBuf,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<(ProverId, SectorId), CacheEntry>,
+    num_bytes: u64,
+
+    // Incremented on every access. Used to order entries by recency and to
+    // name temporary files.
+    clock: u64,
+}
+
+struct CacheEntry {
+    num_bytes: u64,
+    last_used: u64,
+}
+
+impl UnsealCache {
+    pub fn new(config: UnsealCacheConfig) -> error::Result<UnsealCache> {
+        let dir = config.dir.join(CACHE_SUBDIR);
+        fs::create_dir_all(&dir)?;
+
+        // Only files named as the cache names them are removed, so that a
+        // misconfigured directory cannot cost the caller any data.
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+
+            let is_cache_file = entry.file_type()?.is_file()
+                && entry
+                    .file_name()
+                    .to_str()
+                    .map(is_cache_file_name)
+                    .unwrap_or(false);
+
+            if is_cache_file {
+                fs::remove_file(entry.path())?;
+            }
+        }
+
+        Ok(UnsealCache {
+            config,
+            dir,
+            state: Default::default(),
+        })
+    }
+
+    // Returns true if an unsealed copy of the provided size could be cached.
+    pub fn fits(&self, num_bytes: u64) -> bool {
+        num_bytes <= self.config.max_bytes
+    }
+
+    // Opens the cached copy of the sector, marking it as most recently used.
+    // The file is opened while the cache is locked, so a concurrent eviction
+    // cannot remove it from under the caller.
+    pub fn get(&self, prover_id: &ProverId, sector_id: SectorId) -> Option<File> {
+        let mut state = self.state.lock().expects(FATAL_NOLOCK);
+
+        state.clock += 1;
+        let clock = state.clock;
+
+        let entry = state.entries.get_mut(&(*prover_id, sector_id))?;
+        entry.last_used = clock;
+
+        File::open(self.entry_path(prover_id, sector_id)).ok()
+    }
+
+    // Returns a path, unique within this cache, to which a sector may be
+    // unsealed before being inserted.
+    pub fn temp_path(&self) -> PathBuf {
+        let mut state = self.state.lock().expects(FATAL_NOLOCK);
+        state.clock += 1;
+
+        self.dir.join(format!("tmp-{}", state.clock))
+    }
+
+    // Moves the unsealed copy at temp_path into the cache, evicting least
+    // recently used copies as needed, and opens it. If another worker cached
+    // the same sector in the meantime, the temporary copy is discarded.
+    pub fn insert(
+        &self,
+        prover_id: &ProverId,
+        sector_id: SectorId,
+        temp_path: &Path,
+    ) -> error::Result<File> {
+        let num_bytes = fs::metadata(temp_path)?.len();
+
+        let mut state = self.state.lock().expects(FATAL_NOLOCK);
+
+        state.clock += 1;
+        let clock = state.clock;
+
+        let key = (*prover_id, sector_id);
+        let entry_path = self.entry_path(prover_id, sector_id);
+
+        if let Some(entry) = state.entries.get_mut(&key) {
+            entry.last_used = clock;
+            fs::remove_file(temp_path)?;
+
+            return Ok(File::open(entry_path)?);
+        }
+
+        while state.num_bytes + num_bytes > self.config.max_bytes {
+            let lru = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+
+            let evicted = lru.and_then(|lru| state.entries.remove(&lru).map(|e| (lru, e)));
+
+            match evicted {
+                Some(((prover_id, sector_id), entry)) => {
+                    state.num_bytes -= entry.num_bytes;
+                    fs::remove_file(self.entry_path(&prover_id, sector_id))?;
+                }
+                None => break,
+            }
+        }
+
+        fs::rename(temp_path, &entry_path)?;
+
+        state.entries.insert(
+            key,
+            CacheEntry {
+                num_bytes,
+                last_used: clock,
+            },
+        );
+        state.num_bytes += num_bytes;
+
+        Ok(File::open(entry_path)?)
+    }
+
+    fn entry_path(&self, prover_id: &ProverId, sector_id: SectorId) -> PathBuf {
+        let prover_hex: String = prover_id.iter().map(|b| format!("{:02x}", b)).collect();
+
+        self.dir.join(format!("{}-{}", prover_hex, sector_id))
+    }
+}
+
+// Returns true if the file name is one which the cache gives to a cached copy
+// ("<prover id as hex>-<sector id>") or to a temporary file ("tmp-<n>").
+fn is_cache_file_name(name: &str) -> bool {
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+    match name.find('-') {
+        Some(idx) => {
+            let (prefix, suffix) = (&name[..idx], &name[idx + 1..]);
+
+            let is_prover_hex = prefix.len() == 62 && prefix.bytes().all(|b| b.is_ascii_hexdigit());
+
+            (prefix == "tmp" || is_prover_hex) && is_number(suffix)
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn insert_bytes(cache: &UnsealCache, sector_id: SectorId, bytes: &[u8]) {
+        let temp_path = cache.temp_path();
+        fs::write(&temp_path, bytes).unwrap();
+        cache.insert(&[0; 31], sector_id, &temp_path).unwrap();
+    }
+
+    fn read_cached(cache: &UnsealCache, sector_id: SectorId) -> Option<Vec<u8>> {
+        cache.get(&[0; 31], sector_id).map(|mut file| {
+            let mut buf = Vec::new();
+            file.read_to_end(&mut buf).unwrap();
+            buf
+        })
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let cache = UnsealCache::new(UnsealCacheConfig {
+            dir: dir.path().join("unsealed"),
+            max_bytes: 10,
+        })
+        .unwrap();
+
+        assert!(cache.fits(10));
+        assert!(!cache.fits(11));
+
+        insert_bytes(&cache, 1, &[1; 4]);
+        insert_bytes(&cache, 2, &[2; 4]);
+
+        // touch sector 1, making sector 2 the least recently used
+        assert_eq!(read_cached(&cache, 1), Some(vec![1; 4]));
+
+        insert_bytes(&cache, 3, &[3; 4]);
+
+        assert_eq!(read_cached(&cache, 1), Some(vec![1; 4]));
+        assert_eq!(read_cached(&cache, 2), None);
+        assert_eq!(read_cached(&cache, 3), Some(vec![3; 4]));
+
+        // a second insert of a cached sector keeps the existing copy
+        insert_bytes(&cache, 3, &[9; 2]);
+        assert_eq!(read_cached(&cache, 3), Some(vec![3; 4]));
+
+        assert_eq!(
+            fs::read_dir(dir.path().join("unsealed").join(CACHE_SUBDIR))
+                .unwrap()
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_new_removes_only_cache_files() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let config = UnsealCacheConfig {
+            dir: dir.path().to_path_buf(),
+            max_bytes: 10,
+        };
+
+        let cache = UnsealCache::new(config.clone()).unwrap();
+        insert_bytes(&cache, 1, &[1; 4]);
+        fs::write(cache.temp_path(), &[2; 4]).unwrap();
+        drop(cache);
+
+        fs::write(dir.path().join("precious"), &[3; 4]).unwrap();
+        fs::write(dir.path().join(CACHE_SUBDIR).join("notes"), &[4; 4]).unwrap();
+
+        let cache = UnsealCache::new(config).unwrap();
+        assert_eq!(read_cached(&cache, 1), None);
+
+        let mut remaining: Vec<_> = fs::read_dir(dir.path().join(CACHE_SUBDIR))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        remaining.sort();
+
+        assert_eq!(remaining, vec!["notes"]);
+        assert_eq!(fs::read(dir.path().join("precious")).unwrap(), vec![3; 4]);
+    }
+}
@@ -23,11 +23,14 @@ extern crate serde_cbor;
 #[macro_use]
 extern crate serde_derive;
 extern crate blake2;
+extern crate fs2;
+extern crate memmap;
 #[macro_use]
 extern crate slog;
 
 pub mod api;
 pub mod error;
+pub mod metrics;
 pub mod param;
 pub mod serde_big_array;
 
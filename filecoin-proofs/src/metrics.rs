@@ -0,0 +1,304 @@
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const METRIC_PREFIX: &str = "filecoin_proofs";
+
+lazy_static! {
+    pub static ref METRICS: Metrics = Default::default();
+}
+
+// Accumulates the number of observations and their total duration, which is
+// enough to render a Prometheus summary (without quantiles).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timing {
+    pub count: u64,
+    pub sum_seconds: f64,
+}
+
+impl Timing {
+    fn observe(&mut self, duration: Duration) {
+        self.count += 1;
+        self.sum_seconds +=
+            duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0;
+    }
+}
+
+// Number of sectors known to a SectorBuilder, grouped by seal status.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SectorCounts {
+    pub pending: u64,
+    pub sealing: u64,
+    pub sealed: u64,
+    pub failed: u64,
+}
+
+// A point-in-time copy of all metrics. Fields which are derived from
+// SectorBuilder state (sector counts and staged bytes) are filled in by the
+// scheduler.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    pub seal_duration: Timing,
+    pub unseal_duration: Timing,
+    pub post_duration: Timing,
+    pub seals_failed: u64,
+    pub seal_queue_depth: u64,
+    pub unseal_queue_depth: u64,
+    pub staged_bytes: u64,
+    pub sectors: SectorCounts,
+    pub parameter_cache_hits: u64,
+    pub parameter_cache_misses: u64,
+    pub bytes_replicated: u64,
+    pub unseal_cache_hits: u64,
+    pub unseal_cache_misses: u64,
+}
+
+// Process-wide metrics registry. Counters are shared by all SectorBuilders
+// and by the parameter cache, which lives outside of any SectorBuilder.
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<MetricsSnapshot>,
+}
+
+impl Metrics {
+    pub fn record_seal(&self, duration: Duration, succeeded: bool) {
+        self.update(|m| {
+            m.seal_duration.observe(duration);
+            if !succeeded {
+                m.seals_failed += 1;
+            }
+        });
+    }
+
+    pub fn record_unseal(&self, duration: Duration) {
+        self.update(|m| m.unseal_duration.observe(duration));
+    }
+
+    pub fn record_post(&self, duration: Duration) {
+        self.update(|m| m.post_duration.observe(duration));
+    }
+
+    pub fn seal_enqueued(&self) {
+        self.update(|m| m.seal_queue_depth += 1);
+    }
+
+    pub fn seal_dequeued(&self) {
+        self.update(|m| m.seal_queue_depth = m.seal_queue_depth.saturating_sub(1));
+    }
+
+    pub fn unseal_enqueued(&self) {
+        self.update(|m| m.unseal_queue_depth += 1);
+    }
+
+    pub fn unseal_dequeued(&self) {
+        self.update(|m| m.unseal_queue_depth = m.unseal_queue_depth.saturating_sub(1));
+    }
+
+    pub fn record_parameter_cache_lookup(&self, hit: bool) {
+        self.update(|m| {
+            if hit {
+                m.parameter_cache_hits += 1;
+            } else {
+                m.parameter_cache_misses += 1;
+            }
+        });
+    }
+
+    pub fn record_unseal_cache_lookup(&self, hit: bool) {
+        self.update(|m| {
+            if hit {
+                m.unseal_cache_hits += 1;
+            } else {
+                m.unseal_cache_misses += 1;
+            }
+        });
+    }
+
+    pub fn add_bytes_replicated(&self, num_bytes: u64) {
+        self.update(|m| m.bytes_replicated += num_bytes);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.inner.lock().unwrap().clone()
+    }
+
+    fn update<F: FnOnce(&mut MetricsSnapshot)>(&self, f: F) {
+        f(&mut self.inner.lock().unwrap());
+    }
+}
+
+impl MetricsSnapshot {
+    // Renders the snapshot in the Prometheus text exposition format (v0.0.4).
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        write_summary(
+            &mut out,
+            "seal_duration_seconds",
+            "Time spent sealing sectors.",
+            self.seal_duration,
+        );
+        write_summary(
+            &mut out,
+            "unseal_duration_seconds",
+            "Time spent unsealing sectors.",
+            self.unseal_duration,
+        );
+        write_summary(
+            &mut out,
+            "post_duration_seconds",
+            "Time spent generating proofs-of-spacetime.",
+            self.post_duration,
+        );
+        write_single(
+            &mut out,
+            "seals_failed_total",
+            "Number of seal operations which produced an error.",
+            "counter",
+            self.seals_failed,
+        );
+        write_single(
+            &mut out,
+            "seal_queue_depth",
+            "Number of seal tasks waiting for a sealer worker.",
+            "gauge",
+            self.seal_queue_depth,
+        );
+        write_single(
+            &mut out,
+            "unseal_queue_depth",
+            "Number of unseal tasks waiting for a sealer worker.",
+            "gauge",
+            self.unseal_queue_depth,
+        );
+        write_single(
+            &mut out,
+            "staged_bytes",
+            "Number of user piece-bytes in staged sectors.",
+            "gauge",
+            self.staged_bytes,
+        );
+
+        write_header(
+            &mut out,
+            "sectors",
+            "Number of sectors, by seal status.",
+            "gauge",
+        );
+        for (status, n) in &[
+            ("pending", self.sectors.pending),
+            ("sealing", self.sectors.sealing),
+            ("sealed", self.sectors.sealed),
+            ("failed", self.sectors.failed),
+        ] {
+            let _ = writeln!(
+                out,
+                "{}_sectors{{status=\"{}\"}} {}",
+                METRIC_PREFIX, status, n
+            );
+        }
+
+        write_header(
+            &mut out,
+            "parameter_cache_lookups_total",
+            "Number of Groth parameter and verifying key memory cache lookups.",
+            "counter",
+        );
+        for (result, n) in &[
+            ("hit", self.parameter_cache_hits),
+            ("miss", self.parameter_cache_misses),
+        ] {
+            let _ = writeln!(
+                out,
+                "{}_parameter_cache_lookups_total{{result=\"{}\"}} {}",
+                METRIC_PREFIX, result, n
+            );
+        }
+
+        write_single(
+            &mut out,
+            "bytes_replicated_total",
+            "Number of sector bytes replicated by seal.",
+            "counter",
+            self.bytes_replicated,
+        );
+
+        write_header(
+            &mut out,
+            "unseal_cache_lookups_total",
+            "Number of unsealed sector cache lookups.",
+            "counter",
+        );
+        for (result, n) in &[
+            ("hit", self.unseal_cache_hits),
+            ("miss", self.unseal_cache_misses),
+        ] {
+            let _ = writeln!(
+                out,
+                "{}_unseal_cache_lookups_total{{result=\"{}\"}} {}",
+                METRIC_PREFIX, result, n
+            );
+        }
+
+        out
+    }
+}
+
+fn write_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {}_{} {}", METRIC_PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", METRIC_PREFIX, name, kind);
+}
+
+fn write_single(out: &mut String, name: &str, help: &str, kind: &str, value: u64) {
+    write_header(out, name, help, kind);
+    let _ = writeln!(out, "{}_{} {}", METRIC_PREFIX, name, value);
+}
+
+fn write_summary(out: &mut String, name: &str, help: &str, timing: Timing) {
+    write_header(out, name, help, "summary");
+    let _ = writeln!(out, "{}_{}_sum {}", METRIC_PREFIX, name, timing.sum_seconds);
+    let _ = writeln!(out, "{}_{}_count {}", METRIC_PREFIX, name, timing.count);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_and_renders() {
+        let metrics: Metrics = Default::default();
+
+        metrics.record_seal(Duration::from_millis(1500), true);
+        metrics.record_seal(Duration::from_millis(500), false);
+        metrics.seal_enqueued();
+        metrics.seal_enqueued();
+        metrics.seal_dequeued();
+        metrics.unseal_dequeued();
+        metrics.record_parameter_cache_lookup(true);
+        metrics.record_parameter_cache_lookup(false);
+        metrics.record_parameter_cache_lookup(true);
+        metrics.add_bytes_replicated(1024);
+        metrics.record_unseal_cache_lookup(false);
+
+        let mut snapshot = metrics.snapshot();
+        snapshot.sectors.sealed = 3;
+
+        assert_eq!(snapshot.seal_duration.count, 2);
+        assert_eq!(snapshot.seal_duration.sum_seconds, 2.0);
+        assert_eq!(snapshot.seals_failed, 1);
+        assert_eq!(snapshot.seal_queue_depth, 1);
+        assert_eq!(snapshot.unseal_queue_depth, 0);
+        assert_eq!(snapshot.parameter_cache_hits, 2);
+        assert_eq!(snapshot.parameter_cache_misses, 1);
+
+        let text = snapshot.to_prometheus();
+
+        assert!(text.contains("# TYPE filecoin_proofs_seal_duration_seconds summary\n"));
+        assert!(text.contains("filecoin_proofs_seal_duration_seconds_sum 2\n"));
+        assert!(text.contains("filecoin_proofs_seal_duration_seconds_count 2\n"));
+        assert!(text.contains("filecoin_proofs_sectors{status=\"sealed\"} 3\n"));
+        assert!(text.contains("filecoin_proofs_parameter_cache_lookups_total{result=\"hit\"} 2\n"));
+        assert!(text.contains("filecoin_proofs_bytes_replicated_total 1024\n"));
+        assert!(text.contains("filecoin_proofs_unseal_cache_lookups_total{result=\"miss\"} 1\n"));
+    }
+}
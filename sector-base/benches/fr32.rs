@@ -0,0 +1,58 @@
+#[macro_use]
+extern crate criterion;
+extern crate rand;
+extern crate sector_base;
+
+use criterion::{Criterion, ParameterizedBenchmark, Throughput};
+use rand::{thread_rng, Rng};
+use sector_base::io::fr32::{padded_bytes, write_padded, write_padded_aligned, Fr32Writer};
+use std::io::{Cursor, Write};
+use std::time::Duration;
+
+fn random_data(size: usize) -> Vec<u8> {
+    let mut rng = thread_rng();
+    (0..size).map(|_| rng.gen()).collect()
+}
+
+fn fr32_benchmark(c: &mut Criterion) {
+    c.bench(
+        "fr32",
+        ParameterizedBenchmark::new(
+            "write_padded_aligned",
+            |b, size| {
+                let data = &random_data(*size);
+
+                b.iter(|| {
+                    let mut padded = Vec::with_capacity(padded_bytes(data.len()));
+                    write_padded_aligned(data, &mut padded).unwrap();
+                    padded
+                })
+            },
+            vec![127, 1024, 127_000, 1_024_000, 16_256_000],
+        )
+        .with_function("write_padded", |b, size| {
+            let data = &random_data(*size);
+
+            b.iter(|| {
+                let mut cursor = Cursor::new(Vec::with_capacity(padded_bytes(data.len())));
+                write_padded(data, &mut cursor).unwrap();
+                cursor.into_inner()
+            })
+        })
+        .with_function("Fr32Writer", |b, size| {
+            let data = &random_data(*size);
+
+            b.iter(|| {
+                let mut writer = Fr32Writer::new(Vec::with_capacity(padded_bytes(data.len())));
+                writer.write_all(data).unwrap();
+                writer.finish().unwrap()
+            })
+        })
+        .sample_size(10)
+        .throughput(|s| Throughput::Bytes(*s as u32))
+        .warm_up_time(Duration::from_secs(1)),
+    );
+}
+
+criterion_group!(benches, fr32_benchmark);
+criterion_main!(benches);
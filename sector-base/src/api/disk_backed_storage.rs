@@ -1,11 +1,20 @@
 use libc;
-use std::fs::{create_dir_all, remove_file, File, OpenOptions};
+use std::collections::HashMap;
 use std::io::{Read, Seek, SeekFrom};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use storage_backend::error::{is_locked, is_not_found};
+use storage_backend::{Checksummed, LocalFs, LockMode, ObjectCursor, ObjectLock, StorageBackend};
 
 use crate::api::bytes_amount::{PaddedBytesAmount, UnpaddedBytesAmount};
 use crate::api::errors::SectorManagerErr;
+use crate::api::placement::placement_order;
+use crate::api::placement::PlacementPolicy;
+use crate::api::sector_access::{SectorAccess, FILE_SCHEME};
+use crate::api::sector_store::RootCapacity;
 use crate::api::sector_store::SectorConfig;
+use crate::api::sector_store::SectorKind;
 use crate::api::sector_store::SectorManager;
 use crate::api::sector_store::SectorStore;
 use crate::api::util;
@@ -26,6 +35,10 @@ pub const TEST_SECTOR_SIZE: u64 = 1024;
 // Sector size, in bytes, during live operation.
 pub const LIVE_SECTOR_SIZE: u64 = 1 << 28; // 256MiB
 
+// Size, in sectors, of the temporary files which may be written while sealing.
+// Merkle trees, for example, are about twice the size of the data they commit to.
+pub const SEALING_SCRATCH_SECTORS: u64 = 2;
+
 /// Initializes and returns a boxed SectorStore instance with very small, unrealistic/insecure parameters
 /// for use in testing.
 ///
@@ -77,116 +90,643 @@ pub unsafe extern "C" fn destroy_storage(ss_ptr: *mut Box<SectorStore>) {
     let _ = Box::from_raw(ss_ptr);
 }
 
+/// A directory (or other storage location) which holds sectors. A sector's
+/// access has the root's scheme and the name of its object in the backend
+/// joined to the root's path, e.g. `file:///sectors/staging/staged-sector-1`.
+pub struct SectorRoot {
+    pub scheme: String,
+    pub path: String,
+    pub backend: Box<StorageBackend>,
+}
+
+impl SectorRoot {
+    /// Returns a root backed by the local directory at `path`.
+    pub fn local(path: String) -> SectorRoot {
+        SectorRoot {
+            scheme: FILE_SCHEME.to_string(),
+            backend: Box::new(LocalFs::new(path.clone())),
+            path,
+        }
+    }
+
+    /// Returns a root backed by the local directory at `path` which records a
+    /// checksum of each sector when it is closed and checks it when the sector
+    /// is next read through the SectorManager.
+    pub fn local_checksummed(path: String) -> SectorRoot {
+        SectorRoot {
+            scheme: FILE_SCHEME.to_string(),
+            backend: Box::new(Checksummed::new(LocalFs::new(path.clone()))),
+            path,
+        }
+    }
+
+    fn access(&self, name: &str) -> Result<SectorAccess, SectorManagerErr> {
+        Path::new(&self.path)
+            .join(name)
+            .to_str()
+            .map(|path| SectorAccess::new(&self.scheme, path))
+            .ok_or_else(|| SectorManagerErr::ReceiverError("could not create pbuf".to_string()))
+    }
+}
+
+// The roots holding sectors of one kind (staged or sealed).
+struct SectorRoots {
+    kind: SectorKind,
+    roots: Vec<SectorRoot>,
+
+    // Number of sectors provisioned so far, used for round-robin placement.
+    num_provisioned: AtomicUsize,
+
+    // Space set aside for each provisioned sector, by the sector's name. The
+    // lock is held while provisioning so that concurrently provisioned
+    // sectors cannot be promised the same space.
+    reservations: Mutex<HashMap<String, Reservation>>,
+}
+
+struct Reservation {
+    // Index of the root holding the sector.
+    root: usize,
+    num_bytes: u64,
+}
+
+impl SectorRoots {
+    fn new(kind: SectorKind, roots: Vec<SectorRoot>) -> SectorRoots {
+        SectorRoots {
+            kind,
+            roots,
+            num_provisioned: AtomicUsize::new(0),
+            reservations: Default::default(),
+        }
+    }
+
+    // Returns the number of bytes available on each root and the number of
+    // those which are reserved. A reservation only covers the part of its
+    // sector which has not been written yet, since the rest is no longer
+    // available. Backends which cannot report their available space are
+    // assumed to have plenty.
+    fn usage(&self, reservations: &HashMap<String, Reservation>) -> Vec<(u64, u64)> {
+        let mut usage: Vec<(u64, u64)> = self
+            .roots
+            .iter()
+            .map(|root| {
+                let available_bytes = root
+                    .backend
+                    .available_bytes()
+                    .unwrap_or_else(|_| u64::max_value());
+
+                (available_bytes, 0)
+            })
+            .collect();
+
+        for (name, reservation) in reservations {
+            let written_bytes = self.roots[reservation.root].backend.size(name).unwrap_or(0);
+
+            let reserved_bytes = &mut usage[reservation.root].1;
+            *reserved_bytes += reservation.num_bytes.saturating_sub(written_bytes);
+        }
+
+        usage
+    }
+}
+
+/// Stores staged and sealed sectors across any number of roots, each of which
+/// may use a different backend. New sectors are placed according to a
+/// PlacementPolicy, passing over roots which do not have room for a sector.
+///
+/// Provisioning a sector reserves room for it (and, for sealed sectors, the
+/// temporary files written while sealing) on its root until the reservation is
+/// released. Reservations are kept in memory only, so those of sectors which
+/// are still being filled or sealed are restored when a SectorBuilder starts.
 pub struct DiskManager {
-    staging_path: String,
-    sealed_path: String,
+    staging: SectorRoots,
+    sealed: SectorRoots,
+    policy: PlacementPolicy,
+
+    // Space reserved for a new staged or sealed sector.
+    staged_reservation_bytes: u64,
+    sealed_reservation_bytes: u64,
+
+    // Number of unpadded bytes which fit in a sector.
+    max_unsealed_bytes: UnpaddedBytesAmount,
 }
 
 impl SectorManager for DiskManager {
-    fn new_sealed_sector_access(&self) -> Result<String, SectorManagerErr> {
-        self.new_sector_access(Path::new(&self.sealed_path))
+    fn new_sealed_sector_access(&self) -> Result<SectorAccess, SectorManagerErr> {
+        self.new_sector_access(&self.sealed, None)
     }
 
-    fn new_staging_sector_access(&self) -> Result<String, SectorManagerErr> {
-        self.new_sector_access(Path::new(&self.staging_path))
+    fn new_staging_sector_access(&self) -> Result<SectorAccess, SectorManagerErr> {
+        self.new_sector_access(&self.staging, None)
     }
 
-    fn num_unsealed_bytes(&self, access: &str) -> Result<u64, SectorManagerErr> {
-        OpenOptions::new()
-            .read(true)
-            .open(access)
-            .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))
-            .map(|mut f| {
-                target_unpadded_bytes(&mut f)
-                    .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))
-            })
-            .and_then(|n| n)
+    fn new_sealed_sector_access_for(
+        &self,
+        prover_id: &[u8; 31],
+        sector_id: u64,
+    ) -> Result<SectorAccess, SectorManagerErr> {
+        self.new_sector_access(&self.sealed, Some((prover_id, sector_id)))
     }
 
-    fn truncate_unsealed(&self, access: &str, size: u64) -> Result<(), SectorManagerErr> {
-        // I couldn't wrap my head around all ths result mapping, so here it is all laid out.
-        match OpenOptions::new().write(true).open(&access) {
-            Ok(mut file) => match almost_truncate_to_unpadded_bytes(&mut file, size) {
-                Ok(padded_size) => match file.set_len(padded_size as u64) {
-                    Ok(_) => Ok(()),
-                    Err(err) => Err(SectorManagerErr::ReceiverError(format!("{:?}", err))),
-                },
-                Err(err) => Err(SectorManagerErr::ReceiverError(format!("{:?}", err))),
-            },
-            Err(err) => Err(SectorManagerErr::CallerError(format!("{:?}", err))),
+    fn new_staging_sector_access_for(
+        &self,
+        prover_id: &[u8; 31],
+        sector_id: u64,
+    ) -> Result<SectorAccess, SectorManagerErr> {
+        self.new_sector_access(&self.staging, Some((prover_id, sector_id)))
+    }
+
+    fn sector_root(&self, access: &SectorAccess) -> Result<String, SectorManagerErr> {
+        self.locate(access).map(|(_, root, _)| root.path.clone())
+    }
+
+    fn local_path(&self, access: &SectorAccess) -> Result<PathBuf, SectorManagerErr> {
+        let (backend, name) = self.resolve(access)?;
+
+        backend.local_path(name).ok_or_else(|| {
+            SectorManagerErr::CallerError(format!("sector {} is not held in a local file", access))
+        })
+    }
+
+    fn sector_exists(&self, access: &SectorAccess) -> Result<bool, SectorManagerErr> {
+        let (backend, name) = self.resolve(access)?;
+
+        match backend.size(name) {
+            Ok(_) => Ok(true),
+            Err(ref err) if is_not_found(err) => Ok(false),
+            Err(err) => Err(SectorManagerErr::ReceiverError(format!("{:?}", err))),
+        }
+    }
+
+    fn lock_sector(
+        &self,
+        access: &SectorAccess,
+        mode: LockMode,
+    ) -> Result<Box<ObjectLock>, SectorManagerErr> {
+        let (backend, name) = self.resolve(access)?;
+
+        backend.lock(name, mode).map_err(|err| {
+            if is_locked(&err) {
+                SectorManagerErr::SectorLocked(access.to_string())
+            } else {
+                caller_or_receiver_err(err)
+            }
+        })
+    }
+
+    fn capacity(&self) -> Result<Vec<RootCapacity>, SectorManagerErr> {
+        let mut capacity = Vec::new();
+
+        for roots in &[&self.staging, &self.sealed] {
+            let reservations = roots
+                .reservations
+                .lock()
+                .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+            for (root, (available_bytes, reserved_bytes)) in
+                roots.roots.iter().zip(roots.usage(&reservations))
+            {
+                capacity.push(RootCapacity {
+                    root: root.path.clone(),
+                    kind: roots.kind,
+                    available_bytes,
+                    reserved_bytes,
+                });
+            }
+        }
+
+        Ok(capacity)
+    }
+
+    fn reservation_bytes(&self, kind: SectorKind) -> u64 {
+        match kind {
+            SectorKind::Staged => self.staged_reservation_bytes,
+            SectorKind::Sealed => self.sealed_reservation_bytes,
         }
     }
 
-    // TODO: write_and_preprocess should refuse to write more data than will fit. In that case, return 0.
+    fn release_reservation(&self, access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        let (roots, _, name) = self.locate(access)?;
+
+        roots
+            .reservations
+            .lock()
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?
+            .remove(name);
+
+        Ok(())
+    }
+
+    fn restore_reservation(&self, access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        let (roots, root, name) = self.locate(access)?;
+
+        let idx = roots
+            .roots
+            .iter()
+            .position(|r| std::ptr::eq(r, root))
+            .expect("located root is one of its kind's roots");
+
+        roots
+            .reservations
+            .lock()
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?
+            .insert(
+                name.to_string(),
+                Reservation {
+                    root: idx,
+                    num_bytes: self.reservation_bytes(roots.kind),
+                },
+            );
+
+        Ok(())
+    }
+
+    fn num_unsealed_bytes(&self, access: &SectorAccess) -> Result<u64, SectorManagerErr> {
+        let (backend, name) = self.resolve(access)?;
+
+        backend.size(name).map_err(caller_or_receiver_err)?;
+
+        target_unpadded_bytes(&mut ObjectCursor::new(backend, name))
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))
+    }
+
+    fn truncate_unsealed(&self, access: &SectorAccess, size: u64) -> Result<(), SectorManagerErr> {
+        let (backend, name) = self.resolve(access)?;
+
+        let _lock = self.lock_sector(access, LockMode::Exclusive)?;
+
+        let padded_size =
+            almost_truncate_to_unpadded_bytes(&mut ObjectCursor::new(backend, name), size)
+                .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+        backend
+            .set_size(name, padded_size as u64)
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))
+    }
+
     fn write_and_preprocess(
         &self,
-        access: &str,
+        access: &SectorAccess,
         data: &[u8],
     ) -> Result<UnpaddedBytesAmount, SectorManagerErr> {
-        OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(access)
-            .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))
-            .and_then(|mut file| {
-                write_padded(data, &mut file)
-                    .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))
-                    .map(|n| UnpaddedBytesAmount(n as u64))
-            })
+        self.write_within_capacity(access, data, false)
+    }
+
+    fn write_and_preprocess_partial(
+        &self,
+        access: &SectorAccess,
+        data: &[u8],
+    ) -> Result<UnpaddedBytesAmount, SectorManagerErr> {
+        self.write_within_capacity(access, data, true)
+    }
+
+    fn delete_staging_sector_access(&self, access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        self.delete_sector_access(access)
+    }
+
+    fn delete_sealed_sector_access(&self, access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        self.delete_sector_access(access)
     }
 
-    fn delete_staging_sector_access(&self, access: &str) -> Result<(), SectorManagerErr> {
-        remove_file(access).map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))
+    fn write_sector_tree(
+        &self,
+        access: &SectorAccess,
+        data: &[u8],
+    ) -> Result<(), SectorManagerErr> {
+        let (backend, name) = self.resolve(access)?;
+        let tree_name = sector_tree_name(name);
+
+        backend
+            .open(&tree_name)
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+        // held until the tree is durable, so that concurrent writers (e.g. in
+        // another process) cannot interleave their trees
+        let _lock = backend
+            .lock(&tree_name, LockMode::Exclusive)
+            .map_err(|err| {
+                if is_locked(&err) {
+                    SectorManagerErr::SectorLocked(access.to_string())
+                } else {
+                    caller_or_receiver_err(err)
+                }
+            })?;
+
+        backend
+            .set_size(&tree_name, 0)
+            .and_then(|_| backend.write_at(&tree_name, 0, data))
+            .and_then(|_| backend.close(&tree_name))
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))
+    }
+
+    fn sector_tree_path(&self, access: &SectorAccess) -> Result<Option<PathBuf>, SectorManagerErr> {
+        let (backend, name) = self.resolve(access)?;
+        let tree_name = sector_tree_name(name);
+
+        match backend.size(&tree_name) {
+            Ok(_) => Ok(backend.local_path(&tree_name)),
+            Err(ref err) if is_not_found(err) => Ok(None),
+            Err(err) => Err(SectorManagerErr::ReceiverError(format!("{:?}", err))),
+        }
+    }
+
+    fn close_sector(&self, access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        let (backend, name) = self.resolve(access)?;
+
+        backend.close(name).map_err(caller_or_receiver_err)
+    }
+
+    fn verify_sector(&self, access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        let (backend, name) = self.resolve(access)?;
+
+        backend.verify(name).map_err(caller_or_receiver_err)
     }
 
     fn read_raw(
         &self,
-        access: &str,
+        access: &SectorAccess,
         start_offset: u64,
         num_bytes: UnpaddedBytesAmount,
     ) -> Result<Vec<u8>, SectorManagerErr> {
-        OpenOptions::new()
-            .read(true)
-            .open(access)
-            .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))
-            .and_then(|mut file| -> Result<Vec<u8>, SectorManagerErr> {
-                file.seek(SeekFrom::Start(start_offset))
-                    .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))?;
+        let (backend, name) = self.resolve(access)?;
 
-                let mut buf = vec![0; usize::from(num_bytes)];
+        backend
+            .size(name)
+            .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))?;
 
-                file.read_exact(buf.as_mut_slice())
-                    .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))?;
+        let mut cursor = ObjectCursor::new(backend, name);
 
-                Ok(buf)
-            })
+        cursor
+            .seek(SeekFrom::Start(start_offset))
+            .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))?;
+
+        let mut buf = vec![0; usize::from(num_bytes)];
+
+        cursor
+            .read_exact(buf.as_mut_slice())
+            .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))?;
+
+        Ok(buf)
     }
 }
 
 impl DiskManager {
-    fn new_sector_access(&self, root: &Path) -> Result<String, SectorManagerErr> {
-        let pbuf = root.join(util::rand_alpha_string(32));
+    /// Creates a DiskManager which places staged sectors in the `staging`
+    /// roots and sealed sectors in the `sealed` roots. Staged sectors reserve
+    /// the configured sector size and sealed sectors additionally reserve the
+    /// configured sealing scratch space.
+    pub fn new(
+        staging: Vec<SectorRoot>,
+        sealed: Vec<SectorRoot>,
+        policy: PlacementPolicy,
+        config: &SectorConfig,
+    ) -> DiskManager {
+        let sector_bytes = u64::from(config.sector_bytes());
+
+        DiskManager {
+            staging: SectorRoots::new(SectorKind::Staged, staging),
+            sealed: SectorRoots::new(SectorKind::Sealed, sealed),
+            policy,
+            staged_reservation_bytes: sector_bytes,
+            sealed_reservation_bytes: sector_bytes + u64::from(config.sealing_scratch_bytes()),
+            max_unsealed_bytes: config.max_unsealed_bytes_per_sector(),
+        }
+    }
 
-        create_dir_all(root)
-            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))
-            .and_then(|_| {
-                File::create(&pbuf)
-                    .map(|_| 0)
-                    .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))
+    // Writes data to the staged sector if it fits. Otherwise, writes as much
+    // as fits if partial is set and produces an error if not.
+    fn write_within_capacity(
+        &self,
+        access: &SectorAccess,
+        data: &[u8],
+        partial: bool,
+    ) -> Result<UnpaddedBytesAmount, SectorManagerErr> {
+        let (backend, name) = self.resolve(access)?;
+
+        // held until the write is done, so that concurrent writers (e.g. in
+        // another process) cannot interleave their padding with ours
+        let _lock = self.lock_sector(access, LockMode::Exclusive)?;
+
+        let mut cursor = ObjectCursor::new(backend, name);
+
+        let num_unsealed_bytes = target_unpadded_bytes(&mut cursor)
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+        let num_bytes = fit_to_capacity(
+            self.max_unsealed_bytes,
+            num_unsealed_bytes,
+            data.len(),
+            partial,
+        )?;
+
+        let n = write_padded(&data[..num_bytes], &mut cursor)
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+        Ok(UnpaddedBytesAmount(n as u64))
+    }
+
+    // Creates a sector in the first root, in placement order, which has room
+    // for it, and reserves that room. Sectors provisioned for a prover's
+    // sector id are named after both, so that operators can find them.
+    fn new_sector_access(
+        &self,
+        roots: &SectorRoots,
+        sector: Option<(&[u8; 31], u64)>,
+    ) -> Result<SectorAccess, SectorManagerErr> {
+        let reservation_bytes = self.reservation_bytes(roots.kind);
+
+        let mut reservations = roots
+            .reservations
+            .lock()
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+        let unreserved_bytes: Vec<u64> = roots
+            .usage(&reservations)
+            .into_iter()
+            .map(|(available_bytes, reserved_bytes)| available_bytes.saturating_sub(reserved_bytes))
+            .collect();
+
+        let counter = roots.num_provisioned.fetch_add(1, Ordering::SeqCst);
+
+        let mut last_err = None;
+
+        let sector_id = sector.map(|(_, sector_id)| sector_id);
+
+        for idx in placement_order(self.policy, &unreserved_bytes, counter, sector_id) {
+            if unreserved_bytes[idx] < reservation_bytes {
+                continue;
+            }
+
+            let root = &roots.roots[idx];
+            let name = sector_name(roots.kind, sector, &|name| {
+                reservations.contains_key(name) || root.backend.size(name).is_ok()
+            });
+
+            if let Err(err) = root.backend.open(&name) {
+                last_err = Some(err);
+                continue;
+            }
+
+            let access = root.access(&name)?;
+
+            reservations.insert(
+                name,
+                Reservation {
+                    root: idx,
+                    num_bytes: reservation_bytes,
+                },
+            );
+
+            return Ok(access);
+        }
+
+        Err(match last_err {
+            Some(err) => SectorManagerErr::ReceiverError(format!("{:?}", err)),
+            None => SectorManagerErr::InsufficientSpace {
+                required_bytes: reservation_bytes,
+                unreserved_bytes: unreserved_bytes.into_iter().max().unwrap_or(0),
+            },
+        })
+    }
+
+    // Deletes the sector, and any merkle tree written for it, once no other
+    // process holds a lock on it and releases its reservation.
+    fn delete_sector_access(&self, access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        let (backend, name) = self.resolve(access)?;
+
+        let lock = self.lock_sector(access, LockMode::Exclusive)?;
+
+        backend
+            .delete(name)
+            .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))?;
+
+        match backend.delete(&sector_tree_name(name)) {
+            Err(ref err) if !is_not_found(err) => {
+                return Err(SectorManagerErr::ReceiverError(format!("{:?}", err)));
+            }
+            _ => (),
+        }
+
+        drop(lock);
+
+        self.release_reservation(access)
+    }
+
+    // Returns the backend holding the sector and the sector's name within it.
+    fn resolve<'a>(
+        &'a self,
+        access: &'a SectorAccess,
+    ) -> Result<(&'a StorageBackend, &'a str), SectorManagerErr> {
+        self.locate(access)
+            .map(|(_, root, name)| (root.backend.as_ref(), name))
+    }
+
+    // Returns the roots of the sector's kind, the root holding the sector and
+    // the sector's name within it.
+    fn locate<'a>(
+        &'a self,
+        access: &'a SectorAccess,
+    ) -> Result<(&'a SectorRoots, &'a SectorRoot, &'a str), SectorManagerErr> {
+        let staging = self
+            .staging
+            .roots
+            .iter()
+            .map(move |root| (&self.staging, root));
+        let sealed = self
+            .sealed
+            .roots
+            .iter()
+            .map(move |root| (&self.sealed, root));
+
+        staging
+            .chain(sealed)
+            .filter(|(_, root)| root.scheme == access.scheme())
+            .filter_map(|(roots, root)| {
+                let name = Path::new(access.path()).strip_prefix(&root.path).ok()?;
+
+                if name.components().count() != 1 {
+                    return None;
+                }
+
+                name.to_str().map(|name| (roots, root, name))
             })
-            .and_then(|_| {
-                pbuf.to_str().map_or_else(
-                    || {
-                        Err(SectorManagerErr::ReceiverError(
-                            "could not create pbuf".to_string(),
-                        ))
-                    },
-                    |str_ref| Ok(str_ref.to_owned()),
-                )
+            .next()
+            .ok_or_else(|| {
+                SectorManagerErr::CallerError(format!("unknown sector access: {}", access))
             })
     }
 }
 
+// Returns a name for a new sector of the provided kind. Sectors are named after
+// their prover and id if they have them (see sector_file_name), with random
+// characters appended if the name is taken (e.g. by a replica of the sector
+// which could not be deleted after its seal failed).
+fn sector_name(
+    kind: SectorKind,
+    sector: Option<(&[u8; 31], u64)>,
+    is_taken: &Fn(&str) -> bool,
+) -> String {
+    let name = match sector {
+        Some((prover_id, sector_id)) => sector_file_name(kind, prover_id, sector_id),
+        None => return util::rand_alpha_string(32),
+    };
+
+    if is_taken(&name) {
+        format!("{}-{}", name, util::rand_alpha_string(8))
+    } else {
+        name
+    }
+}
+
+// Returns the name of the merkle tree written for the sealed sector of the
+// provided name, which is held alongside the sector.
+fn sector_tree_name(name: &str) -> String {
+    format!("{}.tree", name)
+}
+
+/// Returns the name of a prover's sector of the provided kind, e.g.
+/// `sealed-sector-<prover id as hex>-42`. Provers sharing a root therefore
+/// never contend for a name.
+pub fn sector_file_name(kind: SectorKind, prover_id: &[u8; 31], sector_id: u64) -> String {
+    let prover_hex: String = prover_id.iter().map(|b| format!("{:02x}", b)).collect();
+
+    match kind {
+        SectorKind::Staged => format!("staged-sector-{}-{}", prover_hex, sector_id),
+        SectorKind::Sealed => format!("sealed-sector-{}-{}", prover_hex, sector_id),
+    }
+}
+
+/// Returns how many of `num_bytes` may be written to a sector which can hold
+/// `max_unsealed_bytes` and already holds `num_unsealed_bytes`. Produces a
+/// CapacityExceeded error if not all of them fit, unless `partial` is set.
+pub fn fit_to_capacity(
+    max_unsealed_bytes: UnpaddedBytesAmount,
+    num_unsealed_bytes: u64,
+    num_bytes: usize,
+    partial: bool,
+) -> Result<usize, SectorManagerErr> {
+    let remaining_bytes = u64::from(max_unsealed_bytes).saturating_sub(num_unsealed_bytes);
+
+    if num_bytes as u64 <= remaining_bytes {
+        Ok(num_bytes)
+    } else if partial {
+        Ok(remaining_bytes as usize)
+    } else {
+        Err(SectorManagerErr::CapacityExceeded {
+            num_bytes: num_bytes as u64,
+            remaining_bytes,
+        })
+    }
+}
+
+// Missing sectors are the caller's fault; other storage failures are not.
+fn caller_or_receiver_err(err: failure::Error) -> SectorManagerErr {
+    if is_not_found(&err) {
+        SectorManagerErr::CallerError(format!("{:?}", err))
+    } else {
+        SectorManagerErr::ReceiverError(format!("{:?}", err))
+    }
+}
+
 pub struct Config {
     pub sector_bytes: u64,
 }
@@ -203,6 +743,12 @@ pub struct ConcreteSectorStore {
     manager: Box<SectorManager>,
 }
 
+impl ConcreteSectorStore {
+    pub fn new(config: Box<SectorConfig>, manager: Box<SectorManager>) -> ConcreteSectorStore {
+        ConcreteSectorStore { config, manager }
+    }
+}
+
 impl SectorStore for ConcreteSectorStore {
     fn config(&self) -> &SectorConfig {
         self.config.as_ref()
@@ -218,14 +764,65 @@ pub fn new_sector_store(
     sealed_path: String,
     staging_path: String,
 ) -> ConcreteSectorStore {
-    let manager = Box::new(DiskManager {
-        staging_path,
-        sealed_path,
-    });
+    new_multi_root_sector_store(
+        cs,
+        vec![sealed_path],
+        vec![staging_path],
+        PlacementPolicy::RoundRobin,
+    )
+}
 
+/// Returns a SectorStore which spreads sectors across several local
+/// directories. A directory is passed over once it lacks the space for
+/// another sector.
+pub fn new_multi_root_sector_store(
+    cs: &ConfiguredStore,
+    sealed_paths: Vec<String>,
+    staging_paths: Vec<String>,
+    policy: PlacementPolicy,
+) -> ConcreteSectorStore {
+    new_sector_store_with_roots(
+        cs,
+        sealed_paths.into_iter().map(SectorRoot::local).collect(),
+        staging_paths.into_iter().map(SectorRoot::local).collect(),
+        policy,
+    )
+}
+
+/// Returns a SectorStore like `new_multi_root_sector_store` which also
+/// checksums staged sectors. A staged sector's checksum is recorded when it is
+/// closed before sealing, and checked when it is next read through the
+/// SectorManager or verified, as it is before each later attempt to seal it.
+/// Sealed sectors are written by sealing through their local
+/// files and are not checksummed.
+pub fn new_checksummed_sector_store(
+    cs: &ConfiguredStore,
+    sealed_paths: Vec<String>,
+    staging_paths: Vec<String>,
+    policy: PlacementPolicy,
+) -> ConcreteSectorStore {
+    new_sector_store_with_roots(
+        cs,
+        sealed_paths.into_iter().map(SectorRoot::local).collect(),
+        staging_paths
+            .into_iter()
+            .map(SectorRoot::local_checksummed)
+            .collect(),
+        policy,
+    )
+}
+
+fn new_sector_store_with_roots(
+    cs: &ConfiguredStore,
+    sealed: Vec<SectorRoot>,
+    staging: Vec<SectorRoot>,
+    policy: PlacementPolicy,
+) -> ConcreteSectorStore {
     let config = new_sector_config(cs);
 
-    ConcreteSectorStore { config, manager }
+    let manager = Box::new(DiskManager::new(staging, sealed, policy, config.as_ref()));
+
+    ConcreteSectorStore::new(config, manager)
 }
 
 pub fn new_sector_config(cs: &ConfiguredStore) -> Box<SectorConfig> {
@@ -247,6 +844,10 @@ impl SectorConfig for Config {
     fn sector_bytes(&self) -> PaddedBytesAmount {
         PaddedBytesAmount(self.sector_bytes)
     }
+
+    fn sealing_scratch_bytes(&self) -> PaddedBytesAmount {
+        PaddedBytesAmount(self.sector_bytes * SEALING_SCRATCH_SECTORS)
+    }
 }
 
 #[cfg(test)]
@@ -273,8 +874,8 @@ pub mod tests {
         ))
     }
 
-    fn read_all_bytes(access: &str) -> Vec<u8> {
-        let mut file = File::open(access).unwrap();
+    fn read_all_bytes(path: &Path) -> Vec<u8> {
+        let mut file = File::open(path).unwrap();
         let mut buf = Vec::new();
         file.read_to_end(&mut buf).unwrap();
 
@@ -295,12 +896,13 @@ pub mod tests {
         }
     }
 
-    #[test]
-    fn unsealed_sector_write_and_truncate() {
-        let configured_store = ConfiguredStore::Test;
-        let storage: Box<SectorStore> = create_sector_store(&configured_store);
-        let mgr = storage.manager();
-
+    // Exercises Fr32 preprocessing through a SectorManager. Shared by all
+    // SectorManager implementations; read_all_bytes returns the raw bytes
+    // stored for an access.
+    pub fn check_unsealed_sector_write_and_truncate(
+        mgr: &SectorManager,
+        read_all_bytes: &Fn(&SectorAccess) -> Vec<u8>,
+    ) {
         let access = mgr
             .new_staging_sector_access()
             .expect("failed to create staging file");
@@ -377,26 +979,545 @@ pub mod tests {
         }
     }
 
+    pub fn check_deletes_staging_access(mgr: &SectorManager) {
+        let access = mgr.new_staging_sector_access().unwrap();
+
+        assert!(mgr.read_raw(&access, 0, UnpaddedBytesAmount(0)).is_ok());
+
+        assert!(mgr.delete_staging_sector_access(&access).is_ok());
+
+        assert!(mgr.read_raw(&access, 0, UnpaddedBytesAmount(0)).is_err());
+    }
+
+    // Checks that writes beyond a test sector's capacity are refused, or
+    // accepted up to capacity when partial. Shared by all SectorManager
+    // implementations.
+    pub fn check_write_capacity(mgr: &SectorManager) {
+        let max = unpadded_bytes(TEST_SECTOR_SIZE) as usize;
+
+        // fill a sector exactly, the first write ending on an Fr32 element
+        // boundary (127 bytes is exactly 4 elements)
+        {
+            let access = mgr.new_staging_sector_access().unwrap();
+
+            assert_eq!(
+                mgr.write_and_preprocess(&access, &[1; 127]).unwrap(),
+                UnpaddedBytesAmount(127)
+            );
+
+            assert_eq!(
+                mgr.write_and_preprocess(&access, &vec![2; max - 127])
+                    .unwrap(),
+                UnpaddedBytesAmount((max - 127) as u64)
+            );
+
+            assert_eq!(mgr.num_unsealed_bytes(&access).unwrap(), max as u64);
+
+            match mgr.write_and_preprocess(&access, &[3]) {
+                Err(SectorManagerErr::CapacityExceeded {
+                    num_bytes: 1,
+                    remaining_bytes: 0,
+                }) => (),
+                other => panic!("unexpected result: {:?}", other),
+            }
+
+            assert_eq!(
+                mgr.write_and_preprocess_partial(&access, &[3]).unwrap(),
+                UnpaddedBytesAmount(0)
+            );
+        }
+
+        // overflow a sector whose last write ended mid-element
+        {
+            let access = mgr.new_staging_sector_access().unwrap();
+
+            mgr.write_and_preprocess(&access, &vec![4; max - 16])
+                .unwrap();
+
+            match mgr.write_and_preprocess(&access, &[5; 17]) {
+                Err(SectorManagerErr::CapacityExceeded {
+                    num_bytes: 17,
+                    remaining_bytes: 16,
+                }) => (),
+                other => panic!("unexpected result: {:?}", other),
+            }
+
+            // a refused write leaves the sector untouched
+            assert_eq!(mgr.num_unsealed_bytes(&access).unwrap(), (max - 16) as u64);
+
+            assert_eq!(
+                mgr.write_and_preprocess_partial(&access, &[5; 17]).unwrap(),
+                UnpaddedBytesAmount(16)
+            );
+
+            assert_eq!(mgr.num_unsealed_bytes(&access).unwrap(), max as u64);
+
+            let buf = mgr
+                .read_raw(&access, 0, UnpaddedBytesAmount(max as u64))
+                .unwrap();
+            assert_eq!(buf.len(), max);
+        }
+    }
+
     #[test]
-    fn deletes_staging_access() {
+    fn unsealed_sector_write_and_truncate() {
         let configured_store = ConfiguredStore::Test;
+        let storage: Box<SectorStore> = create_sector_store(&configured_store);
+
+        let mgr = storage.manager();
 
+        check_unsealed_sector_write_and_truncate(mgr, &|access| {
+            read_all_bytes(&mgr.local_path(access).unwrap())
+        });
+    }
+
+    #[test]
+    fn deletes_staging_access() {
+        let configured_store = ConfiguredStore::Test;
         let store = create_sector_store(&configured_store);
-        let access = store.manager().new_staging_sector_access().unwrap();
 
-        assert!(store
-            .manager()
-            .read_raw(&access, 0, UnpaddedBytesAmount(0))
-            .is_ok());
+        check_deletes_staging_access(store.manager());
+    }
 
-        assert!(store
-            .manager()
-            .delete_staging_sector_access(&access)
-            .is_ok());
+    // A local root which reports a fixed amount of available space.
+    struct FixedSpace {
+        inner: LocalFs,
+        available_bytes: u64,
+    }
+
+    impl StorageBackend for FixedSpace {
+        fn open(&self, name: &str) -> storage_backend::error::Result<()> {
+            self.inner.open(name)
+        }
+
+        fn read_at(
+            &self,
+            name: &str,
+            offset: u64,
+            buf: &mut [u8],
+        ) -> storage_backend::error::Result<usize> {
+            self.inner.read_at(name, offset, buf)
+        }
+
+        fn write_at(
+            &self,
+            name: &str,
+            offset: u64,
+            data: &[u8],
+        ) -> storage_backend::error::Result<()> {
+            self.inner.write_at(name, offset, data)
+        }
+
+        fn set_size(&self, name: &str, size: u64) -> storage_backend::error::Result<()> {
+            self.inner.set_size(name, size)
+        }
+
+        fn sync(&self, name: &str) -> storage_backend::error::Result<()> {
+            self.inner.sync(name)
+        }
+
+        fn size(&self, name: &str) -> storage_backend::error::Result<u64> {
+            self.inner.size(name)
+        }
+
+        fn delete(&self, name: &str) -> storage_backend::error::Result<()> {
+            self.inner.delete(name)
+        }
+
+        fn list(&self) -> storage_backend::error::Result<Vec<String>> {
+            self.inner.list()
+        }
+
+        fn available_bytes(&self) -> storage_backend::error::Result<u64> {
+            Ok(self.available_bytes)
+        }
+
+        fn local_path(&self, name: &str) -> Option<PathBuf> {
+            self.inner.local_path(name)
+        }
+
+        fn lock(
+            &self,
+            name: &str,
+            mode: LockMode,
+        ) -> storage_backend::error::Result<Box<ObjectLock>> {
+            self.inner.lock(name, mode)
+        }
+    }
+
+    fn fixed_space_root(dir: &Path, name: &str, available_bytes: u64) -> SectorRoot {
+        let path = dir.join(name).to_str().unwrap().to_owned();
+
+        SectorRoot {
+            scheme: FILE_SCHEME.to_string(),
+            backend: Box::new(FixedSpace {
+                inner: LocalFs::new(path.clone()),
+                available_bytes,
+            }),
+            path,
+        }
+    }
+
+    #[test]
+    fn places_sectors_across_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let root_path = |name: &str| dir.path().join(name).to_str().unwrap().to_owned();
+
+        let mgr = DiskManager::new(
+            vec![
+                fixed_space_root(dir.path(), "staging-0", 1000),
+                fixed_space_root(dir.path(), "staging-1", 1000),
+            ],
+            vec![
+                fixed_space_root(dir.path(), "sealed-0", 1000),
+                fixed_space_root(dir.path(), "sealed-1", 1000),
+                fixed_space_root(dir.path(), "sealed-2", 1000),
+            ],
+            PlacementPolicy::BySectorId,
+            &Config { sector_bytes: 64 },
+        );
+
+        // sectors without an id are placed round-robin
+        let roots: Vec<String> = (0..3)
+            .map(|_| {
+                let access = mgr.new_staging_sector_access().unwrap();
+                mgr.sector_root(&access).unwrap()
+            })
+            .collect();
+
+        assert_eq!(
+            roots,
+            vec![
+                root_path("staging-0"),
+                root_path("staging-1"),
+                root_path("staging-0")
+            ]
+        );
+
+        let access = mgr.new_sealed_sector_access_for(&[0; 31], 5).unwrap();
+        assert_eq!(mgr.sector_root(&access).unwrap(), root_path("sealed-2"));
+
+        // sectors on any root can be written and read back
+        mgr.write_and_preprocess(&access, &[1; 20]).unwrap();
+        assert_eq!(mgr.num_unsealed_bytes(&access).unwrap(), 20);
+
+        let unknown = SectorAccess::file("/not/a/sector").unwrap();
+        assert!(mgr.sector_root(&unknown).is_err());
+        assert!(mgr.sector_exists(&unknown).is_err());
+    }
+
+    #[test]
+    fn fails_over_when_roots_fill_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let root_path = |name: &str| dir.path().join(name).to_str().unwrap().to_owned();
+
+        let mgr = DiskManager::new(
+            vec![fixed_space_root(dir.path(), "staging", 0)],
+            vec![
+                fixed_space_root(dir.path(), "sealed-0", 32),
+                fixed_space_root(dir.path(), "sealed-1", 500),
+                fixed_space_root(dir.path(), "sealed-2", 200),
+            ],
+            PlacementPolicy::BySectorId,
+            &Config { sector_bytes: 64 },
+        );
+
+        let access = mgr.new_sealed_sector_access_for(&[0; 31], 0).unwrap();
+        assert_eq!(mgr.sector_root(&access).unwrap(), root_path("sealed-1"));
+
+        assert!(mgr.new_staging_sector_access().is_err());
+
+        let mgr = DiskManager::new(
+            vec![],
+            vec![
+                fixed_space_root(dir.path(), "sealed-0", 32),
+                fixed_space_root(dir.path(), "sealed-1", 500),
+                fixed_space_root(dir.path(), "sealed-2", 200),
+            ],
+            PlacementPolicy::MostFreeSpace,
+            &Config { sector_bytes: 64 },
+        );
+
+        let access = mgr.new_sealed_sector_access().unwrap();
+        assert_eq!(mgr.sector_root(&access).unwrap(), root_path("sealed-1"));
+    }
+
+    #[test]
+    fn reserves_space_for_provisioned_sectors() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config { sector_bytes: 64 };
+
+        let mgr = DiskManager::new(
+            vec![fixed_space_root(dir.path(), "staging", 200)],
+            vec![fixed_space_root(dir.path(), "sealed", 191)],
+            PlacementPolicy::RoundRobin,
+            &config,
+        );
+
+        assert_eq!(mgr.reservation_bytes(SectorKind::Staged), 64);
+        assert_eq!(mgr.reservation_bytes(SectorKind::Sealed), 64 * 3);
+
+        let accesses: Vec<SectorAccess> = (0..3)
+            .map(|_| mgr.new_staging_sector_access().unwrap())
+            .collect();
+
+        match mgr.new_staging_sector_access() {
+            Err(SectorManagerErr::InsufficientSpace {
+                required_bytes: 64,
+                unreserved_bytes: 8,
+            }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // written bytes no longer count as reserved
+        mgr.write_and_preprocess(&accesses[0], &[1; 20]).unwrap();
+
+        let staging_capacity = mgr.capacity().unwrap()[0].clone();
+        assert_eq!(staging_capacity.kind, SectorKind::Staged);
+        assert_eq!(staging_capacity.available_bytes, 200);
+        assert_eq!(staging_capacity.reserved_bytes, 3 * 64 - 20);
+        assert!(!mgr.can_provision(SectorKind::Staged).unwrap());
+
+        mgr.release_reservation(&accesses[0]).unwrap();
+        mgr.delete_staging_sector_access(&accesses[1]).unwrap();
+
+        assert_eq!(mgr.capacity().unwrap()[0].reserved_bytes, 64);
+        assert!(mgr.can_provision(SectorKind::Staged).unwrap());
+        assert!(mgr.new_staging_sector_access().is_ok());
+
+        // a sealed sector also needs room for the sealing scratch files
+        assert!(!mgr.can_provision(SectorKind::Sealed).unwrap());
+        assert!(mgr.new_sealed_sector_access().is_err());
+    }
+
+    #[test]
+    fn keeps_sector_trees_alongside_sealed_sectors() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mgr = DiskManager::new(
+            vec![fixed_space_root(dir.path(), "staging", 1000)],
+            vec![fixed_space_root(dir.path(), "sealed", 1000)],
+            PlacementPolicy::RoundRobin,
+            &Config { sector_bytes: 64 },
+        );
+
+        let access = mgr.new_sealed_sector_access_for(&[0; 31], 7).unwrap();
+        assert_eq!(mgr.sector_tree_path(&access).unwrap(), None);
+
+        mgr.write_sector_tree(&access, &[1; 64]).unwrap();
+        mgr.write_sector_tree(&access, &[2; 32]).unwrap();
+
+        let tree_path = mgr.sector_tree_path(&access).unwrap().unwrap();
+        assert_eq!(
+            tree_path.parent(),
+            mgr.local_path(&access).unwrap().parent()
+        );
+        assert_eq!(std::fs::read(&tree_path).unwrap(), vec![2; 32]);
+
+        mgr.delete_sealed_sector_access(&access).unwrap();
+        assert!(!tree_path.exists());
+    }
+
+    #[test]
+    fn restores_reservations_of_existing_sectors() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config { sector_bytes: 64 };
+
+        let staging = || vec![fixed_space_root(dir.path(), "staging", 200)];
+        let sealed = || vec![fixed_space_root(dir.path(), "sealed", 200)];
+
+        let access = {
+            let mgr = DiskManager::new(staging(), sealed(), PlacementPolicy::RoundRobin, &config);
+            let access = mgr.new_staging_sector_access().unwrap();
+            mgr.write_and_preprocess(&access, &[1; 20]).unwrap();
+            access
+        };
+
+        // a new manager (e.g. after a restart) knows nothing of the sector's
+        // reservation until it is restored
+        let mgr = DiskManager::new(staging(), sealed(), PlacementPolicy::RoundRobin, &config);
+        assert_eq!(mgr.capacity().unwrap()[0].reserved_bytes, 0);
+
+        mgr.restore_reservation(&access).unwrap();
+        assert_eq!(mgr.capacity().unwrap()[0].reserved_bytes, 64 - 20);
+
+        mgr.delete_staging_sector_access(&access).unwrap();
+        assert_eq!(mgr.capacity().unwrap()[0].reserved_bytes, 0);
+
+        // a sealed sector is deleted along with its reservation
+        let sealed_access = mgr.new_sealed_sector_access().unwrap();
+        assert_eq!(mgr.capacity().unwrap()[1].reserved_bytes, 64 * 3);
+
+        mgr.delete_sealed_sector_access(&sealed_access).unwrap();
+        assert!(!mgr.sector_exists(&sealed_access).unwrap());
+        assert_eq!(mgr.capacity().unwrap()[1].reserved_bytes, 0);
+    }
+
+    #[test]
+    fn names_sectors_after_their_ids() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mgr = DiskManager::new(
+            vec![SectorRoot::local(
+                dir.path().join("staging").to_str().unwrap().to_owned(),
+            )],
+            vec![SectorRoot::local(
+                dir.path().join("sealed").to_str().unwrap().to_owned(),
+            )],
+            PlacementPolicy::RoundRobin,
+            &Config { sector_bytes: 64 },
+        );
+
+        let prover_hex = "01".repeat(31);
+
+        let staged = mgr.new_staging_sector_access_for(&[1; 31], 7).unwrap();
+        let sealed = mgr.new_sealed_sector_access_for(&[1; 31], 7).unwrap();
+
+        assert_eq!(staged.scheme(), FILE_SCHEME);
+        assert_eq!(
+            mgr.local_path(&staged).unwrap(),
+            dir.path()
+                .join("staging")
+                .join(format!("staged-sector-{}-7", prover_hex))
+        );
+        assert_eq!(
+            mgr.local_path(&sealed).unwrap(),
+            dir.path()
+                .join("sealed")
+                .join(format!("sealed-sector-{}-7", prover_hex))
+        );
+
+        // another prover's sector of the same id gets a name of its own
+        let other_prover = mgr.new_sealed_sector_access_for(&[2; 31], 7).unwrap();
+        assert_eq!(
+            mgr.local_path(&other_prover).unwrap(),
+            dir.path()
+                .join("sealed")
+                .join(format!("sealed-sector-{}-7", "02".repeat(31)))
+        );
+        assert!(mgr.sector_exists(&sealed).unwrap());
+
+        // accesses survive a round trip through their string form
+        let parsed = SectorAccess::parse(&staged.to_string()).unwrap();
+        mgr.write_and_preprocess(&parsed, &[1; 20]).unwrap();
+        assert_eq!(mgr.num_unsealed_bytes(&staged).unwrap(), 20);
+
+        // a taken name is not reused
+        let resealed = mgr.new_sealed_sector_access_for(&[1; 31], 7).unwrap();
+        assert_ne!(resealed, sealed);
+        assert!(resealed
+            .path()
+            .contains(&format!("sealed-sector-{}-7-", prover_hex)));
+
+        mgr.delete_staging_sector_access(&staged).unwrap();
+        assert!(!mgr.sector_exists(&staged).unwrap());
+
+        // accesses with another scheme are not this manager's
+        let other = SectorAccess::new("mem", staged.path());
+        assert!(mgr.sector_exists(&other).is_err());
+    }
+
+    #[test]
+    fn checksums_closed_staged_sectors() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let store = new_checksummed_sector_store(
+            &ConfiguredStore::Test,
+            vec![dir.path().join("sealed").to_str().unwrap().to_owned()],
+            vec![dir.path().join("staging").to_str().unwrap().to_owned()],
+            PlacementPolicy::RoundRobin,
+        );
+        let mgr = store.manager();
+
+        let access = mgr.new_staging_sector_access_for(&[0; 31], 1).unwrap();
+        mgr.write_and_preprocess(&access, &[1; 20]).unwrap();
+        mgr.write_and_preprocess(&access, &[2; 20]).unwrap();
+        mgr.close_sector(&access).unwrap();
+
+        assert!(mgr.read_raw(&access, 0, UnpaddedBytesAmount(20)).is_ok());
+        assert!(mgr.verify_sector(&access).is_ok());
+
+        // corrupt the sector through its local file, which the store has
+        // already checked, so the corruption is only found when it is verified
+        // or read through a new store, which checks it before it is first read
+        std::fs::write(mgr.local_path(&access).unwrap(), &[3; 40]).unwrap();
+
+        match mgr.verify_sector(&access) {
+            Err(SectorManagerErr::ReceiverError(_)) => (),
+            other => panic!("expected a checksum mismatch, got {:?}", other),
+        }
+
+        let reopened = new_checksummed_sector_store(
+            &ConfiguredStore::Test,
+            vec![dir.path().join("sealed").to_str().unwrap().to_owned()],
+            vec![dir.path().join("staging").to_str().unwrap().to_owned()],
+            PlacementPolicy::RoundRobin,
+        );
 
-        assert!(store
+        assert!(reopened
             .manager()
-            .read_raw(&access, 0, UnpaddedBytesAmount(0))
+            .read_raw(&access, 0, UnpaddedBytesAmount(20))
             .is_err());
     }
+
+    #[test]
+    fn locks_sectors() {
+        let store = create_sector_store(&ConfiguredStore::Test);
+        let mgr = store.manager();
+
+        let access = mgr.new_staging_sector_access().unwrap();
+
+        {
+            let _shared = mgr.lock_sector(&access, LockMode::Shared).unwrap();
+
+            assert!(mgr.lock_sector(&access, LockMode::Shared).is_ok());
+
+            match mgr.write_and_preprocess(&access, &[1; 20]) {
+                Err(SectorManagerErr::SectorLocked(ref locked))
+                    if *locked == access.to_string() =>
+                {
+                    ()
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+
+            match mgr.delete_staging_sector_access(&access) {
+                Err(SectorManagerErr::SectorLocked(_)) => (),
+                other => panic!("unexpected result: {:?}", other),
+            }
+
+            // a refused write leaves the sector untouched
+            assert_eq!(mgr.num_unsealed_bytes(&access).unwrap(), 0);
+        }
+
+        mgr.write_and_preprocess(&access, &[1; 20]).unwrap();
+        assert_eq!(mgr.num_unsealed_bytes(&access).unwrap(), 20);
+
+        mgr.delete_staging_sector_access(&access).unwrap();
+
+        match mgr.lock_sector(&access, LockMode::Exclusive) {
+            Err(SectorManagerErr::CallerError(_)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn enforces_sector_capacity() {
+        let store = create_sector_store(&ConfiguredStore::Test);
+
+        check_write_capacity(store.manager());
+    }
+
+    #[test]
+    fn fit_to_capacity_checks() {
+        let max = UnpaddedBytesAmount(1016);
+
+        assert_eq!(fit_to_capacity(max, 1000, 16, false).unwrap(), 16);
+        assert_eq!(fit_to_capacity(max, 1000, 17, true).unwrap(), 16);
+        assert_eq!(fit_to_capacity(max, 1016, 0, false).unwrap(), 0);
+        assert!(fit_to_capacity(max, 1000, 17, false).is_err());
+
+        // a sector which is somehow overfull accepts nothing
+        assert_eq!(fit_to_capacity(max, 2000, 1, true).unwrap(), 0);
+    }
 }
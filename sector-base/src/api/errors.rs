@@ -8,4 +8,25 @@ pub enum SectorManagerErr {
 
     #[fail(display = "receiver error: {}", _0)]
     ReceiverError(String),
+
+    #[fail(
+        display = "{} bytes exceed the {} bytes of remaining sector capacity",
+        num_bytes, remaining_bytes
+    )]
+    CapacityExceeded {
+        num_bytes: u64,
+        remaining_bytes: u64,
+    },
+
+    #[fail(
+        display = "no root has {} bytes to spare for a new sector (at most {} bytes are unreserved)",
+        required_bytes, unreserved_bytes
+    )]
+    InsufficientSpace {
+        required_bytes: u64,
+        unreserved_bytes: u64,
+    },
+
+    #[fail(display = "sector {} is in use by another process", _0)]
+    SectorLocked(String),
 }
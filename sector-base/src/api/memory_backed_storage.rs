@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::api::bytes_amount::UnpaddedBytesAmount;
+use crate::api::disk_backed_storage::fit_to_capacity;
+use crate::api::disk_backed_storage::new_sector_config;
+use crate::api::disk_backed_storage::sector_file_name;
+use crate::api::disk_backed_storage::ConcreteSectorStore;
+use crate::api::disk_backed_storage::ConfiguredStore;
+use crate::api::errors::SectorManagerErr;
+use crate::api::sector_access::{SectorAccess, MEMORY_SCHEME};
+use crate::api::sector_store::RootCapacity;
+use crate::api::sector_store::SectorKind;
+use crate::api::sector_store::SectorManager;
+use crate::api::sector_store::{LockMode, ObjectLock};
+use crate::api::util;
+use crate::io::fr32::almost_truncate_to_unpadded_bytes;
+use crate::io::fr32::target_unpadded_bytes;
+use crate::io::fr32::write_padded;
+
+const STAGING_ROOT: &str = "staging";
+const SEALED_ROOT: &str = "sealed";
+
+/// Keeps every sector in memory rather than on disk. Sector contents are lost
+/// when the manager is dropped. Bytes written through `write_and_preprocess`
+/// are Fr32-padded exactly as they are by `DiskManager`. Accesses have the
+/// `mem` scheme, e.g. `mem://staging/staged-sector-7`.
+pub struct MemoryManager {
+    sectors: Mutex<HashMap<SectorAccess, Vec<u8>>>,
+
+    // Number of unpadded bytes which fit in a sector.
+    max_unsealed_bytes: UnpaddedBytesAmount,
+}
+
+impl SectorManager for MemoryManager {
+    fn new_sealed_sector_access(&self) -> Result<SectorAccess, SectorManagerErr> {
+        self.new_sector_access(SEALED_ROOT, util::rand_alpha_string(32))
+    }
+
+    fn new_staging_sector_access(&self) -> Result<SectorAccess, SectorManagerErr> {
+        self.new_sector_access(STAGING_ROOT, util::rand_alpha_string(32))
+    }
+
+    fn new_sealed_sector_access_for(
+        &self,
+        prover_id: &[u8; 31],
+        sector_id: u64,
+    ) -> Result<SectorAccess, SectorManagerErr> {
+        self.new_sector_access(
+            SEALED_ROOT,
+            sector_file_name(SectorKind::Sealed, prover_id, sector_id),
+        )
+    }
+
+    fn new_staging_sector_access_for(
+        &self,
+        prover_id: &[u8; 31],
+        sector_id: u64,
+    ) -> Result<SectorAccess, SectorManagerErr> {
+        self.new_sector_access(
+            STAGING_ROOT,
+            sector_file_name(SectorKind::Staged, prover_id, sector_id),
+        )
+    }
+
+    fn sector_root(&self, access: &SectorAccess) -> Result<String, SectorManagerErr> {
+        self.with_sector(access, |_| {
+            Ok(if access.path().starts_with(SEALED_ROOT) {
+                root_name(SEALED_ROOT)
+            } else {
+                root_name(STAGING_ROOT)
+            })
+        })
+    }
+
+    fn local_path(&self, access: &SectorAccess) -> Result<PathBuf, SectorManagerErr> {
+        Err(SectorManagerErr::CallerError(format!(
+            "sector {} is held in memory, not in a local file",
+            access
+        )))
+    }
+
+    fn sector_exists(&self, access: &SectorAccess) -> Result<bool, SectorManagerErr> {
+        self.sectors
+            .lock()
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))
+            .map(|sectors| sectors.contains_key(access))
+    }
+
+    // Sectors held in memory cannot be shared with other processes, so there
+    // is nothing to lock.
+    fn lock_sector(
+        &self,
+        access: &SectorAccess,
+        _mode: LockMode,
+    ) -> Result<Box<ObjectLock>, SectorManagerErr> {
+        self.with_sector(access, |_| Ok(Box::new(()) as Box<ObjectLock>))
+    }
+
+    fn capacity(&self) -> Result<Vec<RootCapacity>, SectorManagerErr> {
+        let root_capacity = |root: &str, kind| RootCapacity {
+            root: root_name(root),
+            kind,
+            available_bytes: u64::max_value(),
+            reserved_bytes: 0,
+        };
+
+        Ok(vec![
+            root_capacity(STAGING_ROOT, SectorKind::Staged),
+            root_capacity(SEALED_ROOT, SectorKind::Sealed),
+        ])
+    }
+
+    // Memory is not accounted for, so nothing is reserved.
+    fn reservation_bytes(&self, _kind: SectorKind) -> u64 {
+        0
+    }
+
+    fn release_reservation(&self, _access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        Ok(())
+    }
+
+    fn restore_reservation(&self, _access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        Ok(())
+    }
+
+    fn num_unsealed_bytes(&self, access: &SectorAccess) -> Result<u64, SectorManagerErr> {
+        self.with_sector(access, |sector| {
+            target_unpadded_bytes(&mut Cursor::new(sector))
+                .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))
+        })
+    }
+
+    fn truncate_unsealed(&self, access: &SectorAccess, size: u64) -> Result<(), SectorManagerErr> {
+        self.with_sector(access, |sector| {
+            let padded_size =
+                almost_truncate_to_unpadded_bytes(&mut Cursor::new(&mut *sector), size)
+                    .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+            sector.resize(padded_size, 0);
+
+            Ok(())
+        })
+    }
+
+    fn write_and_preprocess(
+        &self,
+        access: &SectorAccess,
+        data: &[u8],
+    ) -> Result<UnpaddedBytesAmount, SectorManagerErr> {
+        self.write_within_capacity(access, data, false)
+    }
+
+    fn write_and_preprocess_partial(
+        &self,
+        access: &SectorAccess,
+        data: &[u8],
+    ) -> Result<UnpaddedBytesAmount, SectorManagerErr> {
+        self.write_within_capacity(access, data, true)
+    }
+
+    fn delete_staging_sector_access(&self, access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        self.delete_sector_access(access)
+    }
+
+    fn delete_sealed_sector_access(&self, access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        let _ = self.delete_sector_access(&sector_tree_access(access));
+
+        self.delete_sector_access(access)
+    }
+
+    fn write_sector_tree(
+        &self,
+        access: &SectorAccess,
+        data: &[u8],
+    ) -> Result<(), SectorManagerErr> {
+        let mut sectors = self
+            .sectors
+            .lock()
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+        if !sectors.contains_key(access) {
+            return Err(SectorManagerErr::CallerError(format!(
+                "no sector at {}",
+                access
+            )));
+        }
+
+        sectors.insert(sector_tree_access(access), data.to_vec());
+
+        Ok(())
+    }
+
+    // Trees are held in memory, like sectors, so they have no local path.
+    fn sector_tree_path(
+        &self,
+        _access: &SectorAccess,
+    ) -> Result<Option<PathBuf>, SectorManagerErr> {
+        Ok(None)
+    }
+
+    fn read_raw(
+        &self,
+        access: &SectorAccess,
+        start_offset: u64,
+        num_bytes: UnpaddedBytesAmount,
+    ) -> Result<Vec<u8>, SectorManagerErr> {
+        self.with_sector(access, |sector| {
+            let mut cursor = Cursor::new(sector);
+
+            cursor
+                .seek(SeekFrom::Start(start_offset))
+                .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))?;
+
+            let mut buf = vec![0; usize::from(num_bytes)];
+
+            cursor
+                .read_exact(buf.as_mut_slice())
+                .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))?;
+
+            Ok(buf)
+        })
+    }
+}
+
+impl MemoryManager {
+    pub fn new(max_unsealed_bytes: UnpaddedBytesAmount) -> MemoryManager {
+        MemoryManager {
+            sectors: Default::default(),
+            max_unsealed_bytes,
+        }
+    }
+
+    fn write_within_capacity(
+        &self,
+        access: &SectorAccess,
+        data: &[u8],
+        partial: bool,
+    ) -> Result<UnpaddedBytesAmount, SectorManagerErr> {
+        self.with_sector(access, |sector| {
+            let mut cursor = Cursor::new(sector);
+
+            let num_unsealed_bytes = target_unpadded_bytes(&mut cursor)
+                .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+            let num_bytes = fit_to_capacity(
+                self.max_unsealed_bytes,
+                num_unsealed_bytes,
+                data.len(),
+                partial,
+            )?;
+
+            write_padded(&data[..num_bytes], &mut cursor)
+                .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))
+                .map(|n| UnpaddedBytesAmount(n as u64))
+        })
+    }
+
+    // Creates an empty sector with the provided name in the provided root. The
+    // name is suffixed with random characters if it is taken.
+    fn new_sector_access(
+        &self,
+        root: &str,
+        name: String,
+    ) -> Result<SectorAccess, SectorManagerErr> {
+        let mut sectors = self
+            .sectors
+            .lock()
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+        let mut access = SectorAccess::new(MEMORY_SCHEME, &format!("{}/{}", root, name));
+
+        if sectors.contains_key(&access) {
+            access = SectorAccess::new(
+                MEMORY_SCHEME,
+                &format!("{}/{}-{}", root, name, util::rand_alpha_string(8)),
+            );
+        }
+
+        sectors.insert(access.clone(), Vec::new());
+
+        Ok(access)
+    }
+
+    // Removes the sector identified by access, producing a caller error if
+    // there is no such sector.
+    fn delete_sector_access(&self, access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        self.sectors
+            .lock()
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?
+            .remove(access)
+            .map(|_| ())
+            .ok_or_else(|| SectorManagerErr::CallerError(format!("no sector at {}", access)))
+    }
+
+    // Runs f against the bytes of the sector identified by access, producing
+    // a caller error if there is no such sector.
+    fn with_sector<T, F>(&self, access: &SectorAccess, f: F) -> Result<T, SectorManagerErr>
+    where
+        F: FnOnce(&mut Vec<u8>) -> Result<T, SectorManagerErr>,
+    {
+        let mut sectors = self
+            .sectors
+            .lock()
+            .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+        match sectors.get_mut(access) {
+            Some(sector) => f(sector),
+            None => Err(SectorManagerErr::CallerError(format!(
+                "no sector at {}",
+                access
+            ))),
+        }
+    }
+}
+
+// Returns the name under which a root's capacity is reported.
+fn root_name(root: &str) -> String {
+    SectorAccess::new(MEMORY_SCHEME, root).to_string()
+}
+
+/// Returns a SectorStore which keeps its sectors in memory. Intended for tests
+/// and simulations which should not touch the filesystem.
+pub fn new_in_memory_sector_store(cs: &ConfiguredStore) -> ConcreteSectorStore {
+    let config = new_sector_config(cs);
+    let manager = Box::new(MemoryManager::new(config.max_unsealed_bytes_per_sector()));
+
+    ConcreteSectorStore::new(config, manager)
+}
+
+// Returns the access under which the merkle tree written for the sealed sector
+// identified by access is held.
+fn sector_tree_access(access: &SectorAccess) -> SectorAccess {
+    SectorAccess::new(MEMORY_SCHEME, &format!("{}.tree", access.path()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::api::disk_backed_storage::tests::{
+        check_deletes_staging_access, check_unsealed_sector_write_and_truncate,
+        check_write_capacity,
+    };
+    use crate::api::disk_backed_storage::TEST_SECTOR_SIZE;
+    use crate::api::sector_store::SectorStore;
+    use crate::io::fr32::unpadded_bytes;
+
+    fn test_manager() -> MemoryManager {
+        MemoryManager::new(UnpaddedBytesAmount(unpadded_bytes(TEST_SECTOR_SIZE)))
+    }
+
+    fn read_all_bytes(mgr: &MemoryManager, access: &SectorAccess) -> Vec<u8> {
+        mgr.sectors.lock().unwrap()[access].clone()
+    }
+
+    #[test]
+    fn unsealed_sector_write_and_truncate() {
+        let mgr = test_manager();
+
+        check_unsealed_sector_write_and_truncate(&mgr, &|access| read_all_bytes(&mgr, access));
+    }
+
+    #[test]
+    fn deletes_staging_access() {
+        check_deletes_staging_access(&test_manager());
+    }
+
+    #[test]
+    fn enforces_sector_capacity() {
+        check_write_capacity(&test_manager());
+    }
+
+    #[test]
+    fn sealed_and_staging_accesses_are_distinct() {
+        let store = new_in_memory_sector_store(&ConfiguredStore::Test);
+
+        let staging = store.manager().new_staging_sector_access().unwrap();
+        let sealed = store.manager().new_sealed_sector_access().unwrap();
+
+        assert_ne!(staging, sealed);
+        assert_eq!(store.manager().num_unsealed_bytes(&sealed).unwrap(), 0);
+        assert_eq!(
+            store.manager().sector_root(&sealed).unwrap(),
+            "mem://sealed"
+        );
+    }
+
+    #[test]
+    fn names_sectors_after_their_ids() {
+        let mgr = test_manager();
+
+        let staged = mgr.new_staging_sector_access_for(&[0xab; 31], 7).unwrap();
+        assert_eq!(
+            staged.as_str(),
+            format!("mem://staging/staged-sector-{}-7", "ab".repeat(31))
+        );
+        assert!(mgr.sector_exists(&staged).unwrap());
+
+        // sectors in memory cannot be resolved to files
+        assert!(mgr.local_path(&staged).is_err());
+
+        let restaged = mgr.new_staging_sector_access_for(&[0xab; 31], 7).unwrap();
+        assert_ne!(restaged, staged);
+
+        mgr.delete_staging_sector_access(&staged).unwrap();
+        assert!(!mgr.sector_exists(&staged).unwrap());
+    }
+}
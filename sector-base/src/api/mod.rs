@@ -1,5 +1,8 @@
 pub mod bytes_amount;
 pub mod disk_backed_storage;
 pub mod errors;
+pub mod memory_backed_storage;
+pub mod placement;
+pub mod sector_access;
 pub mod sector_store;
 pub mod util;
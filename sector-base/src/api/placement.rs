@@ -0,0 +1,77 @@
+/// Decides which of several roots receives a newly-provisioned sector.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlacementPolicy {
+    /// cycle through the roots, one sector at a time
+    RoundRobin,
+
+    /// place the sector on the root with the most available space
+    MostFreeSpace,
+
+    /// place the sector on root `sector_id % num_roots`; sectors provisioned
+    /// without an id are placed round-robin
+    BySectorId,
+}
+
+// Returns the indices of all roots in the order in which they should be tried
+// for a new sector. Roots after the first are used when the preferred root is
+// full. available_bytes holds the space available on each root and
+// round_robin_counter the number of sectors provisioned so far.
+pub fn placement_order(
+    policy: PlacementPolicy,
+    available_bytes: &[u64],
+    round_robin_counter: usize,
+    sector_id: Option<u64>,
+) -> Vec<usize> {
+    let num_roots = available_bytes.len();
+
+    if num_roots == 0 {
+        return Vec::new();
+    }
+
+    let rotated_from =
+        |first: usize| -> Vec<usize> { (0..num_roots).map(|n| (first + n) % num_roots).collect() };
+
+    match (policy, sector_id) {
+        (PlacementPolicy::BySectorId, Some(sector_id)) => {
+            rotated_from((sector_id % num_roots as u64) as usize)
+        }
+        (PlacementPolicy::MostFreeSpace, _) => {
+            let mut order: Vec<usize> = (0..num_roots).collect();
+            order.sort_by_key(|&idx| ::std::cmp::Reverse(available_bytes[idx]));
+            order
+        }
+        _ => rotated_from(round_robin_counter % num_roots),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placement_order_checks() {
+        let available = [10, 30, 20];
+
+        assert_eq!(
+            placement_order(PlacementPolicy::RoundRobin, &available, 4, Some(0)),
+            vec![1, 2, 0]
+        );
+
+        assert_eq!(
+            placement_order(PlacementPolicy::MostFreeSpace, &available, 4, None),
+            vec![1, 2, 0]
+        );
+
+        assert_eq!(
+            placement_order(PlacementPolicy::BySectorId, &available, 0, Some(5)),
+            vec![2, 0, 1]
+        );
+
+        assert_eq!(
+            placement_order(PlacementPolicy::BySectorId, &available, 0, None),
+            vec![0, 1, 2]
+        );
+
+        assert!(placement_order(PlacementPolicy::RoundRobin, &[], 0, None).is_empty());
+    }
+}
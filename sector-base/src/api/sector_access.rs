@@ -0,0 +1,194 @@
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::api::errors::SectorManagerErr;
+
+/// The scheme of accesses to sectors held in local files.
+pub const FILE_SCHEME: &str = "file";
+
+/// The scheme of accesses to sectors held in memory.
+pub const MEMORY_SCHEME: &str = "mem";
+
+const SCHEME_SEPARATOR: &str = "://";
+
+/// Identifies a sector held by a SectorManager in the form of a URI, e.g.
+/// `file:///var/sectors/sealed/sealed-sector-42` or `mem://staging/staged-sector-7`.
+/// The scheme names the kind of storage holding the sector and the path
+/// (everything following `://`) locates the sector within it.
+///
+/// Only the SectorManager which provisioned an access knows how to resolve
+/// it, e.g. to a local file with `SectorManager::local_path`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SectorAccess(String);
+
+impl SectorAccess {
+    /// Returns the access with the provided scheme and path.
+    pub fn new(scheme: &str, path: &str) -> SectorAccess {
+        SectorAccess(format!("{}{}{}", scheme, SCHEME_SEPARATOR, path))
+    }
+
+    /// Returns a `file://` access to the local file at `path`.
+    pub fn file<P: AsRef<Path>>(path: P) -> Result<SectorAccess, SectorManagerErr> {
+        let path = path.as_ref();
+
+        path.to_str()
+            .map(|path| SectorAccess::new(FILE_SCHEME, path))
+            .ok_or_else(|| {
+                SectorManagerErr::CallerError(format!("path is not valid UTF-8: {:?}", path))
+            })
+    }
+
+    /// Parses an access. A string without a scheme is taken to be the path of
+    /// a local file, which is how sectors were identified before accesses had
+    /// schemes.
+    pub fn parse(access: &str) -> Result<SectorAccess, SectorManagerErr> {
+        let invalid =
+            || SectorManagerErr::CallerError(format!("invalid sector access: {}", access));
+
+        match access.find(SCHEME_SEPARATOR) {
+            Some(idx) => {
+                let scheme = &access[..idx];
+                let path = &access[idx + SCHEME_SEPARATOR.len()..];
+
+                if !is_valid_scheme(scheme) || path.is_empty() {
+                    return Err(invalid());
+                }
+
+                Ok(SectorAccess::new(scheme, path))
+            }
+            None if !access.is_empty() => Ok(SectorAccess::new(FILE_SCHEME, access)),
+            None => Err(invalid()),
+        }
+    }
+
+    pub fn scheme(&self) -> &str {
+        self.0
+            .find(SCHEME_SEPARATOR)
+            .map(|idx| &self.0[..idx])
+            .unwrap_or("")
+    }
+
+    pub fn path(&self) -> &str {
+        self.0
+            .find(SCHEME_SEPARATOR)
+            .map(|idx| &self.0[idx + SCHEME_SEPARATOR.len()..])
+            .unwrap_or(&self.0)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the access as sectors were identified before accesses had
+    /// schemes: the path of its file for a `file://` access, which `parse`
+    /// accepts, and the full access otherwise.
+    pub fn to_legacy_string(&self) -> String {
+        if self.scheme() == FILE_SCHEME {
+            self.path().to_string()
+        } else {
+            self.0.clone()
+        }
+    }
+}
+
+// Schemes follow RFC 3986: a letter followed by letters, digits, '+', '-' or '.'.
+fn is_valid_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() => {
+            chars.all(|c: char| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        }
+        _ => false,
+    }
+}
+
+impl fmt::Display for SectorAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for SectorAccess {
+    type Err = SectorManagerErr;
+
+    fn from_str(access: &str) -> Result<SectorAccess, SectorManagerErr> {
+        SectorAccess::parse(access)
+    }
+}
+
+// Accesses are (de)serialized as strings, so that metadata written before
+// accesses had schemes can still be read.
+impl Serialize for SectorAccess {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for SectorAccess {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SectorAccess, D::Error> {
+        let access = String::deserialize(deserializer)?;
+
+        SectorAccess::parse(&access).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_accesses() {
+        let access = SectorAccess::parse("file:///sectors/sealed/sealed-sector-1").unwrap();
+        assert_eq!(access.scheme(), FILE_SCHEME);
+        assert_eq!(access.path(), "/sectors/sealed/sealed-sector-1");
+        assert_eq!(access.to_string(), "file:///sectors/sealed/sealed-sector-1");
+
+        let access: SectorAccess = "s3+v2://bucket/staged-sector-2".parse().unwrap();
+        assert_eq!(access.scheme(), "s3+v2");
+        assert_eq!(access.path(), "bucket/staged-sector-2");
+
+        // paths without a scheme are local files
+        assert_eq!(
+            SectorAccess::parse("/sectors/staging/abc").unwrap(),
+            SectorAccess::file("/sectors/staging/abc").unwrap()
+        );
+
+        for invalid in &["", "://path", "1abc://path", "a b://path", "mem://"] {
+            assert!(SectorAccess::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn serializes_as_string() {
+        let access = SectorAccess::new(MEMORY_SCHEME, "sealed/sealed-sector-3");
+
+        let json = serde_json::to_string(&access).unwrap();
+        assert_eq!(json, "\"mem://sealed/sealed-sector-3\"");
+        assert_eq!(serde_json::from_str::<SectorAccess>(&json).unwrap(), access);
+
+        let legacy: SectorAccess = serde_json::from_str("\"/tmp/staging/abc\"").unwrap();
+        assert_eq!(legacy.as_str(), "file:///tmp/staging/abc");
+
+        assert!(serde_json::from_str::<SectorAccess>("\"\"").is_err());
+    }
+
+    #[test]
+    fn legacy_strings_parse_to_the_same_access() {
+        let file = SectorAccess::file("/sectors/sealed/sealed-sector-1").unwrap();
+        assert_eq!(file.to_legacy_string(), "/sectors/sealed/sealed-sector-1");
+
+        let mem = SectorAccess::new(MEMORY_SCHEME, "sealed/sealed-sector-3");
+        assert_eq!(mem.to_legacy_string(), "mem://sealed/sealed-sector-3");
+
+        for access in &[file, mem] {
+            assert_eq!(
+                &SectorAccess::parse(&access.to_legacy_string()).unwrap(),
+                access
+            );
+        }
+    }
+}
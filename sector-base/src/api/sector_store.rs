@@ -1,5 +1,9 @@
 use crate::api::bytes_amount::{PaddedBytesAmount, UnpaddedBytesAmount};
 use crate::api::errors::SectorManagerErr;
+use crate::api::sector_access::SectorAccess;
+use std::path::PathBuf;
+
+pub use storage_backend::{LockMode, ObjectLock};
 
 pub trait SectorConfig {
     /// returns the number of user-provided bytes that will fit into a sector managed by this store
@@ -7,33 +11,167 @@ pub trait SectorConfig {
 
     /// returns the number of bytes in a sealed sector managed by this store
     fn sector_bytes(&self) -> PaddedBytesAmount;
+
+    /// returns the number of bytes of temporary files (e.g. replica layers and merkle trees) which may be written
+    /// alongside a sealed sector while it is being sealed
+    fn sealing_scratch_bytes(&self) -> PaddedBytesAmount;
+}
+
+/// The kind of sectors held by a root.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SectorKind {
+    Staged,
+    Sealed,
+}
+
+/// The space on one of the roots (e.g. disks) in which a SectorManager stores
+/// sectors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RootCapacity {
+    pub root: String,
+    pub kind: SectorKind,
+
+    /// bytes which the root's storage reports as available
+    pub available_bytes: u64,
+
+    /// bytes of `available_bytes` set aside for provisioned sectors which
+    /// have not been written yet
+    pub reserved_bytes: u64,
+}
+
+impl RootCapacity {
+    /// returns the number of bytes which may still be set aside for new sectors
+    pub fn unreserved_bytes(&self) -> u64 {
+        self.available_bytes.saturating_sub(self.reserved_bytes)
+    }
 }
 
 pub trait SectorManager {
     /// provisions a new sealed sector and reports the corresponding access
-    fn new_sealed_sector_access(&self) -> Result<String, SectorManagerErr>;
+    fn new_sealed_sector_access(&self) -> Result<SectorAccess, SectorManagerErr>;
 
     /// provisions a new staging sector and reports the corresponding access
-    fn new_staging_sector_access(&self) -> Result<String, SectorManagerErr>;
+    fn new_staging_sector_access(&self) -> Result<SectorAccess, SectorManagerErr>;
+
+    /// provisions a new sealed sector for prover `prover_id`'s sector with id `sector_id`, which managers may use to decide
+    /// where it is stored and to name it
+    fn new_sealed_sector_access_for(
+        &self,
+        _prover_id: &[u8; 31],
+        _sector_id: u64,
+    ) -> Result<SectorAccess, SectorManagerErr> {
+        self.new_sealed_sector_access()
+    }
+
+    /// provisions a new staging sector for prover `prover_id`'s sector with id `sector_id`, which managers may use to
+    /// decide where it is stored and to name it
+    fn new_staging_sector_access_for(
+        &self,
+        _prover_id: &[u8; 31],
+        _sector_id: u64,
+    ) -> Result<SectorAccess, SectorManagerErr> {
+        self.new_staging_sector_access()
+    }
+
+    /// reports the root (e.g. disk) holding the sector identified by `access`
+    fn sector_root(&self, access: &SectorAccess) -> Result<String, SectorManagerErr>;
+
+    /// resolves `access` to the local file holding the sector, for consumers (e.g. sealing) which operate on files;
+    /// produces a CallerError if the sector is not held in a local file
+    fn local_path(&self, access: &SectorAccess) -> Result<PathBuf, SectorManagerErr>;
+
+    /// reports whether the sector identified by `access` exists
+    fn sector_exists(&self, access: &SectorAccess) -> Result<bool, SectorManagerErr>;
+
+    /// takes an advisory lock on the sector identified by `access`, which is held until the returned lock is dropped;
+    /// produces a SectorLocked error without waiting if another process (e.g. a second SectorBuilder sharing the
+    /// sector's root) holds an incompatible lock
+    fn lock_sector(
+        &self,
+        access: &SectorAccess,
+        mode: LockMode,
+    ) -> Result<Box<ObjectLock>, SectorManagerErr>;
+
+    /// reports the space available on each of the roots holding sectors
+    fn capacity(&self) -> Result<Vec<RootCapacity>, SectorManagerErr>;
+
+    /// reports the number of bytes set aside when a sector of the provided kind is provisioned, which covers the
+    /// sector itself and any temporary files written while producing it
+    fn reservation_bytes(&self, kind: SectorKind) -> u64;
+
+    /// reports whether a sector of the provided kind could be provisioned without exhausting any root
+    fn can_provision(&self, kind: SectorKind) -> Result<bool, SectorManagerErr> {
+        let reservation_bytes = self.reservation_bytes(kind);
+
+        Ok(self
+            .capacity()?
+            .iter()
+            .any(|root| root.kind == kind && root.unreserved_bytes() >= reservation_bytes))
+    }
+
+    /// releases the space set aside when the sector identified by `access` was provisioned, e.g. once the sector
+    /// has been sealed and will not grow any further; deleting a staging sector releases its reservation too
+    fn release_reservation(&self, access: &SectorAccess) -> Result<(), SectorManagerErr>;
+
+    /// sets aside space for the existing sector identified by `access` as though it had just been provisioned;
+    /// reservations do not survive a restart, so they are restored for sectors which have not been sealed yet
+    fn restore_reservation(&self, access: &SectorAccess) -> Result<(), SectorManagerErr>;
 
     /// reports the number of bytes written to an unsealed sector
-    fn num_unsealed_bytes(&self, access: &str) -> Result<u64, SectorManagerErr>;
+    fn num_unsealed_bytes(&self, access: &SectorAccess) -> Result<u64, SectorManagerErr>;
 
     /// sets the number of bytes in an unsealed sector identified by `access`
-    fn truncate_unsealed(&self, access: &str, size: u64) -> Result<(), SectorManagerErr>;
+    fn truncate_unsealed(&self, access: &SectorAccess, size: u64) -> Result<(), SectorManagerErr>;
 
-    /// writes `data` to the staging sector identified by `access`, incrementally preprocessing `access`
+    /// writes `data` to the staging sector identified by `access`, incrementally preprocessing `access`;
+    /// writes nothing and produces a CapacityExceeded error if `data` does not fit in the sector
     fn write_and_preprocess(
         &self,
-        access: &str,
+        access: &SectorAccess,
         data: &[u8],
     ) -> Result<UnpaddedBytesAmount, SectorManagerErr>;
 
-    fn delete_staging_sector_access(&self, access: &str) -> Result<(), SectorManagerErr>;
+    /// writes as much of `data` as fits in the staging sector identified by `access` and reports how many bytes
+    /// were written, which is zero if the sector is full
+    fn write_and_preprocess_partial(
+        &self,
+        access: &SectorAccess,
+        data: &[u8],
+    ) -> Result<UnpaddedBytesAmount, SectorManagerErr>;
+
+    fn delete_staging_sector_access(&self, access: &SectorAccess) -> Result<(), SectorManagerErr>;
+
+    /// deletes the sealed sector identified by `access`, along with any merkle tree written for it, and releases its
+    /// reservation, e.g. once a seal into it has been withdrawn before it started
+    fn delete_sealed_sector_access(&self, access: &SectorAccess) -> Result<(), SectorManagerErr>;
+
+    /// writes `data`, the persisted part of the merkle tree of the sealed sector identified by `access`, alongside the
+    /// sector, replacing any tree written for it before; the tree is held on the sector's root (whose reservation for
+    /// the sector covers it while the sector is sealed) and is deleted with the sector
+    fn write_sector_tree(&self, access: &SectorAccess, data: &[u8])
+        -> Result<(), SectorManagerErr>;
+
+    /// returns the local path of the merkle tree written for the sealed sector identified by `access`, if any
+    fn sector_tree_path(&self, access: &SectorAccess) -> Result<Option<PathBuf>, SectorManagerErr>;
+
+    /// makes all writes to the sector identified by `access` durable and releases anything (e.g. an open file) held
+    /// for it; writes are otherwise not guaranteed to be durable, so sectors are closed once they will not be written
+    /// to again, e.g. before they are sealed
+    fn close_sector(&self, _access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        Ok(())
+    }
+
+    /// reads the sector identified by `access` through its root's storage, producing a ReceiverError if it does not
+    /// match the checksum recorded when it was last closed; consumers reading the sector's local file (e.g. sealing)
+    /// verify it first, since reading the file directly checks nothing. Sectors on roots which record no checksums
+    /// pass, as do sectors written to since they were last closed
+    fn verify_sector(&self, _access: &SectorAccess) -> Result<(), SectorManagerErr> {
+        Ok(())
+    }
 
     fn read_raw(
         &self,
-        access: &str,
+        access: &SectorAccess,
         start_offset: u64,
         num_bytes: UnpaddedBytesAmount,
     ) -> Result<Vec<u8>, SectorManagerErr>;
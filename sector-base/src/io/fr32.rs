@@ -635,25 +635,143 @@ pub fn clear_right_bits(byte: &mut u8, offset: usize) {
     *(byte) &= !((1 << offset) - 1)
 }
 
+// Raw bytes in the smallest run of full elements which ends at a byte boundary
+// in both layouts: 4 data units of 254 bits are 127 bytes, padded to 128 (see
+// `PaddingMap#alignment`). Padding (unpadding) such runs independently produces
+// the same output as padding (unpadding) them all at once.
+const ALIGNED_RAW_BYTES: usize = 127;
+const ALIGNED_PADDED_BYTES: usize = 128;
+
+// Byte offset in a run of raw data at which each element's data unit starts,
+// and the bit offset within that byte: element `i` starts at bit `254 * i`.
+const ELEMENT_DATA_OFFSETS: [(usize, usize); 4] = [(0, 0), (31, 6), (63, 4), (95, 2)];
+
 pub fn write_padded<W: ?Sized>(source: &[u8], target: &mut W) -> io::Result<usize>
 where
     W: Read + Write + Seek,
 {
-    // In order to optimize alignment in the common case of writing from an aligned start,
-    // we should make the chunk a multiple of 127 (4 full elements, see `PaddingMap#alignment`).
-    // n was hand-tuned to do reasonably well in the benchmarks.
-    let n = 1000;
-    let chunk_size = 127 * n;
+    let (padded_bytes, raw_data_bytes, _) = FR32_PADDING_MAP.target_offsets(target)?;
+
+    // If the `target` ends in the middle of an aligned run, complete that run with
+    // the bit-level padder; from there on the data can be padded a run at a time.
+    let unaligned_bytes = if padded_bytes as usize % ALIGNED_PADDED_BYTES == 0 {
+        0
+    } else {
+        min(
+            source.len(),
+            ALIGNED_RAW_BYTES - raw_data_bytes as usize % ALIGNED_RAW_BYTES,
+        )
+    };
 
     let mut written = 0;
 
-    for chunk in source.chunks(chunk_size) {
-        written += write_padded_aux(&FR32_PADDING_MAP, chunk, target)?;
+    if unaligned_bytes > 0 {
+        written += write_padded_aux(&FR32_PADDING_MAP, &source[..unaligned_bytes], target)?;
     }
 
+    written += write_padded_aligned(&source[unaligned_bytes..], target)?;
+
     Ok(written)
 }
 
+/// Pads `source` as the start of a new padded layout, writing it to `target`,
+/// which need not be seekable. Also valid for data appended to a layout of a
+/// whole number of 127-byte runs of raw data, where it produces the same bytes
+/// `write_padded` would.
+///
+/// Whole runs are padded with word-sized shifts rather than bit by bit; a
+/// trailing incomplete run is padded as though it were followed by zeros and
+/// cut to its padded length.
+pub fn write_padded_aligned<W: ?Sized>(source: &[u8], target: &mut W) -> io::Result<usize>
+where
+    W: Write,
+{
+    // Pad n runs at a time, which bounds the size of the padded output buffer.
+    let n = 1000;
+    let chunk_size = ALIGNED_RAW_BYTES * n;
+
+    let mut padded_output =
+        vec![0u8; ALIGNED_PADDED_BYTES * min(n, source.len() / ALIGNED_RAW_BYTES + 1)];
+
+    for chunk in source.chunks(chunk_size) {
+        let mut padded_len = 0;
+
+        for run in chunk.chunks(ALIGNED_RAW_BYTES) {
+            let padded_run = &mut padded_output[padded_len..padded_len + ALIGNED_PADDED_BYTES];
+
+            if run.len() == ALIGNED_RAW_BYTES {
+                pad_aligned_run(run, padded_run);
+                padded_len += ALIGNED_PADDED_BYTES;
+            } else {
+                let mut last_run = [0u8; ALIGNED_RAW_BYTES];
+                last_run[..run.len()].copy_from_slice(run);
+
+                pad_aligned_run(&last_run, padded_run);
+                padded_len += padded_bytes(run.len());
+            }
+        }
+
+        target.write_all(&padded_output[..padded_len])?;
+    }
+
+    Ok(source.len())
+}
+
+// Pads a run of `ALIGNED_RAW_BYTES` into `ALIGNED_PADDED_BYTES`. Each element is
+// the 254 bits starting at its data offset, read as a little-endian integer of
+// four 64-bit words (plus the next word for the bits shifted in at the top),
+// shifted down to the byte boundary and with the 2 padding bits cleared.
+fn pad_aligned_run(run: &[u8], padded: &mut [u8]) {
+    debug_assert_eq!(run.len(), ALIGNED_RAW_BYTES);
+    debug_assert_eq!(padded.len(), ALIGNED_PADDED_BYTES);
+
+    for (element, &(byte_offset, bit_offset)) in
+        padded.chunks_mut(32).zip(ELEMENT_DATA_OFFSETS.iter())
+    {
+        let mut words = [0u64; 5];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = read_le_u64(run, byte_offset + i * 8);
+        }
+
+        for (i, (pair, padded_word)) in words.windows(2).zip(element.chunks_mut(8)).enumerate() {
+            let mut word = if bit_offset == 0 {
+                pair[0]
+            } else {
+                (pair[0] >> bit_offset) | (pair[1] << (64 - bit_offset))
+            };
+
+            if i == 3 {
+                word &= (1u64 << (64 - FR32_PADDING_MAP.pad_bits())) - 1;
+            }
+
+            write_le_u64(word, padded_word);
+        }
+    }
+}
+
+// Reads the 8 bytes of `bytes` from `pos` as a little-endian u64, taking any
+// bytes past the end of `bytes` to be zero.
+#[inline]
+fn read_le_u64(bytes: &[u8], pos: usize) -> u64 {
+    let end = min(pos + 8, bytes.len());
+
+    if pos >= end {
+        return 0;
+    }
+
+    bytes[pos..end]
+        .iter()
+        .rev()
+        .fold(0, |word, &byte| (word << 8) | u64::from(byte))
+}
+
+#[inline]
+fn write_le_u64(word: u64, bytes: &mut [u8]) {
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (word >> (i * 8)) as u8;
+    }
+}
+
 /** Padding process.
 
 Read a `source` of raw byte-aligned data, pad it in a bit stream and
@@ -980,11 +1098,174 @@ where
     Ok(raw_data.len())
 }
 
+// Number of aligned runs the streaming adapters buffer before passing data on.
+const STREAM_BUFFER_RUNS: usize = 64;
+
+/// Pads the raw data written to it and writes the padded layout to `inner`,
+/// producing exactly the bytes `write_padded` would for the same data.
+///
+/// Raw data is buffered until it forms whole runs of 127 bytes, which pad to
+/// byte-aligned 128-byte runs, so at most `127 * STREAM_BUFFER_RUNS` bytes are
+/// held at a time. The trailing incomplete run can only be padded once no more
+/// data follows it, so it is written by `finish`, which must be called once all
+/// data has been written; dropping the writer discards it.
+pub struct Fr32Writer<W: Write> {
+    inner: W,
+    // Raw bytes waiting to be padded.
+    raw: Vec<u8>,
+}
+
+impl<W: Write> Fr32Writer<W> {
+    pub fn new(inner: W) -> Fr32Writer<W> {
+        Fr32Writer {
+            inner,
+            raw: Vec::with_capacity(ALIGNED_RAW_BYTES * STREAM_BUFFER_RUNS),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Pads and writes any buffered data, including a trailing incomplete run,
+    /// flushes `inner` and returns it.
+    pub fn finish(mut self) -> io::Result<W> {
+        let num_bytes = self.raw.len();
+        self.pad_buffered(num_bytes)?;
+        self.inner.flush()?;
+
+        Ok(self.inner)
+    }
+
+    // Pads the first `num_bytes` buffered raw bytes and writes them to `inner`.
+    // Unless this is the end of the data, `num_bytes` must be a multiple of
+    // ALIGNED_RAW_BYTES, otherwise the next bytes written would need to share
+    // the last padded byte.
+    fn pad_buffered(&mut self, num_bytes: usize) -> io::Result<()> {
+        if num_bytes == 0 {
+            return Ok(());
+        }
+
+        write_padded_aligned(&self.raw[..num_bytes], &mut self.inner)?;
+        self.raw.drain(..num_bytes);
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Fr32Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let max_raw_bytes = ALIGNED_RAW_BYTES * STREAM_BUFFER_RUNS;
+
+        if self.raw.len() == max_raw_bytes {
+            self.pad_buffered(max_raw_bytes)?;
+        }
+
+        let num_bytes = min(buf.len(), max_raw_bytes - self.raw.len());
+        self.raw.extend_from_slice(&buf[..num_bytes]);
+
+        Ok(num_bytes)
+    }
+
+    // Writes out every whole run buffered so far. A trailing incomplete run
+    // stays buffered until `finish`.
+    fn flush(&mut self) -> io::Result<()> {
+        let num_aligned = self.raw.len() - self.raw.len() % ALIGNED_RAW_BYTES;
+        self.pad_buffered(num_aligned)?;
+
+        self.inner.flush()
+    }
+}
+
+/// Reads a padded layout from `inner` and returns the raw data it encodes,
+/// exactly as `write_unpadded` would recover it from the whole layout.
+///
+/// Padded bytes are read and unpadded in runs of 128 bytes, at most
+/// `128 * STREAM_BUFFER_RUNS` at a time. A trailing incomplete run is unpadded
+/// to the largest number of whole raw bytes it can hold (see `unpadded_bytes`)
+/// once `inner` reaches its end.
+pub struct Fr32Reader<R: Read> {
+    inner: R,
+    // Padded bytes read from `inner`, the first `num_padded` of which have
+    // not been unpadded yet.
+    padded: Vec<u8>,
+    num_padded: usize,
+    // Unpadded bytes, of which those from `raw_pos` on have not been returned.
+    raw: Vec<u8>,
+    raw_pos: usize,
+    at_end: bool,
+}
+
+impl<R: Read> Fr32Reader<R> {
+    pub fn new(inner: R) -> Fr32Reader<R> {
+        Fr32Reader {
+            inner,
+            padded: vec![0; ALIGNED_PADDED_BYTES * STREAM_BUFFER_RUNS],
+            num_padded: 0,
+            raw: Vec::with_capacity(ALIGNED_RAW_BYTES * STREAM_BUFFER_RUNS),
+            raw_pos: 0,
+            at_end: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns `inner`, discarding any data read from it but not yet returned.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Refills `raw` from `inner`, leaving it empty only once all data has been
+    // returned.
+    fn fill_raw(&mut self) -> io::Result<()> {
+        // Bytes already read are kept in `padded` if `inner` fails, so that a
+        // retried read picks up where this one left off.
+        while self.num_padded < self.padded.len() && !self.at_end {
+            match self.inner.read(&mut self.padded[self.num_padded..]) {
+                Ok(0) => self.at_end = true,
+                Ok(n) => self.num_padded += n,
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.raw.clear();
+        self.raw_pos = 0;
+
+        if self.num_padded > 0 {
+            let num_raw = unpadded_bytes(self.num_padded as u64) as usize;
+
+            write_unpadded(&self.padded[..self.num_padded], &mut self.raw, 0, num_raw)?;
+            self.num_padded = 0;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Fr32Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.raw_pos == self.raw.len() {
+            self.fill_raw()?;
+        }
+
+        let num_bytes = min(buf.len(), self.raw.len() - self.raw_pos);
+        buf[..num_bytes].copy_from_slice(&self.raw[self.raw_pos..self.raw_pos + num_bytes]);
+        self.raw_pos += num_bytes;
+
+        Ok(num_bytes)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use itertools::Itertools;
     use pairing::bls12_381::Bls12;
+    use proptest::collection::vec;
+    use proptest::num;
     use rand::{Rng, SeedableRng, XorShiftRng};
     use std::io::Cursor;
     use storage_proofs::fr32::bytes_into_fr;
@@ -1299,6 +1580,138 @@ mod tests {
         }
     }
 
+    // Pads `data` appended to the padded layout of `prefix` using only the
+    // bit-level padder (`write_padded_aux`).
+    fn bit_level_padding(prefix: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        write_padded_aux(&FR32_PADDING_MAP, prefix, &mut cursor).unwrap();
+        write_padded_aux(&FR32_PADDING_MAP, data, &mut cursor).unwrap();
+        cursor.into_inner()
+    }
+
+    proptest! {
+        // `write_padded` appending to layouts of every alignment, which mixes the
+        // bit-level and word-oriented padders, against the bit-level padder alone.
+        #[test]
+        fn write_padded_matches_bit_level_padding(
+            prefix in vec(num::u8::ANY, 0..300),
+            data in vec(num::u8::ANY, 0..2000),
+        ) {
+            let mut cursor = Cursor::new(Vec::new());
+            write_padded_aux(&FR32_PADDING_MAP, &prefix, &mut cursor).unwrap();
+            write_padded(&data, &mut cursor).unwrap();
+
+            prop_assert_eq!(cursor.into_inner(), bit_level_padding(&prefix, &data));
+        }
+
+        // `write_padded_aligned` against the `BitVec` padder.
+        #[test]
+        fn write_padded_aligned_matches_bit_vec_padding(data in vec(num::u8::ANY, 0..2000)) {
+            let mut padded = Vec::new();
+            let written = write_padded_aligned(&data, &mut padded).unwrap();
+
+            prop_assert_eq!(written, data.len());
+            prop_assert_eq!(padded.into_boxed_slice(), bit_vec_padding(data));
+        }
+    }
+
+    // Lengths around the aligned run and buffer boundaries of the streaming adapters.
+    const STREAM_TEST_LENGTHS: [usize; 10] = [0, 1, 31, 126, 127, 128, 1000, 8128, 8129, 20000];
+
+    fn padded_with_write_padded(data: &[u8]) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        write_padded(data, &mut cursor).unwrap();
+        cursor.into_inner()
+    }
+
+    // Returns at most `max_read` bytes per call, like a pipe or socket might.
+    struct ShortReads<R: Read> {
+        inner: R,
+        max_read: usize,
+    }
+
+    impl<R: Read> Read for ShortReads<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = min(buf.len(), self.max_read);
+            self.inner.read(&mut buf[..len])
+        }
+    }
+
+    // `Fr32Writer` fed in chunks of different sizes, check its output against
+    // `write_padded`.
+    #[test]
+    fn test_fr32_writer_matches_write_padded() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for len in STREAM_TEST_LENGTHS.iter() {
+            let data: Vec<u8> = (0..*len).map(|_| rng.gen()).collect();
+            let expected = padded_with_write_padded(&data);
+
+            for chunk_size in [1, 7, 127, 500, 10000].iter() {
+                let mut writer = Fr32Writer::new(Vec::new());
+
+                for chunk in data.chunks(*chunk_size) {
+                    writer.write_all(chunk).unwrap();
+                    writer.flush().unwrap();
+                }
+
+                let padded = writer.finish().unwrap();
+
+                assert_eq!(padded, expected, "len {}, chunk {}", len, chunk_size);
+            }
+        }
+    }
+
+    // `Fr32Reader` over short reads and with different buffer sizes, check it
+    // recovers the raw data written by `write_padded`.
+    #[test]
+    fn test_fr32_reader_recovers_raw_data() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for len in STREAM_TEST_LENGTHS.iter() {
+            let data: Vec<u8> = (0..*len).map(|_| rng.gen()).collect();
+            let padded = padded_with_write_padded(&data);
+
+            for max_read in [1, 33, 128, 100_000].iter() {
+                let mut reader = Fr32Reader::new(ShortReads {
+                    inner: &padded[..],
+                    max_read: *max_read,
+                });
+
+                let mut unpadded = Vec::new();
+                let mut buf = [0u8; 100];
+
+                loop {
+                    let n = reader.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    unpadded.extend_from_slice(&buf[..n]);
+                }
+
+                assert_eq!(unpadded, data, "len {}, max read {}", len, max_read);
+            }
+        }
+    }
+
+    // Piping `Fr32Writer` into `Fr32Reader` is the identity.
+    #[test]
+    fn test_fr32_stream_roundtrip() {
+        let data: Vec<u8> = (0..5000).map(|i| (i * 7) as u8).collect();
+
+        let mut writer = Fr32Writer::new(Vec::new());
+        io::copy(&mut &data[..], &mut writer).unwrap();
+        let padded = writer.finish().unwrap();
+
+        let mut unpadded = Vec::new();
+        Fr32Reader::new(&padded[..])
+            .read_to_end(&mut unpadded)
+            .unwrap();
+
+        assert_eq!(padded.len(), padded_bytes(data.len()));
+        assert_eq!(unpadded, data);
+    }
+
     // TODO: Add a test that drops the last part of an element and tries to recover
     // the rest of the data (may already be present in some form in the above tests).
 }
@@ -9,8 +9,15 @@ extern crate itertools;
 extern crate libc;
 extern crate pairing;
 extern crate rand;
+extern crate serde;
+extern crate storage_backend;
 extern crate storage_proofs;
 
+#[cfg(test)]
+#[macro_use]
+extern crate proptest;
+#[cfg(test)]
+extern crate serde_json;
 #[cfg(test)]
 extern crate tempfile;
 
@@ -0,0 +1,196 @@
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use crate::error::Result;
+
+/// The kind of advisory lock taken on an object. Any number of shared locks
+/// may be held at once, but an exclusive lock excludes all other locks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+/// An advisory lock on an object, which is released when dropped.
+pub trait ObjectLock: Send {}
+
+impl ObjectLock for () {}
+
+impl ObjectLock for File {}
+
+/// A flat namespace of named, randomly-accessible byte objects, e.g. the files
+/// of a single directory. Implementations must be safe for concurrent access.
+pub trait StorageBackend: Send + Sync {
+    /// creates an empty object with the provided name, unless one exists
+    fn open(&self, name: &str) -> Result<()>;
+
+    /// reads up to `buf.len()` bytes starting at `offset` and reports how many
+    /// were read, which is less than requested only at the end of the object
+    fn read_at(&self, name: &str, offset: u64, buf: &mut [u8]) -> Result<usize>;
+
+    /// writes `data` starting at `offset`, growing the object if needed
+    fn write_at(&self, name: &str, offset: u64, data: &[u8]) -> Result<()>;
+
+    /// truncates or zero-extends the object to `size` bytes
+    fn set_size(&self, name: &str, size: u64) -> Result<()>;
+
+    /// makes all writes to the object durable
+    fn sync(&self, name: &str) -> Result<()>;
+
+    /// makes all writes to the object durable and releases anything (e.g. an
+    /// open file) held for it since it was last used; the object may be used
+    /// again afterwards
+    fn close(&self, name: &str) -> Result<()> {
+        self.sync(name)
+    }
+
+    /// reports the number of bytes in the object
+    fn size(&self, name: &str) -> Result<u64>;
+
+    /// removes the object
+    fn delete(&self, name: &str) -> Result<()>;
+
+    /// reports the names of all objects
+    fn list(&self) -> Result<Vec<String>>;
+
+    /// reports the number of bytes which may still be written to the backend
+    fn available_bytes(&self) -> Result<u64>;
+
+    /// checks the object's contents against any checksum recorded for them,
+    /// e.g. before they are read through `local_path`, which bypasses the
+    /// checks made when reading through the backend; backends which record
+    /// no checksums have nothing to check
+    fn verify(&self, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// reports the path of the local file holding the object, if the object is
+    /// held in one which may be read and written directly
+    fn local_path(&self, _name: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// takes an advisory lock on the object without waiting, which is held
+    /// until the returned lock is dropped; produces a Locked error if the
+    /// object is locked incompatibly. Backends whose objects cannot be shared
+    /// with other processes need not lock anything.
+    fn lock(&self, _name: &str, _mode: LockMode) -> Result<Box<ObjectLock>> {
+        Ok(Box::new(()))
+    }
+}
+
+/// Adapts a single object of a StorageBackend to the std::io traits, so that it
+/// can be used wherever a file could be.
+pub struct ObjectCursor<'a> {
+    backend: &'a StorageBackend,
+    name: &'a str,
+    position: u64,
+}
+
+impl<'a> ObjectCursor<'a> {
+    pub fn new(backend: &'a StorageBackend, name: &'a str) -> ObjectCursor<'a> {
+        ObjectCursor {
+            backend,
+            name,
+            position: 0,
+        }
+    }
+}
+
+impl<'a> Read for ObjectCursor<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self
+            .backend
+            .read_at(self.name, self.position, buf)
+            .map_err(into_io_error)?;
+
+        self.position += n as u64;
+
+        Ok(n)
+    }
+}
+
+impl<'a> Write for ObjectCursor<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.backend
+            .write_at(self.name, self.position, buf)
+            .map_err(into_io_error)?;
+
+        self.position += buf.len() as u64;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> Seek for ObjectCursor<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, delta) = match pos {
+            SeekFrom::Start(offset) => {
+                self.position = offset;
+                return Ok(offset);
+            }
+            SeekFrom::End(delta) => (self.backend.size(self.name).map_err(into_io_error)?, delta),
+            SeekFrom::Current(delta) => (self.position, delta),
+        };
+
+        let position = if delta >= 0 {
+            base.checked_add(delta as u64)
+        } else {
+            base.checked_sub(delta.wrapping_neg() as u64)
+        };
+
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+fn into_io_error(err: failure::Error) -> io::Error {
+    io::Error::new(ErrorKind::Other, format!("{}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local_fs::LocalFs;
+
+    #[test]
+    fn cursor_reads_writes_and_seeks() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = LocalFs::new(dir.path());
+
+        backend.open("obj").unwrap();
+
+        let mut cursor = ObjectCursor::new(&backend, "obj");
+
+        cursor.write_all(b"hello world").unwrap();
+        assert_eq!(cursor.seek(SeekFrom::End(-5)).unwrap(), 6);
+
+        let mut buf = Vec::new();
+        cursor.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"world".to_vec());
+
+        cursor.seek(SeekFrom::Start(0)).unwrap();
+        cursor.write_all(b"J").unwrap();
+        assert_eq!(cursor.seek(SeekFrom::Current(0)).unwrap(), 1);
+
+        let mut buf = [0; 5];
+        cursor.seek(SeekFrom::Start(0)).unwrap();
+        cursor.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"Jello");
+
+        assert!(cursor.seek(SeekFrom::Current(-6)).is_err());
+    }
+}
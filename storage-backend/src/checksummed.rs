@@ -0,0 +1,322 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use blake2::{Blake2s, Digest};
+
+use crate::backend::{LockMode, ObjectLock, StorageBackend};
+use crate::error::{is_not_found, Result, StorageErr};
+
+// Appended to an object's name to form the name of the object holding its
+// checksum.
+const CHECKSUM_SUFFIX: &str = ".blake2s";
+
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// Wraps another backend, recording a checksum of each object's contents when
+/// the object is synced or closed and checking it before the object is first
+/// read.
+///
+/// The first write after a sync discards the object's checksum until the next
+/// sync, so objects which have been written but not synced are read unchecked.
+/// An object is checked once per Checksummed instance; `verify` checks it
+/// again.
+pub struct Checksummed<B: StorageBackend> {
+    inner: B,
+
+    // Objects whose contents have matched their checksum since they were last
+    // written.
+    verified: Mutex<HashSet<String>>,
+
+    // Objects whose checksum has been discarded by a write since they were
+    // last synced.
+    stale: Mutex<HashSet<String>>,
+}
+
+impl<B: StorageBackend> Checksummed<B> {
+    pub fn new(inner: B) -> Checksummed<B> {
+        Checksummed {
+            inner,
+            verified: Default::default(),
+            stale: Default::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    fn verify_once(&self, name: &str) -> Result<()> {
+        let is_verified = self
+            .verified
+            .lock()
+            .map_err(|_| format_err!("verified set lock poisoned"))?
+            .contains(name);
+
+        if is_verified {
+            Ok(())
+        } else {
+            self.verify(name)
+        }
+    }
+
+    fn compute_checksum(&self, name: &str) -> Result<Vec<u8>> {
+        let mut hasher = Blake2s::new();
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut offset = 0;
+
+        loop {
+            let n = self.inner.read_at(name, offset, &mut buf)?;
+
+            if n == 0 {
+                break;
+            }
+
+            hasher.input(&buf[..n]);
+            offset += n as u64;
+        }
+
+        Ok(hasher.result().to_vec())
+    }
+
+    fn read_checksum(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let checksum_name = checksum_name(name);
+
+        let size = match self.inner.size(&checksum_name) {
+            Ok(size) => size,
+            Err(ref err) if is_not_found(err) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let mut checksum = vec![0; size as usize];
+        let n = self.inner.read_at(&checksum_name, 0, &mut checksum)?;
+        checksum.truncate(n);
+
+        Ok(Some(checksum))
+    }
+
+    // Writes the checksum of the object's current contents, which is durable
+    // once the checksum object is synced or closed.
+    fn record_checksum(&self, name: &str) -> Result<()> {
+        let checksum = self.compute_checksum(name)?;
+        let checksum_name = checksum_name(name);
+
+        self.inner.open(&checksum_name)?;
+        self.inner.set_size(&checksum_name, 0)?;
+        self.inner.write_at(&checksum_name, 0, &checksum)?;
+
+        self.stale
+            .lock()
+            .map_err(|_| format_err!("stale set lock poisoned"))?
+            .remove(name);
+
+        self.verified
+            .lock()
+            .map_err(|_| format_err!("verified set lock poisoned"))?
+            .insert(name.to_string());
+
+        Ok(())
+    }
+
+    // Drops the object's checksum because its contents are changing, unless
+    // it was already dropped by an earlier write.
+    fn mark_stale(&self, name: &str) -> Result<()> {
+        let newly_stale = self
+            .stale
+            .lock()
+            .map_err(|_| format_err!("stale set lock poisoned"))?
+            .insert(name.to_string());
+
+        if newly_stale {
+            self.discard_checksum(name)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Drops the object's checksum, e.g. because its contents are changing.
+    fn discard_checksum(&self, name: &str) -> Result<()> {
+        self.forget(name);
+
+        match self.inner.delete(&checksum_name(name)) {
+            Err(ref err) if is_not_found(err) => Ok(()),
+            result => result,
+        }
+    }
+
+    fn forget(&self, name: &str) {
+        if let Ok(mut verified) = self.verified.lock() {
+            verified.remove(name);
+        }
+    }
+}
+
+impl<B: StorageBackend> StorageBackend for Checksummed<B> {
+    fn open(&self, name: &str) -> Result<()> {
+        if name.ends_with(CHECKSUM_SUFFIX) {
+            return Err(StorageErr::InvalidName(name.to_string()).into());
+        }
+
+        self.inner.open(name)
+    }
+
+    fn read_at(&self, name: &str, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        self.verify_once(name)?;
+        self.inner.read_at(name, offset, buf)
+    }
+
+    fn write_at(&self, name: &str, offset: u64, data: &[u8]) -> Result<()> {
+        self.mark_stale(name)?;
+        self.inner.write_at(name, offset, data)
+    }
+
+    fn set_size(&self, name: &str, size: u64) -> Result<()> {
+        self.mark_stale(name)?;
+        self.inner.set_size(name, size)
+    }
+
+    // The object is made durable before its checksum, so that a crash cannot
+    // leave a checksum of contents which were never written.
+    fn sync(&self, name: &str) -> Result<()> {
+        self.inner.sync(name)?;
+        self.record_checksum(name)?;
+        self.inner.sync(&checksum_name(name))
+    }
+
+    fn close(&self, name: &str) -> Result<()> {
+        self.inner.sync(name)?;
+        self.record_checksum(name)?;
+        self.inner.close(name)?;
+        self.inner.close(&checksum_name(name))
+    }
+
+    // Produces a ChecksumMismatch error if the object's contents differ from
+    // its recorded checksum. Objects without a recorded checksum pass.
+    fn verify(&self, name: &str) -> Result<()> {
+        let expected = match self.read_checksum(name)? {
+            Some(checksum) => checksum,
+            None => return Ok(()),
+        };
+
+        if self.compute_checksum(name)? != expected {
+            self.forget(name);
+            return Err(StorageErr::ChecksumMismatch(name.to_string()).into());
+        }
+
+        self.verified
+            .lock()
+            .map_err(|_| format_err!("verified set lock poisoned"))?
+            .insert(name.to_string());
+
+        Ok(())
+    }
+
+    fn size(&self, name: &str) -> Result<u64> {
+        self.inner.size(name)
+    }
+
+    fn delete(&self, name: &str) -> Result<()> {
+        self.inner.delete(name)?;
+
+        self.stale
+            .lock()
+            .map_err(|_| format_err!("stale set lock poisoned"))?
+            .remove(name);
+
+        self.discard_checksum(name)
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        Ok(self
+            .inner
+            .list()?
+            .into_iter()
+            .filter(|name| !name.ends_with(CHECKSUM_SUFFIX))
+            .collect())
+    }
+
+    fn available_bytes(&self) -> Result<u64> {
+        self.inner.available_bytes()
+    }
+
+    fn lock(&self, name: &str, mode: LockMode) -> Result<Box<ObjectLock>> {
+        self.inner.lock(name, mode)
+    }
+
+    // Objects may be read through the inner backend's files, e.g. by sealing,
+    // but are only checked when read through this backend. Writes made
+    // directly to those files are not checksummed until the object is next
+    // synced or closed.
+    fn local_path(&self, name: &str) -> Option<PathBuf> {
+        self.inner.local_path(name)
+    }
+}
+
+fn checksum_name(name: &str) -> String {
+    format!("{}{}", name, CHECKSUM_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local_fs::LocalFs;
+
+    #[test]
+    fn detects_corruption_of_synced_objects() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = Checksummed::new(LocalFs::new(dir.path()));
+
+        backend.open("obj").unwrap();
+        backend.write_at("obj", 0, b"some bytes").unwrap();
+        backend.sync("obj").unwrap();
+
+        assert_eq!(backend.list().unwrap(), vec!["obj"]);
+
+        // corrupt the object behind the wrapper's back
+        backend.inner().write_at("obj", 0, b"S").unwrap();
+
+        let reopened = Checksummed::new(LocalFs::new(dir.path()));
+        let mut buf = [0; 4];
+
+        match reopened.read_at("obj", 0, &mut buf) {
+            Err(err) => match err.downcast_ref() {
+                Some(StorageErr::ChecksumMismatch(name)) => assert_eq!(name, "obj"),
+                _ => panic!("unexpected error: {}", err),
+            },
+            Ok(_) => panic!("expected a checksum mismatch"),
+        }
+
+        // rewriting and syncing the object records a new checksum
+        reopened.write_at("obj", 0, b"s").unwrap();
+        assert_eq!(reopened.read_at("obj", 0, &mut buf).unwrap(), 4);
+        reopened.sync("obj").unwrap();
+
+        assert!(Checksummed::new(LocalFs::new(dir.path()))
+            .verify("obj")
+            .is_ok());
+
+        reopened.delete("obj").unwrap();
+        assert_eq!(reopened.inner().list().unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn records_checksum_on_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = Checksummed::new(LocalFs::new(dir.path()));
+
+        backend.open("obj").unwrap();
+        backend.write_at("obj", 0, b"some").unwrap();
+        backend.write_at("obj", 4, b" bytes").unwrap();
+
+        // the checksum discarded by the first write is only recorded on close
+        assert_eq!(backend.inner().list().unwrap(), vec!["obj"]);
+        backend.close("obj").unwrap();
+        assert_eq!(backend.inner().list().unwrap(), vec!["obj", "obj.blake2s"]);
+
+        backend.inner().write_at("obj", 0, b"S").unwrap();
+
+        assert!(Checksummed::new(LocalFs::new(dir.path()))
+            .verify("obj")
+            .is_err());
+    }
+}
@@ -0,0 +1,34 @@
+use failure::Error;
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+#[derive(Debug, Fail)]
+pub enum StorageErr {
+    #[fail(display = "no object named {}", _0)]
+    NotFound(String),
+
+    #[fail(display = "invalid object name: {}", _0)]
+    InvalidName(String),
+
+    #[fail(display = "contents of {} do not match their checksum", _0)]
+    ChecksumMismatch(String),
+
+    #[fail(display = "{} is locked by another process", _0)]
+    Locked(String),
+}
+
+// Returns true if err reports a missing object.
+pub fn is_not_found(err: &Error) -> bool {
+    match err.downcast_ref() {
+        Some(StorageErr::NotFound(_)) => true,
+        _ => false,
+    }
+}
+
+// Returns true if err reports an object locked by someone else.
+pub fn is_locked(err: &Error) -> bool {
+    match err.downcast_ref() {
+        Some(StorageErr::Locked(_)) => true,
+        _ => false,
+    }
+}
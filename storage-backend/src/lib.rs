@@ -1,2 +1,21 @@
 #![deny(clippy::all, clippy::perf, clippy::correctness)]
 #![allow(clippy::unreadable_literal)]
+
+extern crate blake2;
+#[macro_use]
+extern crate failure;
+extern crate fs2;
+extern crate libc;
+
+#[cfg(test)]
+extern crate tempfile;
+
+pub mod backend;
+pub mod checksummed;
+pub mod error;
+pub mod local_fs;
+
+pub use crate::backend::{LockMode, ObjectCursor, ObjectLock, StorageBackend};
+pub use crate::checksummed::Checksummed;
+pub use crate::error::StorageErr;
+pub use crate::local_fs::LocalFs;
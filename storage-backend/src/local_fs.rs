@@ -0,0 +1,339 @@
+use fs2::FileExt as LockExt;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind};
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::backend::{LockMode, ObjectLock, StorageBackend};
+use crate::error::{Result, StorageErr};
+
+/// Stores each object as a file in a single directory. The directory is
+/// created when the first object is opened.
+///
+/// The file of an object which has been read or written stays open until the
+/// object is closed or deleted, so files must not be replaced (rather than
+/// written to) behind the backend's back while their objects are in use.
+pub struct LocalFs {
+    root: PathBuf,
+
+    // Files of the objects in use, by object name.
+    files: Mutex<HashMap<String, Arc<File>>>,
+}
+
+impl LocalFs {
+    pub fn new<P: Into<PathBuf>>(root: P) -> LocalFs {
+        LocalFs {
+            root: root.into(),
+            files: Default::default(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Objects live directly in the root directory, so names which would
+    // resolve elsewhere are refused.
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        if name.is_empty()
+            || name == "."
+            || name == ".."
+            || name.contains(|c: char| c == '/' || c == '\\')
+        {
+            return Err(StorageErr::InvalidName(name.to_string()).into());
+        }
+
+        Ok(self.root.join(name))
+    }
+
+    fn open_file(&self, name: &str, options: &OpenOptions) -> Result<File> {
+        options
+            .open(self.path(name)?)
+            .map_err(|err| map_io_error(name, err))
+    }
+
+    // Returns the object's open file, opening it if it is not in use. Files
+    // which may not be written are opened for reading only.
+    fn file(&self, name: &str) -> Result<Arc<File>> {
+        let mut files = self
+            .files
+            .lock()
+            .map_err(|_| format_err!("open files lock poisoned"))?;
+
+        if let Some(file) = files.get(name) {
+            return Ok(file.clone());
+        }
+
+        let file = match self.open_file(name, OpenOptions::new().read(true).write(true)) {
+            Ok(file) => file,
+            Err(err) => {
+                let is_read_only = err
+                    .downcast_ref::<io::Error>()
+                    .map(|err| err.kind() == ErrorKind::PermissionDenied)
+                    .unwrap_or(false);
+
+                if !is_read_only {
+                    return Err(err);
+                }
+
+                self.open_file(name, OpenOptions::new().read(true))?
+            }
+        };
+
+        let file = Arc::new(file);
+        files.insert(name.to_string(), file.clone());
+
+        Ok(file)
+    }
+
+    // Stops keeping the object's file open, returning it if it was.
+    fn forget_file(&self, name: &str) -> Result<Option<Arc<File>>> {
+        Ok(self
+            .files
+            .lock()
+            .map_err(|_| format_err!("open files lock poisoned"))?
+            .remove(name))
+    }
+}
+
+impl StorageBackend for LocalFs {
+    fn open(&self, name: &str) -> Result<()> {
+        let path = self.path(name)?;
+
+        fs::create_dir_all(&self.root)?;
+
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(path)
+            .map(|_| ())
+            .map_err(|err| map_io_error(name, err))
+    }
+
+    fn read_at(&self, name: &str, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let file = self.file(name)?;
+
+        let mut num_read = 0;
+
+        while num_read < buf.len() {
+            match file.read_at(&mut buf[num_read..], offset + num_read as u64) {
+                Ok(0) => break,
+                Ok(n) => num_read += n,
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(num_read)
+    }
+
+    fn write_at(&self, name: &str, offset: u64, data: &[u8]) -> Result<()> {
+        let file = self.file(name)?;
+
+        let mut num_written = 0;
+
+        while num_written < data.len() {
+            match file.write_at(&data[num_written..], offset + num_written as u64) {
+                Ok(0) => return Err(io::Error::from(ErrorKind::WriteZero).into()),
+                Ok(n) => num_written += n,
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_size(&self, name: &str, size: u64) -> Result<()> {
+        self.file(name)?.set_len(size)?;
+
+        Ok(())
+    }
+
+    fn sync(&self, name: &str) -> Result<()> {
+        self.file(name)?.sync_all()?;
+
+        Ok(())
+    }
+
+    fn close(&self, name: &str) -> Result<()> {
+        let file = match self.forget_file(name)? {
+            Some(file) => file,
+            None => Arc::new(self.open_file(name, OpenOptions::new().read(true))?),
+        };
+
+        file.sync_all()?;
+
+        Ok(())
+    }
+
+    fn size(&self, name: &str) -> Result<u64> {
+        fs::metadata(self.path(name)?)
+            .map(|metadata| metadata.len())
+            .map_err(|err| map_io_error(name, err))
+    }
+
+    fn delete(&self, name: &str) -> Result<()> {
+        self.forget_file(name)?;
+
+        fs::remove_file(self.path(name)?).map_err(|err| map_io_error(name, err))
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = Vec::new();
+
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+
+            if entry.file_type()?.is_file() {
+                if let Some(name) = entry.file_name().to_str() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+
+        names.sort();
+
+        Ok(names)
+    }
+
+    fn available_bytes(&self) -> Result<u64> {
+        fs::create_dir_all(&self.root)?;
+
+        let path = CString::new(self.root.as_os_str().as_bytes())?;
+        let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+    }
+
+    fn local_path(&self, name: &str) -> Option<PathBuf> {
+        self.path(name).ok()
+    }
+
+    // Objects are locked with flock(2) (or its equivalent), which also
+    // excludes locks taken through other descriptors of the same process.
+    fn lock(&self, name: &str, mode: LockMode) -> Result<Box<ObjectLock>> {
+        let file = self.open_file(name, OpenOptions::new().read(true))?;
+
+        let locked = match mode {
+            LockMode::Shared => file.try_lock_shared(),
+            LockMode::Exclusive => file.try_lock_exclusive(),
+        };
+
+        match locked {
+            Ok(()) => Ok(Box::new(file)),
+            Err(ref err) if err.kind() == fs2::lock_contended_error().kind() => {
+                Err(StorageErr::Locked(name.to_string()).into())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+fn map_io_error(name: &str, err: io::Error) -> failure::Error {
+    if err.kind() == ErrorKind::NotFound {
+        StorageErr::NotFound(name.to_string()).into()
+    } else {
+        err.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{is_locked, is_not_found};
+
+    #[test]
+    fn local_fs_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = LocalFs::new(dir.path().join("objects"));
+
+        assert_eq!(backend.list().unwrap(), Vec::<String>::new());
+        assert!(is_not_found(&backend.size("a").unwrap_err()));
+
+        backend.open("b").unwrap();
+        backend.open("a").unwrap();
+        assert_eq!(backend.list().unwrap(), vec!["a", "b"]);
+
+        backend.write_at("a", 2, b"xyz").unwrap();
+        backend.sync("a").unwrap();
+        assert_eq!(backend.size("a").unwrap(), 5);
+
+        // opening an existing object keeps its contents
+        backend.open("a").unwrap();
+
+        let mut buf = [9; 8];
+        assert_eq!(backend.read_at("a", 1, &mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], &[0, b'x', b'y', b'z']);
+
+        backend.set_size("a", 3).unwrap();
+        assert_eq!(backend.read_at("a", 0, &mut buf).unwrap(), 3);
+
+        // closing an object keeps its contents, and it may be used again
+        backend.close("a").unwrap();
+        assert_eq!(backend.read_at("a", 0, &mut buf).unwrap(), 3);
+        backend.close("b").unwrap();
+
+        backend.delete("a").unwrap();
+        assert!(is_not_found(&backend.delete("a").unwrap_err()));
+        assert_eq!(backend.list().unwrap(), vec!["b"]);
+
+        assert!(backend.available_bytes().unwrap() > 0);
+
+        assert_eq!(
+            backend.local_path("b"),
+            Some(dir.path().join("objects").join("b"))
+        );
+        assert_eq!(backend.local_path("../b"), None);
+
+        assert!(backend.open("../escape").is_err());
+        assert!(backend.open("").is_err());
+    }
+
+    #[test]
+    fn local_fs_locks() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = LocalFs::new(dir.path());
+
+        assert!(is_not_found(
+            &backend.lock("a", LockMode::Shared).err().unwrap()
+        ));
+
+        backend.open("a").unwrap();
+
+        {
+            let _shared_a = backend.lock("a", LockMode::Shared).unwrap();
+            let _shared_b = backend.lock("a", LockMode::Shared).unwrap();
+
+            assert!(is_locked(
+                &backend.lock("a", LockMode::Exclusive).err().unwrap()
+            ));
+        }
+
+        {
+            let _exclusive = backend.lock("a", LockMode::Exclusive).unwrap();
+
+            assert!(is_locked(
+                &backend.lock("a", LockMode::Shared).err().unwrap()
+            ));
+
+            // locks are advisory, so writes are not prevented
+            backend.write_at("a", 0, b"x").unwrap();
+        }
+
+        let _exclusive = backend.lock("a", LockMode::Exclusive).unwrap();
+    }
+}
@@ -227,6 +227,7 @@ mod tests {
             params: merklepor::PublicParams {
                 leaves: 32,
                 private: false,
+                arity: 2,
             },
             batch_count: 10,
         };
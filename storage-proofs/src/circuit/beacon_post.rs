@@ -215,17 +215,7 @@ mod tests {
 
             for porep_proof in &p.porep_proofs {
                 // -- paths
-                paths_vec.push(
-                    porep_proof
-                        .paths()
-                        .iter()
-                        .map(|p| {
-                            p.iter()
-                                .map(|v| Some((v.0.into(), v.1)))
-                                .collect::<Vec<_>>()
-                        })
-                        .collect::<Vec<_>>(),
-                );
+                paths_vec.push(porep_proof.paths().unwrap());
 
                 // -- challenged leafs
                 challenged_leafs_vec.push(
@@ -48,8 +48,9 @@ use std::marker::PhantomData;
 //    "drg-proof-of-replication",
 //    false
 //);
-use crate::circuit::por::{PoRCircuit, PoRCompound};
+use crate::circuit::por::{binary_auth_path, PoRCircuit, PoRCompound};
 use crate::hasher::{Domain, Hasher};
+use crate::merkle::DEFAULT_ARITY;
 
 pub struct DrgPoRepCircuit<'a, E: JubjubEngine> {
     params: &'a E::Params,
@@ -178,6 +179,7 @@ where
         let por_pub_params = merklepor::PublicParams {
             leaves,
             private: comm_d.is_none(),
+            arity: DEFAULT_ARITY,
         };
 
         let mut input = Vec::new();
@@ -230,7 +232,11 @@ where
         let replica_nodes_paths = proof
             .replica_nodes
             .iter()
-            .map(|node| node.proof.as_options())
+            .map(|node| {
+                node.proof
+                    .as_options()
+                    .expect("DrgPoRep proves binary trees only")
+            })
             .collect();
 
         let private_data_root = component_private_inputs.comm_d;
@@ -258,7 +264,12 @@ where
             .map(|parents| {
                 let p: Vec<_> = parents
                     .iter()
-                    .map(|(_, parent)| parent.proof.as_options())
+                    .map(|(_, parent)| {
+                        parent
+                            .proof
+                            .as_options()
+                            .expect("DrgPoRep proves binary trees only")
+                    })
                     .collect();
                 p
             })
@@ -273,7 +284,11 @@ where
         let data_nodes_paths = proof
             .nodes
             .iter()
-            .map(|node| node.proof.as_options())
+            .map(|node| {
+                node.proof
+                    .as_options()
+                    .expect("DrgPoRep proves binary trees only")
+            })
             .collect();
 
         DrgPoRepCircuit {
@@ -377,7 +392,8 @@ impl<'a, E: JubjubEngine> Circuit<E> for DrgPoRepCircuit<'a, E> {
                     cs.namespace(|| "replica_inclusion"),
                     &params,
                     *replica_node,
-                    replica_node_path.clone(),
+                    binary_auth_path(replica_node_path.clone()),
+                    DEFAULT_ARITY,
                     replica_root_var.clone(),
                     self.private,
                 )?;
@@ -388,7 +404,8 @@ impl<'a, E: JubjubEngine> Circuit<E> for DrgPoRepCircuit<'a, E> {
                         cs.namespace(|| format!("parents_inclusion_{}", i)),
                         &params,
                         replica_parents[i],
-                        replica_parents_paths[i].clone(),
+                        binary_auth_path(replica_parents_paths[i].clone()),
+                        DEFAULT_ARITY,
                         replica_root_var.clone(),
                         self.private,
                     )?;
@@ -399,7 +416,8 @@ impl<'a, E: JubjubEngine> Circuit<E> for DrgPoRepCircuit<'a, E> {
                     cs.namespace(|| "data_inclusion"),
                     &params,
                     *data_node,
-                    data_node_path.clone(),
+                    binary_auth_path(data_node_path.clone()),
+                    DEFAULT_ARITY,
                     data_root_var.clone(),
                     self.private,
                 )?;
@@ -524,7 +542,7 @@ mod tests {
             challenges: vec![challenge],
             tau: Some(tau.into()),
         };
-        let priv_inputs = drgporep::PrivateInputs::<PedersenHasher> { aux: &aux };
+        let priv_inputs = drgporep::PrivateInputs::<PedersenHasher>::new(&aux);
 
         let proof_nc =
             drgporep::DrgPoRep::<PedersenHasher, _>::prove(&pp, &pub_inputs, &priv_inputs)
@@ -538,7 +556,7 @@ mod tests {
 
         let replica_node: Option<Fr> = Some(proof_nc.replica_nodes[0].data.into());
 
-        let replica_node_path = proof_nc.replica_nodes[0].proof.as_options();
+        let replica_node_path = proof_nc.replica_nodes[0].proof.as_options().unwrap();
         let replica_root = Root::Val(Some((proof_nc.replica_root).into()));
         let replica_parents = proof_nc.replica_parents[0]
             .iter()
@@ -546,15 +564,15 @@ mod tests {
             .collect();
         let replica_parents_paths: Vec<_> = proof_nc.replica_parents[0]
             .iter()
-            .map(|(_, parent)| parent.proof.as_options())
+            .map(|(_, parent)| parent.proof.as_options().unwrap())
             .collect();
 
-        let data_node_path = proof_nc.nodes[0].proof.as_options();
+        let data_node_path = proof_nc.nodes[0].proof.as_options().unwrap();
         let data_root = Root::Val(Some((proof_nc.data_root).into()));
         let replica_id = Some(replica_id);
 
         assert!(
-            proof_nc.nodes[0].proof.validate(challenge),
+            proof_nc.nodes[0].proof.validate(challenge, DEFAULT_ARITY),
             "failed to verify data commitment"
         );
         assert!(
@@ -686,7 +704,7 @@ mod tests {
             challenges,
             tau: Some(tau),
         };
-        let private_inputs = drgporep::PrivateInputs { aux: &aux };
+        let private_inputs = drgporep::PrivateInputs::new(&aux);
 
         // This duplication is necessary so public_params don't outlive public_inputs and private_inputs.
         let setup_params = compound_proof::SetupParams {
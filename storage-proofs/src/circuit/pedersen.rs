@@ -1,5 +1,7 @@
 use bellman::{ConstraintSystem, SynthesisError};
 use sapling_crypto::circuit::boolean::Boolean;
+use sapling_crypto::circuit::ecc::{EdwardsPoint, MontgomeryPoint};
+use sapling_crypto::circuit::lookup::lookup3_xy_with_conditional_negation;
 use sapling_crypto::circuit::{num, pedersen_hash};
 use sapling_crypto::jubjub::JubjubEngine;
 
@@ -62,6 +64,89 @@ pub fn pedersen_compression_num<E: JubjubEngine, CS: ConstraintSystem<E>>(
     .clone())
 }
 
+/// Hashes `bits`, after the constant bits of `personalization`, as
+/// `crypto::pedersen::pedersen_hash_with_generators` does, given the tables of
+/// its generators returned by `crypto::pedersen::circuit_generators`.
+pub fn pedersen_hash_with_generators<E, CS>(
+    mut cs: CS,
+    personalization: &[bool],
+    bits: &[Boolean],
+    generators: &[Vec<Vec<(E::Fr, E::Fr)>>],
+    params: &E::Params,
+) -> Result<EdwardsPoint<E>, SynthesisError>
+where
+    E: JubjubEngine,
+    CS: ConstraintSystem<E>,
+{
+    let personalization: Vec<Boolean> = personalization
+        .iter()
+        .map(|b| Boolean::constant(*b))
+        .collect();
+
+    let boolean_false = Boolean::constant(false);
+    let mut bits = personalization.iter().chain(bits.iter());
+    let mut generators = generators.iter();
+    let mut edwards_result: Option<EdwardsPoint<E>> = None;
+    let mut segment_i = 0;
+
+    loop {
+        let mut segment_result: Option<MontgomeryPoint<E>> = None;
+        let mut segment_windows = &generators
+            .next()
+            .expect("not enough Pedersen hash generators")[..];
+        let mut window_i = 0;
+
+        while let Some(a) = bits.next() {
+            let b = bits.next().unwrap_or(&boolean_false);
+            let c = bits.next().unwrap_or(&boolean_false);
+
+            let (x, y) = lookup3_xy_with_conditional_negation(
+                cs.namespace(|| format!("segment {}, window {}", segment_i, window_i)),
+                &[a.clone(), b.clone(), c.clone()],
+                &segment_windows[0],
+            )?;
+            let tmp = MontgomeryPoint::interpret_unchecked(x, y);
+
+            segment_result = Some(match segment_result {
+                None => tmp,
+                Some(segment_result) => tmp.add(
+                    cs.namespace(|| {
+                        format!("addition of segment {}, window {}", segment_i, window_i)
+                    }),
+                    &segment_result,
+                    params,
+                )?,
+            });
+
+            segment_windows = &segment_windows[1..];
+            if segment_windows.is_empty() {
+                break;
+            }
+            window_i += 1;
+        }
+
+        let segment_result = match segment_result {
+            Some(segment_result) => segment_result.into_edwards(
+                cs.namespace(|| format!("conversion of segment {} into edwards", segment_i)),
+                params,
+            )?,
+            None => break,
+        };
+
+        edwards_result = Some(match edwards_result {
+            None => segment_result,
+            Some(edwards_result) => segment_result.add(
+                cs.namespace(|| format!("edwards addition of segment {}", segment_i)),
+                &edwards_result,
+                params,
+            )?,
+        });
+        segment_i += 1;
+    }
+
+    Ok(edwards_result.expect("nothing to hash"))
+}
+
 pub fn pedersen_compression<E: JubjubEngine, CS: ConstraintSystem<E>>(
     mut cs: CS,
     params: &E::Params,
@@ -80,14 +165,15 @@ pub fn pedersen_compression<E: JubjubEngine, CS: ConstraintSystem<E>>(
 
 #[cfg(test)]
 mod tests {
-    use super::pedersen_md_no_padding;
+    use super::{pedersen_hash_with_generators, pedersen_md_no_padding};
     use crate::circuit::test::TestConstraintSystem;
     use crate::crypto;
+    use crate::crypto::pedersen::{circuit_generators, merkle_generators, merkle_personalization};
     use crate::util::bytes_into_boolean_vec;
     use bellman::ConstraintSystem;
     use pairing::bls12_381::Bls12;
     use rand::{Rng, SeedableRng, XorShiftRng};
-    use sapling_crypto::circuit::boolean::Boolean;
+    use sapling_crypto::circuit::boolean::{AllocatedBit, Boolean};
     use sapling_crypto::jubjub::JubjubBls12;
 
     #[test]
@@ -117,4 +203,52 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_pedersen_hash_with_generators_circuit() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = &JubjubBls12::new();
+        let generators = merkle_generators::<Bls12>(params);
+        let personalization = merkle_personalization(2, 8);
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let bits: Vec<bool> = (0..8 * 255).map(|_| rng.gen()).collect();
+        let bits_circuit: Vec<Boolean> = bits
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                Boolean::from(
+                    AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), Some(*b)).unwrap(),
+                )
+            })
+            .collect();
+
+        let out = pedersen_hash_with_generators(
+            cs.namespace(|| "pedersen"),
+            &personalization,
+            &bits_circuit,
+            &circuit_generators::<Bls12>(&generators, params),
+            params,
+        )
+        .unwrap();
+
+        assert!(cs.is_satisfied(), "constraints not satisfied");
+
+        let expected = crypto::pedersen::pedersen_hash_with_generators::<Bls12, _>(
+            &personalization,
+            bits,
+            &generators,
+            params,
+        )
+        .into_xy();
+
+        assert_eq!(
+            (
+                out.get_x().get_value().unwrap(),
+                out.get_y().get_value().unwrap()
+            ),
+            expected,
+            "circuit and non circuit do not match"
+        );
+    }
 }
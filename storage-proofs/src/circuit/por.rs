@@ -4,9 +4,11 @@ use sapling_crypto::circuit::{boolean, multipack, num, pedersen_hash};
 use sapling_crypto::jubjub::{JubjubBls12, JubjubEngine};
 
 use crate::circuit::constraint;
+use crate::circuit::pedersen::pedersen_hash_with_generators;
 use crate::circuit::variables::Root;
 use crate::compound_proof::{CircuitComponent, CompoundProof};
-use crate::drgraph::graph_height;
+use crate::crypto::pedersen::{circuit_generators, merkle_generators, merkle_personalization};
+use crate::merkle::{tree_depth, MAX_ARITY};
 use crate::merklepor::MerklePoR;
 use crate::parameter_cache::{CacheableParameters, ParameterSetIdentifier};
 use crate::proof::ProofScheme;
@@ -18,6 +20,7 @@ use crate::proof::ProofScheme;
 /// * `params` - The params for the bls curve.
 /// * `value` - The value of the leaf.
 /// * `auth_path` - The authentication path of the leaf in the tree.
+/// * `arity` - The arity of the tree.
 /// * `root` - The merkle root of the tree.
 ///
 use crate::hasher::Hasher;
@@ -26,11 +29,25 @@ use std::marker::PhantomData;
 pub struct PoRCircuit<'a, E: JubjubEngine> {
     params: &'a E::Params,
     value: Option<E::Fr>,
-    auth_path: Vec<Option<(E::Fr, bool)>>,
+    auth_path: AuthPath<E::Fr>,
+    arity: usize,
     root: Root<E>,
     private: bool,
 }
 
+/// The authentication path of a leaf in a tree of any arity: for each level
+/// below the root, the siblings of the leaf's ancestor on that level, in
+/// order, and the index of the ancestor among them.
+pub type AuthPath<F> = Vec<Option<(Vec<F>, usize)>>;
+
+/// Converts a binary authentication path, each element of which is marked by
+/// whether the path is taking the right path, to an `AuthPath`.
+pub fn binary_auth_path<F>(path: Vec<Option<(F, bool)>>) -> AuthPath<F> {
+    path.into_iter()
+        .map(|e| e.map(|(hash, is_right)| (vec![hash], is_right as usize)))
+        .collect()
+}
+
 impl<'a, E: JubjubEngine> CircuitComponent for PoRCircuit<'a, E> {
     type ComponentPrivateInputs = Option<Root<E>>;
 }
@@ -39,8 +56,9 @@ pub struct PoRCompound<H: Hasher> {
     _h: PhantomData<H>,
 }
 
-pub fn challenge_into_auth_path_bits(challenge: usize, leaves: usize) -> Vec<bool> {
-    let height = graph_height(leaves);
+pub fn challenge_into_auth_path_bits(challenge: usize, leaves: usize, arity: usize) -> Vec<bool> {
+    // Each level of the path contributes the bits of an index among arity nodes.
+    let height = tree_depth(leaves, arity) * arity.trailing_zeros() as usize;
     let mut bits = Vec::new();
     let mut n = challenge;
     for _ in 0..height {
@@ -50,6 +68,160 @@ pub fn challenge_into_auth_path_bits(challenge: usize, leaves: usize) -> Vec<boo
     bits
 }
 
+/// Ascends the authentication path of `leaf` in a tree of the given arity,
+/// which must be a power of two no greater than `MAX_ARITY`. Returns the root
+/// it leads to and the bits of the leaf's index in the tree, least significant
+/// first.
+///
+/// The children of each node are hashed as by `merkle::hash_children`.
+pub fn ascend_auth_path<E, CS>(
+    mut cs: CS,
+    params: &E::Params,
+    leaf: num::AllocatedNum<E>,
+    auth_path: AuthPath<E::Fr>,
+    arity: usize,
+) -> Result<(num::AllocatedNum<E>, Vec<boolean::Boolean>), SynthesisError>
+where
+    E: JubjubEngine,
+    CS: ConstraintSystem<E>,
+{
+    assert!(
+        arity.is_power_of_two() && arity > 1 && arity <= MAX_ARITY,
+        "arity must be a power of two no greater than MAX_ARITY"
+    );
+    let index_bits = arity.trailing_zeros() as usize;
+
+    // The children of nodes of binary trees are hashed with sapling's
+    // generators, and those of greater arity with further ones.
+    let generators = if arity > 2 {
+        circuit_generators::<E>(&merkle_generators::<E>(params), params)
+    } else {
+        Vec::new()
+    };
+
+    let mut cur = leaf;
+
+    let mut auth_path_bits = Vec::with_capacity(auth_path.len() * index_bits);
+
+    // Ascend the merkle tree authentication path
+    for (i, e) in auth_path.into_iter().enumerate() {
+        let cs = &mut cs.namespace(|| format!("merkle tree hash {}", i));
+
+        // Determines the position of the current subtree among its siblings at
+        // this depth of the tree: bit b is set if it is in the "right" half of
+        // the block of 2^(b + 1) nodes containing it.
+        let mut position_bits = Vec::with_capacity(index_bits);
+        for b in 0..index_bits {
+            position_bits.push(boolean::Boolean::from(boolean::AllocatedBit::alloc(
+                cs.namespace(|| format!("position bit {}", b)),
+                e.as_ref().map(|e| (e.1 >> b) & 1 == 1),
+            )?));
+        }
+
+        // Witness the authentication path elements adjacent at this depth,
+        // ordered by the position bit which places them before or after the
+        // current subtree.
+        let values = match e {
+            Some((siblings, index)) => {
+                assert_eq!(siblings.len(), arity - 1, "wrong number of siblings");
+                sibling_blocks(&siblings, index)
+                    .into_iter()
+                    .map(Some)
+                    .collect()
+            }
+            None => vec![None; arity - 1],
+        };
+
+        let mut path_elements = Vec::with_capacity(arity - 1);
+        for (k, value) in values.into_iter().enumerate() {
+            path_elements.push(num::AllocatedNum::alloc(
+                cs.namespace(|| format!("path element {}", k)),
+                || value.ok_or(SynthesisError::AssignmentMissing),
+            )?);
+        }
+
+        // Place the current subtree among the path elements: for each position
+        // bit, swap the block of nodes containing it with the block of path
+        // elements next to it if it is on the right.
+        let mut children = vec![cur];
+        let mut path_elements = path_elements.into_iter();
+
+        for (b, bit) in position_bits.iter().enumerate() {
+            let block = path_elements.by_ref().take(children.len());
+
+            let mut left = Vec::with_capacity(2 * children.len());
+            let mut right = Vec::with_capacity(children.len());
+
+            for (k, (child, path_element)) in children.iter().zip(block).enumerate() {
+                let (xl, xr) = num::AllocatedNum::conditionally_reverse(
+                    cs.namespace(|| format!("conditional reversal of preimage {} {}", b, k)),
+                    child,
+                    &path_element,
+                    bit,
+                )?;
+
+                left.push(xl);
+                right.push(xr);
+            }
+
+            left.extend(right);
+            children = left;
+        }
+
+        // We don't need to be strict, because the function is
+        // collision-resistant. If the prover witnesses a congruency,
+        // they will be unable to find an authentication path in the
+        // tree with high probability.
+        let mut preimage = vec![];
+        for (k, child) in children.iter().enumerate() {
+            preimage.extend(child.into_bits_le(cs.namespace(|| format!("child {} into bits", k)))?);
+        }
+
+        // Compute the new subtree value
+        let hash = if arity == 2 {
+            pedersen_hash::pedersen_hash(
+                cs.namespace(|| "computation of pedersen hash"),
+                pedersen_hash::Personalization::MerkleTree(i),
+                &preimage,
+                params,
+            )?
+        } else {
+            pedersen_hash_with_generators(
+                cs.namespace(|| "computation of pedersen hash"),
+                &merkle_personalization(i, arity),
+                &preimage,
+                &generators,
+                params,
+            )?
+        };
+        cur = hash.get_x().clone(); // Injective encoding
+
+        auth_path_bits.extend(position_bits);
+    }
+
+    Ok((cur, auth_path_bits))
+}
+
+// Orders the siblings of the node at index among them as ascend_auth_path
+// expects: for each bit b of the index, the block of 2^b siblings which that
+// bit places before or after the node.
+fn sibling_blocks<T: Copy>(siblings: &[T], index: usize) -> Vec<T> {
+    let arity = siblings.len() + 1;
+    let at = |k: usize| siblings[if k < index { k } else { k - 1 }];
+
+    let mut blocks = Vec::with_capacity(siblings.len());
+    let mut size = 1;
+
+    while size < arity {
+        let start = ((index / size) ^ 1) * size;
+        blocks.extend((start..start + size).map(&at));
+
+        size <<= 1;
+    }
+
+    blocks
+}
+
 impl<E: JubjubEngine, C: Circuit<E>, P: ParameterSetIdentifier, H: Hasher>
     CacheableParameters<E, C, P> for PoRCompound<H>
 {
@@ -81,7 +253,8 @@ where
         PoRCircuit::<Bls12> {
             params: engine_params,
             value: Some(proof.data.into()),
-            auth_path: proof.proof.as_options(),
+            auth_path: proof.proof.as_auth_path(),
+            arity: public_params.arity,
             root,
             private,
         }
@@ -92,7 +265,11 @@ where
         pub_params: &<MerklePoR<H> as ProofScheme<'a>>::PublicParams,
         _k: Option<usize>,
     ) -> Vec<Fr> {
-        let auth_path_bits = challenge_into_auth_path_bits(pub_inputs.challenge, pub_params.leaves);
+        let auth_path_bits = challenge_into_auth_path_bits(
+            pub_inputs.challenge,
+            pub_params.leaves,
+            pub_params.arity,
+        );
         let packed_auth_path = multipack::compute_multipacking::<Bls12>(&auth_path_bits);
 
         let mut inputs = Vec::new();
@@ -114,7 +291,8 @@ impl<'a, E: JubjubEngine> Circuit<E> for PoRCircuit<'a, E> {
     ///
     /// This circuit expects the following public inputs.
     ///
-    /// * [0] - packed version of the `is_right` components of the auth_path.
+    /// * [0] - packed version of the position bits (the `is_right` components, in a binary
+    ///         tree) of the auth_path.
     /// * [1] - the merkle root of the tree.
     ///
     /// This circuit derives the following private inputs from its fields:
@@ -135,58 +313,10 @@ impl<'a, E: JubjubEngine> Circuit<E> for PoRCircuit<'a, E> {
                 Ok(value.ok_or_else(|| SynthesisError::AssignmentMissing)?)
             })?;
 
-            let mut cur = value_num;
-
-            let mut auth_path_bits = Vec::with_capacity(auth_path.len());
-
-            // Ascend the merkle tree authentication path
-            for (i, e) in auth_path.into_iter().enumerate() {
-                let cs = &mut cs.namespace(|| format!("merkle tree hash {}", i));
+            let (cur, auth_path_bits) =
+                ascend_auth_path(&mut *cs, params, value_num, auth_path, self.arity)?;
 
-                // Determines if the current subtree is the "right" leaf at this
-                // depth of the tree.
-                let cur_is_right = boolean::Boolean::from(boolean::AllocatedBit::alloc(
-                    cs.namespace(|| "position bit"),
-                    e.map(|e| e.1),
-                )?);
-
-                // Witness the authentication path element adjacent
-                // at this depth.
-                let path_element =
-                    num::AllocatedNum::alloc(cs.namespace(|| "path element"), || {
-                        Ok(e.ok_or(SynthesisError::AssignmentMissing)?.0)
-                    })?;
-
-                // Swap the two if the current subtree is on the right
-                let (xl, xr) = num::AllocatedNum::conditionally_reverse(
-                    cs.namespace(|| "conditional reversal of preimage"),
-                    &cur,
-                    &path_element,
-                    &cur_is_right,
-                )?;
-
-                // We don't need to be strict, because the function is
-                // collision-resistant. If the prover witnesses a congruency,
-                // they will be unable to find an authentication path in the
-                // tree with high probability.
-                let mut preimage = vec![];
-                preimage.extend(xl.into_bits_le(cs.namespace(|| "xl into bits"))?);
-                preimage.extend(xr.into_bits_le(cs.namespace(|| "xr into bits"))?);
-
-                // Compute the new subtree value
-                cur = pedersen_hash::pedersen_hash(
-                    cs.namespace(|| "computation of pedersen hash"),
-                    pedersen_hash::Personalization::MerkleTree(i),
-                    &preimage,
-                    params,
-                )?
-                .get_x()
-                .clone(); // Injective encoding
-
-                auth_path_bits.push(cur_is_right);
-            }
-
-            // allocate input for is_right auth_path
+            // allocate input for the position bits of the auth_path
             multipack::pack_into_inputs(cs.namespace(|| "path"), &auth_path_bits)?;
 
             {
@@ -211,7 +341,8 @@ impl<'a, E: JubjubEngine> PoRCircuit<'a, E> {
         mut cs: CS,
         params: &E::Params,
         value: Option<E::Fr>,
-        auth_path: Vec<Option<(E::Fr, bool)>>,
+        auth_path: AuthPath<E::Fr>,
+        arity: usize,
         root: Root<E>,
         private: bool,
     ) -> Result<(), SynthesisError>
@@ -223,6 +354,7 @@ impl<'a, E: JubjubEngine> PoRCircuit<'a, E> {
             params,
             value,
             auth_path,
+            arity,
             root,
             private,
         };
@@ -245,6 +377,8 @@ mod tests {
     use crate::drgraph::{new_seed, BucketGraph, Graph};
     use crate::fr32::{bytes_into_fr, fr_into_bytes};
     use crate::hasher::pedersen::*;
+    use crate::hasher::Domain;
+    use crate::merkle::{MerkleTree, TreeStorage};
     use crate::merklepor;
     use crate::proof::ProofScheme;
     use crate::util::data_at_node;
@@ -270,6 +404,7 @@ mod tests {
                 vanilla_params: &merklepor::SetupParams {
                     leaves,
                     private: false,
+                    arity: 2,
                 },
                 engine_params: &JubjubBls12::new(),
                 partitions: None,
@@ -335,6 +470,7 @@ mod tests {
             let pub_params = merklepor::PublicParams {
                 leaves,
                 private: true,
+                arity: 2,
             };
             let pub_inputs = merklepor::PublicInputs {
                 challenge: i,
@@ -372,7 +508,8 @@ mod tests {
             let por = PoRCircuit::<Bls12> {
                 params,
                 value: Some(proof.data.into()),
-                auth_path: proof.proof.as_options(),
+                auth_path: proof.proof.as_auth_path(),
+                arity: 2,
                 root: Root::Val(Some(pub_inputs.commitment.unwrap().into())),
                 private: false,
             };
@@ -386,7 +523,7 @@ mod tests {
                 .proof
                 .path()
                 .iter()
-                .map(|(_, is_right)| *is_right)
+                .map(|(_, index)| *index == 1)
                 .collect();
             let packed_auth_path = multipack::compute_multipacking::<Bls12>(&auth_path_bits);
 
@@ -413,6 +550,106 @@ mod tests {
         }
     }
 
+    fn test_por_input_circuit_with_arity(arity: usize) {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let leaves = 13;
+
+        let data: Vec<u8> = (0..leaves)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&rng.gen()))
+            .collect();
+
+        let tree_leaves = (0..leaves).map(|i| {
+            PedersenDomain::try_from_bytes(data_at_node(data.as_slice(), i).unwrap()).unwrap()
+        });
+        let tree = MerkleTree::with_arity(TreeStorage::Memory, arity, tree_leaves).unwrap();
+
+        let pub_params = merklepor::PublicParams {
+            leaves,
+            private: false,
+            arity,
+        };
+
+        for i in 0..leaves {
+            let pub_inputs = merklepor::PublicInputs {
+                challenge: i,
+                commitment: Some(tree.root()),
+            };
+
+            let priv_inputs = merklepor::PrivateInputs::<PedersenHasher>::new(
+                bytes_into_fr::<Bls12>(data_at_node(data.as_slice(), i).unwrap())
+                    .unwrap()
+                    .into(),
+                &tree,
+            );
+
+            let proof = merklepor::MerklePoR::<PedersenHasher>::prove(
+                &pub_params,
+                &pub_inputs,
+                &priv_inputs,
+            )
+            .unwrap();
+
+            assert!(
+                merklepor::MerklePoR::<PedersenHasher>::verify(&pub_params, &pub_inputs, &proof)
+                    .unwrap(),
+                "failed to verify merklepor proof"
+            );
+
+            let expected_inputs = PoRCompound::<PedersenHasher>::generate_public_inputs(
+                &pub_inputs,
+                &pub_params,
+                None,
+            );
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+
+            let por = PoRCircuit::<Bls12> {
+                params,
+                value: Some(proof.data.into()),
+                auth_path: proof.proof.as_auth_path(),
+                arity,
+                root: Root::Val(Some(tree.root().into())),
+                private: false,
+            };
+
+            por.synthesize(&mut cs).unwrap();
+
+            assert!(cs.is_satisfied(), "constraints are not all satisfied");
+            assert!(cs.verify(&expected_inputs), "failed to verify inputs");
+
+            // The proof does not lead to any other root.
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+
+            let por = PoRCircuit::<Bls12> {
+                params,
+                value: Some(proof.data.into()),
+                auth_path: proof.proof.as_auth_path(),
+                arity,
+                root: Root::Val(Some(rng.gen())),
+                private: false,
+            };
+
+            por.synthesize(&mut cs).unwrap();
+
+            assert!(
+                !cs.is_satisfied(),
+                "constraints are satisfied for a wrong root"
+            );
+        }
+    }
+
+    #[test]
+    fn test_por_input_circuit_with_arity_4() {
+        test_por_input_circuit_with_arity(4);
+    }
+
+    #[test]
+    fn test_por_input_circuit_with_arity_8() {
+        test_por_input_circuit_with_arity(8);
+    }
+
     #[ignore] // Slow test – run only when compiled for release.
     #[test]
     fn private_por_test_compound() {
@@ -434,6 +671,7 @@ mod tests {
                 vanilla_params: &merklepor::SetupParams {
                     leaves,
                     private: true,
+                    arity: 2,
                 },
                 engine_params: &JubjubBls12::new(),
                 partitions: None,
@@ -502,6 +740,7 @@ mod tests {
             let pub_params = merklepor::PublicParams {
                 leaves,
                 private: true,
+                arity: 2,
             };
             let pub_inputs = merklepor::PublicInputs {
                 challenge: i,
@@ -539,7 +778,8 @@ mod tests {
             let por = PoRCircuit::<Bls12> {
                 params,
                 value: Some(proof.data.into()),
-                auth_path: proof.proof.as_options(),
+                auth_path: proof.proof.as_auth_path(),
+                arity: 2,
                 root: Root::Val(Some(tree.root().into())),
                 private: true,
             };
@@ -553,7 +793,7 @@ mod tests {
                 .proof
                 .path()
                 .iter()
-                .map(|(_, is_right)| *is_right)
+                .map(|(_, index)| *index == 1)
                 .collect();
             let packed_auth_path = multipack::compute_multipacking::<Bls12>(&auth_path_bits);
 
@@ -107,9 +107,7 @@ where
 
         let paths: Vec<Vec<_>> = vanilla_proof
             .paths()
-            .iter()
-            .map(|v| v.iter().map(|p| Some(((*p).0.into(), p.1))).collect())
-            .collect();
+            .expect("PoRC proves binary trees only");
 
         let challenges: Vec<_> = pub_in
             .challenges
@@ -313,15 +311,7 @@ mod tests {
 
         // actual circuit test
 
-        let paths: Vec<_> = proof
-            .paths()
-            .iter()
-            .map(|p| {
-                p.iter()
-                    .map(|v| Some((v.0.into(), v.1)))
-                    .collect::<Vec<_>>()
-            })
-            .collect();
+        let paths: Vec<_> = proof.paths().unwrap();
         let challenged_leafs: Vec<_> = proof.leafs().iter().map(|l| Some((**l).into())).collect();
         let commitments: Vec<_> = pub_inputs
             .commitments
@@ -1,8 +1,9 @@
 use bellman::{Circuit, ConstraintSystem, SynthesisError};
-use sapling_crypto::circuit::{boolean, multipack, num, pedersen_hash};
+use sapling_crypto::circuit::{multipack, num};
 use sapling_crypto::jubjub::JubjubEngine;
 
 use crate::circuit::constraint;
+use crate::circuit::por::{ascend_auth_path, AuthPath};
 /// This is an instance of the `ParallelProofOfRetrievability` circuit.
 ///
 /// # Public Inputs
@@ -11,7 +12,8 @@ use crate::circuit::constraint;
 ///
 /// * for i in 0..values.len()
 ///   * [0] - packed version of `value` as bits. (might be more than one Fr)
-///   * [1] - packed version of the `is_right` components of the auth_path.
+///   * [1] - packed version of the position bits (the `is_right` components, in a binary
+///           tree) of the auth_path.
 ///   * [2] - the merkle root of the tree.
 pub struct ParallelProofOfRetrievability<'a, E: JubjubEngine> {
     /// Paramters for the engine.
@@ -21,8 +23,10 @@ pub struct ParallelProofOfRetrievability<'a, E: JubjubEngine> {
     pub values: Vec<Option<E::Fr>>,
 
     /// The authentication path of the commitment in the tree.
-    #[allow(clippy::type_complexity)]
-    pub auth_paths: Vec<Vec<Option<(E::Fr, bool)>>>,
+    pub auth_paths: Vec<AuthPath<E::Fr>>,
+
+    /// The arity of the tree.
+    pub arity: usize,
 
     /// The root of the underyling merkle tree.
     pub root: Option<E::Fr>,
@@ -53,58 +57,10 @@ impl<'a, E: JubjubEngine> Circuit<E> for ParallelProofOfRetrievability<'a, E> {
 
             // This is an injective encoding, as cur is a
             // point in the prime order subgroup.
-            let mut cur = value_num;
-
-            let mut auth_path_bits = Vec::with_capacity(auth_path.len());
-
-            // Ascend the merkle tree authentication path
-            for (i, e) in auth_path.into_iter().enumerate() {
-                let cs = &mut cs.namespace(|| format!("merkle tree hash {}", i));
-
-                // Determines if the current subtree is the "right" leaf at this
-                // depth of the tree.
-                let cur_is_right = boolean::Boolean::from(boolean::AllocatedBit::alloc(
-                    cs.namespace(|| "position bit"),
-                    e.map(|e| e.1),
-                )?);
-
-                // Witness the authentication path element adjacent
-                // at this depth.
-                let path_element =
-                    num::AllocatedNum::alloc(cs.namespace(|| "path element"), || {
-                        Ok(e.ok_or(SynthesisError::AssignmentMissing)?.0)
-                    })?;
-
-                // Swap the two if the current subtree is on the right
-                let (xl, xr) = num::AllocatedNum::conditionally_reverse(
-                    cs.namespace(|| "conditional reversal of preimage"),
-                    &cur,
-                    &path_element,
-                    &cur_is_right,
-                )?;
-
-                // We don't need to be strict, because the function is
-                // collision-resistant. If the prover witnesses a congruency,
-                // they will be unable to find an authentication path in the
-                // tree with high probability.
-                let mut preimage = vec![];
-                preimage.extend(xl.into_bits_le(cs.namespace(|| "xl into bits"))?);
-                preimage.extend(xr.into_bits_le(cs.namespace(|| "xr into bits"))?);
-
-                // Compute the new subtree value
-                cur = pedersen_hash::pedersen_hash(
-                    cs.namespace(|| "computation of pedersen hash"),
-                    pedersen_hash::Personalization::MerkleTree(i),
-                    &preimage,
-                    params,
-                )?
-                .get_x()
-                .clone(); // Injective encoding
-
-                auth_path_bits.push(cur_is_right);
-            }
+            let (cur, auth_path_bits) =
+                ascend_auth_path(&mut cs, params, value_num, auth_path, self.arity)?;
 
-            // allocate input for is_right auth_path
+            // allocate input for the position bits of the auth_path
             multipack::pack_into_inputs(cs.namespace(|| "packed auth_path"), &auth_path_bits)?;
 
             {
@@ -127,6 +83,8 @@ mod tests {
     use crate::drgraph::{new_seed, BucketGraph, Graph};
     use crate::fr32::{bytes_into_fr, fr_into_bytes};
     use crate::hasher::pedersen::*;
+    use crate::hasher::Domain;
+    use crate::merkle::{MerkleTree, TreeStorage};
     use crate::merklepor;
     use crate::proof::ProofScheme;
     use crate::util::data_at_node;
@@ -144,6 +102,7 @@ mod tests {
         let pub_params = merklepor::PublicParams {
             leaves,
             private: false,
+            arity: 2,
         };
 
         for _ in 0..5 {
@@ -197,7 +156,7 @@ mod tests {
                 );
             }
 
-            let auth_paths: Vec<_> = proofs.iter().map(|p| p.proof.as_options()).collect();
+            let auth_paths: Vec<_> = proofs.iter().map(|p| p.proof.as_auth_path()).collect();
             let values: Vec<_> = proofs.iter().map(|p| Some(p.data.into())).collect();
 
             let mut cs = TestConstraintSystem::<Bls12>::new();
@@ -206,6 +165,7 @@ mod tests {
                 params,
                 values,
                 auth_paths,
+                arity: 2,
                 root: Some(tree.root().into()),
             };
 
@@ -220,4 +180,71 @@ mod tests {
             assert_eq!(cs.get_input(0, "ONE"), Fr::one());
         }
     }
+
+    #[test]
+    fn test_parallel_por_input_circuit_with_arity_4() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let leaves = 16;
+        let pub_params = merklepor::PublicParams {
+            leaves,
+            private: false,
+            arity: 4,
+        };
+
+        let data: Vec<u8> = (0..leaves)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&rng.gen()))
+            .collect();
+
+        let tree_leaves = (0..leaves).map(|i| {
+            PedersenDomain::try_from_bytes(data_at_node(data.as_slice(), i).unwrap()).unwrap()
+        });
+        let tree = MerkleTree::with_arity(TreeStorage::Memory, 4, tree_leaves).unwrap();
+
+        let proofs: Vec<_> = (0..leaves)
+            .map(|i| {
+                let pub_inputs = merklepor::PublicInputs {
+                    challenge: i,
+                    commitment: Some(tree.root()),
+                };
+                let priv_inputs = merklepor::PrivateInputs::<PedersenHasher>::new(
+                    bytes_into_fr::<Bls12>(data_at_node(data.as_slice(), i).unwrap())
+                        .unwrap()
+                        .into(),
+                    &tree,
+                );
+
+                merklepor::MerklePoR::<PedersenHasher>::prove(
+                    &pub_params,
+                    &pub_inputs,
+                    &priv_inputs,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let auth_paths: Vec<_> = proofs.iter().map(|p| p.proof.as_auth_path()).collect();
+        let values: Vec<_> = proofs.iter().map(|p| Some(p.data.into())).collect();
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+
+        let instance = ParallelProofOfRetrievability {
+            params,
+            values,
+            auth_paths,
+            arity: 4,
+            root: Some(tree.root().into()),
+        };
+
+        instance
+            .synthesize(&mut cs)
+            .expect("failed to synthesize circuit");
+
+        assert!(cs.is_satisfied(), "constraints not satisfied");
+
+        // Each path holds the same number of position bits as in a binary tree.
+        assert_eq!(cs.num_inputs(), 34, "wrong number of inputs");
+        assert_eq!(cs.get_input(0, "ONE"), Fr::one());
+    }
 }
@@ -100,17 +100,7 @@ where
 
         for porep_proof in &vanilla_proof.porep_proofs {
             // -- paths
-            paths_vec.push(
-                porep_proof
-                    .paths()
-                    .iter()
-                    .map(|p| {
-                        p.iter()
-                            .map(|v| Some((v.0.into(), v.1)))
-                            .collect::<Vec<_>>()
-                    })
-                    .collect::<Vec<_>>(),
-            );
+            paths_vec.push(porep_proof.paths().expect("PoRC proves binary trees only"));
 
             // -- challenged leafs
             challenged_leafs_vec.push(
@@ -474,17 +464,7 @@ mod tests {
 
         for porep_proof in &proof.porep_proofs {
             // -- paths
-            paths_vec.push(
-                porep_proof
-                    .paths()
-                    .iter()
-                    .map(|p| {
-                        p.iter()
-                            .map(|v| Some((v.0.into(), v.1)))
-                            .collect::<Vec<_>>()
-                    })
-                    .collect::<Vec<_>>(),
-            );
+            paths_vec.push(porep_proof.paths().unwrap());
 
             // -- challenged leafs
             challenged_leafs_vec.push(
@@ -604,5 +584,4 @@ mod tests {
 
         assert!(verified);
     }
-
 }
@@ -1,16 +1,28 @@
+use byteorder::{LittleEndian, WriteBytesExt};
 use pairing::bls12_381::{Bls12, Fr, FrRepr};
-use pairing::PrimeFieldRepr;
-use sapling_crypto::jubjub::JubjubBls12;
+use pairing::{Field, PrimeField, PrimeFieldRepr};
+use sapling_crypto::constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION;
+use sapling_crypto::group_hash::group_hash;
+use sapling_crypto::jubjub::{
+    edwards, montgomery, JubjubBls12, JubjubEngine, JubjubParams, PrimeOrder,
+};
 use sapling_crypto::pedersen_hash::{pedersen_hash, Personalization};
 
 use crate::fr32::bytes_into_frs;
+use crate::merkle::MAX_ARITY;
 
 use bitvec::{self, BitVec};
 
 lazy_static! {
     pub static ref JJ_PARAMS: JubjubBls12 = JubjubBls12::new();
+    static ref MERKLE_GENERATORS: Vec<edwards::Point<Bls12, PrimeOrder>> =
+        merkle_generators::<Bls12>(&JJ_PARAMS);
 }
 
+/// The number of bits of the arity of a node of a merkle tree which follow
+/// those of `Personalization::MerkleTree` in the personalization of its hash.
+pub const MERKLE_ARITY_BITS: usize = 8;
+
 pub const PEDERSEN_BLOCK_SIZE: usize = 256;
 pub const PEDERSEN_BLOCK_BYTES: usize = PEDERSEN_BLOCK_SIZE / 8;
 
@@ -55,6 +67,173 @@ pub fn pedersen_md_no_padding(data: &[u8]) -> Fr {
     frs[0]
 }
 
+/// Returns the bits personalizing the hash of a node, at the given height, of
+/// a merkle tree of the given arity: those of `Personalization::MerkleTree`,
+/// followed by the `MERKLE_ARITY_BITS` bits of the arity.
+pub fn merkle_personalization(height: usize, arity: usize) -> Vec<bool> {
+    let mut bits = Personalization::MerkleTree(height).get_bits();
+    bits.extend((0..MERKLE_ARITY_BITS).map(|i| (arity >> i) & 1 == 1));
+
+    bits
+}
+
+/// Hashes all the children of a node, at the given height, of a merkle tree
+/// of any arity up to `MAX_ARITY`, given the bits of each of them one after
+/// the other, with a single Pedersen hash personalized by
+/// `merkle_personalization`.
+pub fn pedersen_merkle_node<I: IntoIterator<Item = bool>>(
+    height: usize,
+    arity: usize,
+    bits: I,
+) -> Fr {
+    pedersen_hash_with_generators::<Bls12, _>(
+        &merkle_personalization(height, arity),
+        bits,
+        &MERKLE_GENERATORS,
+        &JJ_PARAMS,
+    )
+    .into_xy()
+    .0
+}
+
+/// Returns enough generators to hash the children of a node of a merkle tree
+/// of any arity up to `MAX_ARITY` with `pedersen_hash_with_generators`: those
+/// of sapling's Pedersen hash, followed by further ones derived in the same
+/// way. Inputs short enough for sapling's generators thus hash as they do
+/// with sapling's Pedersen hash.
+pub fn merkle_generators<E: JubjubEngine>(
+    params: &E::Params,
+) -> Vec<edwards::Point<E, PrimeOrder>> {
+    let bits = merkle_personalization(0, MAX_ARITY).len()
+        + MAX_ARITY * <E::Fr as PrimeField>::NUM_BITS as usize;
+    let bits_per_generator = 3 * params.pedersen_hash_chunks_per_generator();
+    let count = (bits + bits_per_generator - 1) / bits_per_generator;
+
+    let mut generators = params.pedersen_hash_generators().to_vec();
+
+    for m in generators.len() as u32..count as u32 {
+        let mut tag = Vec::with_capacity(5);
+        tag.write_u32::<LittleEndian>(m)
+            .expect("failed to write segment number");
+        tag.push(0);
+
+        // Not every tag hashes to a point of the group: try the next one.
+        let generator = loop {
+            if let Some(generator) =
+                group_hash::<E>(&tag, PEDERSEN_HASH_GENERATORS_PERSONALIZATION, params)
+            {
+                break generator;
+            }
+            tag[4] += 1;
+        };
+
+        assert!(
+            !generators.contains(&generator),
+            "Pedersen hash generators must be distinct"
+        );
+        generators.push(generator);
+    }
+
+    generators
+}
+
+/// Returns the tables the `circuit::pedersen::pedersen_hash_with_generators`
+/// gadget looks the multiples of the given generators up in.
+pub fn circuit_generators<E: JubjubEngine>(
+    generators: &[edwards::Point<E, PrimeOrder>],
+    params: &E::Params,
+) -> Vec<Vec<Vec<(E::Fr, E::Fr)>>> {
+    generators
+        .iter()
+        .map(|generator| {
+            let mut generator = montgomery::Point::from_edwards(generator, params);
+
+            (0..params.pedersen_hash_chunks_per_generator())
+                .map(|_| {
+                    let mut coeffs = Vec::with_capacity(4);
+                    let mut g = generator.clone();
+                    for _ in 0..4 {
+                        coeffs.push(g.into_xy().expect("cannot produce O"));
+                        g = g.add(&generator, params);
+                    }
+
+                    for _ in 0..4 {
+                        generator = generator.double(params);
+                    }
+
+                    coeffs
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Hashes the given bits, after those of `personalization`, as sapling's
+/// Pedersen hash does, but over the given generators, one of which is used for
+/// each `pedersen_hash_chunks_per_generator` chunks of 3 bits.
+pub fn pedersen_hash_with_generators<E, I>(
+    personalization: &[bool],
+    bits: I,
+    generators: &[edwards::Point<E, PrimeOrder>],
+    params: &E::Params,
+) -> edwards::Point<E, PrimeOrder>
+where
+    E: JubjubEngine,
+    I: IntoIterator<Item = bool>,
+{
+    let mut bits = personalization.iter().cloned().chain(bits);
+    let mut generators = generators.iter();
+    let mut result = edwards::Point::zero();
+
+    loop {
+        let mut acc = E::Fs::zero();
+        let mut cur = E::Fs::one();
+        let mut chunks_remaining = params.pedersen_hash_chunks_per_generator();
+        let mut encountered_bits = false;
+
+        // Each chunk of 3 bits is encoded as (1 + a + 2b) * (-1)^c * 2^(4i).
+        while let Some(a) = bits.next() {
+            encountered_bits = true;
+
+            let b = bits.next().unwrap_or(false);
+            let c = bits.next().unwrap_or(false);
+
+            let mut tmp = cur;
+            if a {
+                tmp.add_assign(&cur);
+            }
+            cur.double();
+            if b {
+                tmp.add_assign(&cur);
+            }
+            if c {
+                tmp.negate();
+            }
+            acc.add_assign(&tmp);
+
+            chunks_remaining -= 1;
+            if chunks_remaining == 0 {
+                break;
+            }
+
+            cur.double();
+            cur.double();
+            cur.double();
+        }
+
+        if !encountered_bits {
+            break;
+        }
+
+        let generator = generators
+            .next()
+            .expect("not enough Pedersen hash generators");
+        result = result.add(&generator.mul(acc.into_repr(), params), params);
+    }
+
+    result
+}
+
 pub fn pedersen_compression(bytes: &mut Vec<u8>) {
     let bits = BitVec::<bitvec::LittleEndian, u8>::from(&bytes[..]);
     let (x, _) = pedersen_hash::<Bls12, _>(
@@ -100,6 +279,42 @@ mod tests {
         assert_eq!(expected, data);
     }
 
+    #[test]
+    fn test_pedersen_hash_with_generators() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let generators = merkle_generators::<Bls12>(&JJ_PARAMS);
+
+        // Inputs which sapling's generators suffice for hash as with them.
+        let bits: Vec<bool> = (0..510).map(|_| rng.gen()).collect();
+        assert_eq!(
+            pedersen_hash_with_generators::<Bls12, _>(
+                &Personalization::MerkleTree(3).get_bits(),
+                bits.iter().cloned(),
+                &generators,
+                &JJ_PARAMS,
+            )
+            .into_xy(),
+            pedersen_hash::<Bls12, _>(
+                Personalization::MerkleTree(3),
+                bits.iter().cloned(),
+                &JJ_PARAMS
+            )
+            .into_xy(),
+        );
+
+        // The children of nodes of the greatest arity all fit in a single hash,
+        // which depends on the arity.
+        let bits: Vec<bool> = (0..MAX_ARITY * Fr::NUM_BITS as usize)
+            .map(|_| rng.gen())
+            .collect();
+        let hashed = pedersen_merkle_node(0, MAX_ARITY, bits.iter().cloned());
+        assert_ne!(hashed, Fr::zero());
+        assert_ne!(
+            hashed,
+            pedersen_merkle_node(0, MAX_ARITY / 2, bits.iter().cloned())
+        );
+    }
+
     #[test]
     fn test_pedersen_md_no_padding() {
         let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
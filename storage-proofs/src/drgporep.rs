@@ -5,9 +5,9 @@ use serde::de::Deserialize;
 use serde::ser::Serialize;
 
 use crate::drgraph::Graph;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::hasher::{Domain, Hasher};
-use crate::merkle::{MerkleProof, MerkleTree};
+use crate::merkle::{MerkleProof, MerkleTree, DEFAULT_ARITY};
 use crate::parameter_cache::ParameterSetIdentifier;
 use crate::porep::{self, PoRep};
 use crate::proof::ProofScheme;
@@ -20,9 +20,21 @@ pub struct PublicInputs<T: Domain> {
     pub tau: Option<porep::Tau<T>>,
 }
 
+// The trees are borrowed, so that provers holding them elsewhere (e.g. the
+// layered prover) need not copy them.
 #[derive(Debug)]
 pub struct PrivateInputs<'a, H: 'a + Hasher> {
-    pub aux: &'a porep::ProverAux<H>,
+    pub tree_d: &'a MerkleTree<H::Domain, H::Function>,
+    pub tree_r: &'a MerkleTree<H::Domain, H::Function>,
+}
+
+impl<'a, H: Hasher> PrivateInputs<'a, H> {
+    pub fn new(aux: &'a porep::ProverAux<H>) -> Self {
+        PrivateInputs {
+            tree_d: &aux.tree_d,
+            tree_r: &aux.tree_r,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -115,12 +127,13 @@ impl<H: Hasher> DataProof<H> {
     /// proves_challenge returns true if this self.proof corresponds to challenge.
     /// This is useful for verifying that a supplied proof is actually relevant to a given challenge.
     pub fn proves_challenge(&self, challenge: usize) -> bool {
+        let arity = DEFAULT_ARITY;
         let mut c = challenge;
-        for (_, is_right) in self.proof.path().iter() {
-            if ((c & 1) == 1) ^ is_right {
+        for (_, index) in self.proof.path().iter() {
+            if c % arity != *index {
                 return false;
             };
-            c >>= 1;
+            c /= arity;
         }
         true
     }
@@ -264,18 +277,23 @@ where
         let mut replica_parents = Vec::with_capacity(len);
         let mut data_nodes: Vec<DataProof<H>> = Vec::with_capacity(len);
 
+        for tree in &[priv_inputs.tree_d, priv_inputs.tree_r] {
+            if tree.arity() != DEFAULT_ARITY {
+                return Err(Error::InvalidArity(tree.arity()));
+            }
+        }
+
         for i in 0..len {
             let challenge = pub_inputs.challenges[i] % pub_params.graph.size();
             assert_ne!(challenge, 0, "cannot prove the first node");
 
-            let tree_d = &priv_inputs.aux.tree_d;
-            let tree_r = &priv_inputs.aux.tree_r;
-            let domain_replica = tree_r.as_slice();
+            let tree_d = priv_inputs.tree_d;
+            let tree_r = priv_inputs.tree_r;
 
-            let data = domain_replica[challenge];
+            let data = tree_r.read_at(challenge);
 
             replica_nodes.push(DataProof {
-                proof: MerkleProof::new_from_proof(&tree_r.gen_proof(challenge)),
+                proof: MerkleProof::from_tree(tree_r, challenge),
                 data,
             });
 
@@ -284,17 +302,16 @@ where
 
             for p in parents {
                 replica_parentsi.push((p, {
-                    let proof = tree_r.gen_proof(p);
                     DataProof {
-                        proof: MerkleProof::new_from_proof(&proof),
-                        data: domain_replica[p],
+                        proof: MerkleProof::from_tree(tree_r, p),
+                        data: tree_r.read_at(p),
                     }
                 }));
             }
 
             replica_parents.push(replica_parentsi);
 
-            let node_proof = tree_d.gen_proof(challenge);
+            let node_proof = MerkleProof::from_tree(tree_d, challenge);
 
             {
                 // TODO: use this again, I can't make lifetimes work though atm and I do not know why
@@ -315,7 +332,7 @@ where
                 .into_bytes();
                 data_nodes.push(DataProof {
                     data: H::Domain::try_from_bytes(&extracted)?,
-                    proof: MerkleProof::new_from_proof(&node_proof),
+                    proof: node_proof,
                 });
             }
         }
@@ -369,13 +386,16 @@ where
             let challenge = pub_inputs.challenges[i] % pub_params.graph.size();
             assert_ne!(challenge, 0, "cannot prove the first node");
 
-            if !proof.replica_nodes[i].proof.validate(challenge) {
+            if !proof.replica_nodes[i]
+                .proof
+                .validate(challenge, DEFAULT_ARITY)
+            {
                 println!("invalid replica node");
                 return Ok(false);
             }
 
             for (parent_node, p) in &proof.replica_parents[i] {
-                if !p.proof.validate(*parent_node) {
+                if !p.proof.validate(*parent_node, DEFAULT_ARITY) {
                     println!("invalid replica parent: {:?}", p);
                     return Ok(false);
                 }
@@ -639,7 +659,7 @@ mod tests {
                 tau: Some(tau.clone().into()),
             };
 
-            let priv_inputs = PrivateInputs::<H> { aux: &aux };
+            let priv_inputs = PrivateInputs::<H>::new(&aux);
 
             let real_proof = DrgPoRep::<H, _>::prove(&pp, &pub_inputs, &priv_inputs).unwrap();
 
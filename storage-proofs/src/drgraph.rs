@@ -2,12 +2,11 @@ use std::cmp;
 use std::marker::PhantomData;
 
 use rand::{ChaChaRng, OsRng, Rng, SeedableRng};
-use rayon::prelude::*;
 
 use crate::error::*;
 use crate::hasher::pedersen::PedersenHasher;
 use crate::hasher::{Domain, Hasher};
-use crate::merkle::MerkleTree;
+use crate::merkle::{MerkleTree, TreeStorage};
 use crate::parameter_cache::ParameterSetIdentifier;
 use crate::util::data_at_node;
 /// The default hasher currently in use.
@@ -24,15 +23,17 @@ pub trait Graph<H: Hasher>: ::std::fmt::Debug + Clone + PartialEq + Eq {
 
     /// Builds a merkle tree based on the given data.
     fn merkle_tree<'a>(&self, data: &'a [u8]) -> Result<MerkleTree<H::Domain, H::Function>> {
-        self.merkle_tree_aux(data, 32, PARALLEL_MERKLE)
+        self.merkle_tree_aux(data, 32, PARALLEL_MERKLE, TreeStorage::Memory)
     }
 
-    /// Builds a merkle tree based on the given data.
+    /// Builds a merkle tree based on the given data, whose nodes are kept in
+    /// the provided storage.
     fn merkle_tree_aux<'a>(
         &self,
         data: &'a [u8],
         node_size: usize,
         parallel: bool,
+        storage: TreeStorage,
     ) -> Result<MerkleTree<H::Domain, H::Function>> {
         if data.len() != (node_size * self.size()) as usize {
             return Err(Error::InvalidMerkleTreeArgs(
@@ -59,11 +60,9 @@ pub trait Graph<H: Hasher>: ::std::fmt::Debug + Clone + PartialEq + Eq {
         };
 
         if parallel {
-            Ok(MerkleTree::from_par_iter(
-                (0..self.size()).into_par_iter().map(f),
-            ))
+            MerkleTree::from_par_leaves(storage, self.size(), f)
         } else {
-            Ok(MerkleTree::new((0..self.size()).map(f)))
+            MerkleTree::new_in(storage, (0..self.size()).map(f))
         }
     }
 
@@ -257,10 +256,24 @@ mod tests {
         let data = vec![2u8; node_size * 5];
 
         let mmapped = &mmap_from(&data);
-        let tree = g.merkle_tree_aux(mmapped, node_size, parallel).unwrap();
-        let proof = tree.gen_proof(2);
+        let tree = g
+            .merkle_tree_aux(mmapped, node_size, parallel, TreeStorage::Memory)
+            .unwrap();
+        let proof = tree.gen_proof(2).unwrap();
 
         assert!(proof.validate::<H::Function>());
+
+        let mmap_tree = g
+            .merkle_tree_aux(
+                mmapped,
+                node_size,
+                parallel,
+                TreeStorage::Mmap(std::env::temp_dir()),
+            )
+            .unwrap();
+
+        assert_eq!(mmap_tree.root(), tree.root());
+        assert_eq!(mmap_tree.gen_proof(2).unwrap().lemma(), proof.lemma());
     }
 
     #[test]
@@ -16,6 +16,8 @@ pub enum Error {
     InvalidMerkleTreeArgs(usize, usize, usize),
     #[fail(display = "invalid node size ({}), must be 16, 32 or 64", _0)]
     InvalidNodeSize(usize),
+    #[fail(display = "invalid merkle tree arity ({}), must be a power of two", _0)]
+    InvalidArity(usize),
     #[fail(display = "{}", _0)]
     Synthesis(#[cause] SynthesisError),
     #[fail(display = "{}", _0)]
@@ -168,6 +168,22 @@ impl<D: Digester> HashFunction<DigestDomain> for DigestFunction<D> {
         res.trim_to_fr32();
         res
     }
+
+    fn hash_children(children: &[DigestDomain], height: usize) -> DigestDomain {
+        let mut a = Self::default();
+
+        if children.len() == 2 {
+            return a.node(children[0], children[1], height);
+        }
+
+        height.hash(&mut a);
+        children.len().hash(&mut a);
+
+        for child in children {
+            child.hash(&mut a);
+        }
+        a.hash()
+    }
 }
 
 impl<D: Digester> Algorithm<DigestDomain> for DigestFunction<D> {
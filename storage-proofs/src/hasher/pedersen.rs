@@ -207,6 +207,20 @@ impl HashFunction<PedersenDomain> for PedersenFunction {
     fn hash(data: &[u8]) -> PedersenDomain {
         pedersen::pedersen_md_no_padding(data).into()
     }
+
+    fn hash_children(children: &[PedersenDomain], height: usize) -> PedersenDomain {
+        if children.len() == 2 {
+            return Self::default().node(children[0], children[1], height);
+        }
+
+        let mut bits = Vec::with_capacity(children.len() * Fr::NUM_BITS as usize);
+        for child in children {
+            let child_bits = BitVec::<bitvec::LittleEndian, u64>::from(&(child.0).0[..]);
+            bits.extend(child_bits.iter().take(Fr::NUM_BITS as usize));
+        }
+
+        pedersen::pedersen_merkle_node(height, children.len(), bits).into()
+    }
 }
 
 impl LightAlgorithm<PedersenDomain> for PedersenFunction {
@@ -277,7 +291,7 @@ mod tests {
         let values = ["hello", "world", "you", "two"];
         let t = MerkleTree::<PedersenDomain, PedersenFunction>::from_data(values.iter());
 
-        let p = t.gen_proof(0); // create a proof for the first value = "hello"
+        let p = t.gen_proof(0).unwrap(); // create a proof for the first value = "hello"
         assert_eq!(*p.path(), vec![true, true]);
         assert_eq!(p.validate::<PedersenFunction>(), true);
     }
@@ -301,24 +315,24 @@ mod tests {
             })
             .collect();
 
-        assert_eq!(t[0], leaves[0]);
-        assert_eq!(t[1], leaves[1]);
-        assert_eq!(t[2], leaves[2]);
-        assert_eq!(t[3], leaves[3]);
+        assert_eq!(t.read_at(0), leaves[0]);
+        assert_eq!(t.read_at(1), leaves[1]);
+        assert_eq!(t.read_at(2), leaves[2]);
+        assert_eq!(t.read_at(3), leaves[3]);
 
         let i1 = a.node(leaves[0], leaves[1], 0);
         a.reset();
         let i2 = a.node(leaves[2], leaves[3], 0);
         a.reset();
 
-        assert_eq!(t[4], i1);
-        assert_eq!(t[5], i2);
+        assert_eq!(t.read_at(4), i1);
+        assert_eq!(t.read_at(5), i2);
 
         let root = a.node(i1, i2, 1);
         a.reset();
 
         assert_eq!(
-            t[0].0,
+            t.read_at(0).0,
             FrRepr([
                 5516429847681692214,
                 1363403528947283679,
@@ -333,11 +347,11 @@ mod tests {
             16116531553419129213,
             6357427774790868134,
         ]);
-        let actual = t[6].0;
+        let actual = t.read_at(6).0;
 
         assert_eq!(actual, expected);
 
-        assert_eq!(t[6], root);
+        assert_eq!(t.read_at(6), root);
     }
 
     #[test]
@@ -20,6 +20,7 @@ pub trait Domain:
     + Rand
     + Serialize
     + DeserializeOwned
+    + 'static
 {
     fn serialize(&self) -> Vec<u8>;
     fn into_bytes(&self) -> Vec<u8>;
@@ -45,6 +46,12 @@ pub trait HashFunction<T: Domain>:
         data.hash(&mut a);
         a.hash()
     }
+
+    /// Hashes all the children of a node, at the given height, of a merkle
+    /// tree of which they give the arity. Two children hash as with
+    /// `node`, so that binary trees are those of `merkle_light`; more are
+    /// hashed at once, together with their number.
+    fn hash_children(children: &[T], height: usize) -> T;
 }
 
 pub trait Hasher: Clone + ::std::fmt::Debug + Eq + Default + Send + Sync {
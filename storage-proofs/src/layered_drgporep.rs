@@ -9,10 +9,10 @@ use slog::*;
 
 use crate::challenge_derivation::derive_challenges;
 use crate::drgporep::{self, DrgPoRep};
-use crate::drgraph::Graph;
+use crate::drgraph::{Graph, PARALLEL_MERKLE};
 use crate::error::{Error, Result};
 use crate::hasher::{Domain, HashFunction, Hasher};
-use crate::merkle::MerkleTree;
+use crate::merkle::{MerkleTree, TreeStorage};
 use crate::parameter_cache::ParameterSetIdentifier;
 use crate::porep::{self, PoRep};
 use crate::proof::ProofScheme;
@@ -259,10 +259,8 @@ pub trait Layers {
                 let inner_layers = layers - layer;
 
                 let new_priv_inputs = drgporep::PrivateInputs {
-                    aux: &porep::ProverAux {
-                        tree_d: aux[layer].clone(),
-                        tree_r: aux[layer + 1].clone(),
-                    },
+                    tree_d: &aux[layer],
+                    tree_r: &aux[layer + 1],
                 };
                 let layer_diff = total_layers - inner_layers;
 
@@ -312,11 +310,41 @@ pub trait Layers {
         Ok(())
     }
 
+    /// Replicate `data` like `PoRep::replicate`, keeping the trees of all layers in `storage`.
+    /// The trees are kept until proving finishes, so callers replicating large sectors should
+    /// memory-map them next to the replica rather than holding them in memory.
+    fn replicate_with_storage(
+        pp: &PublicParams<Self::Hasher, Self::Graph>,
+        replica_id: &<Self::Hasher as Hasher>::Domain,
+        data: &mut [u8],
+        storage: &TreeStorage,
+    ) -> Result<(
+        Tau<<Self::Hasher as Hasher>::Domain>,
+        Vec<Tree<Self::Hasher>>,
+    )> {
+        let (taus, auxs) = Self::transform_and_replicate_layers(
+            &pp.drg_porep_public_params,
+            pp.layer_challenges.layers(),
+            replica_id,
+            data,
+            storage,
+        )?;
+
+        let comm_rs: Vec<_> = taus.iter().map(|tau| tau.comm_r).collect();
+        let crs = comm_r_star::<Self::Hasher>(replica_id, &comm_rs)?;
+        let tau = Tau {
+            layer_taus: taus,
+            comm_r_star: crs,
+        };
+        Ok((tau, auxs))
+    }
+
     fn transform_and_replicate_layers(
         drgpp: &drgporep::PublicParams<Self::Hasher, Self::Graph>,
         layers: usize,
         replica_id: &<Self::Hasher as Hasher>::Domain,
         data: &mut [u8],
+        storage: &TreeStorage,
     ) -> Result<TransformedLayers<Self::Hasher>> {
         assert!(layers > 0);
         let mut taus = Vec::with_capacity(layers);
@@ -331,10 +359,9 @@ pub trait Layers {
             // It would not be a bad idea to add tests ensuring the parallel and serial cases
             // generate the same results.
             (0..layers).fold((*drgpp).clone(), |current_drgpp, layer| {
-                let previous_replica_tree = if !auxs.is_empty() {
-                    auxs.last().cloned()
-                } else {
-                    None
+                let previous_replica_tree = match auxs.last() {
+                    Some(tree) => Some(tree.try_clone().unwrap()),
+                    None => None,
                 };
 
                 let (tau, aux) =
@@ -375,6 +402,7 @@ pub trait Layers {
                         data_copy[0..data.len()].clone_from_slice(data);
 
                         let return_channel = tx.clone();
+                        let storage = storage.clone();
                         let (transfer_tx, transfer_rx) =
                             channel::<drgporep::PublicParams<Self::Hasher, Self::Graph>>();
 
@@ -384,7 +412,10 @@ pub trait Layers {
                             // If we panic anywhere in this closure, thread.join() below will receive an error —
                             // so it is safe to unwrap.
                             let drgpp = transfer_rx.recv().unwrap();
-                            let tree_d = drgpp.graph.merkle_tree(&data_copy).unwrap();
+                            let tree_d = drgpp
+                                .graph
+                                .merkle_tree_aux(&data_copy, 32, PARALLEL_MERKLE, storage)
+                                .unwrap();
 
                             info!(SP_LOG, "returning tree"; "layer" => format!("{}", layer));
                             return_channel.send((layer, tree_d)).unwrap();
@@ -422,22 +453,23 @@ pub trait Layers {
                 labeled_trees
             };
 
-            sorted_trees.iter().fold(
+            sorted_trees.into_iter().fold(
                 None,
-                |previous_tree: Option<&MerkleTree<_, _>>, (i, replica_tree)| {
+                |previous_root: Option<<Self::Hasher as Hasher>::Domain>, (i, replica_tree)| {
                     // Each iteration's replica_tree becomes the next iteration's previous_tree (data_tree).
                     // The first iteration has no previous_tree.
-                    if let Some(data_tree) = previous_tree {
+                    let replica_root = replica_tree.root();
+                    if let Some(data_root) = previous_root {
                         let tau = porep::Tau {
-                            comm_r: replica_tree.root(),
-                            comm_d: data_tree.root(),
+                            comm_r: replica_root,
+                            comm_d: data_root,
                         };
                         info!(SP_LOG, "setting tau/aux"; "layer" => format!("{}", i - 1));
                         taus.push(tau);
                     };
-                    auxs.push(replica_tree.clone());
+                    auxs.push(replica_tree);
 
-                    Some(replica_tree)
+                    Some(replica_root)
                 },
             );
         };
@@ -605,20 +637,12 @@ impl<'a, 'c, L: Layers> PoRep<'a, L::Hasher> for L {
         data: &mut [u8],
         _data_tree: Option<Tree<L::Hasher>>,
     ) -> Result<(Self::Tau, Self::ProverAux)> {
-        let (taus, auxs) = Self::transform_and_replicate_layers(
-            &pp.drg_porep_public_params,
-            pp.layer_challenges.layers(),
+        Self::replicate_with_storage(
+            pp,
             replica_id,
             data,
-        )?;
-
-        let comm_rs: Vec<_> = taus.iter().map(|tau| tau.comm_r).collect();
-        let crs = comm_r_star::<L::Hasher>(replica_id, &comm_rs)?;
-        let tau = Tau {
-            layer_taus: taus,
-            comm_r_star: crs,
-        };
-        Ok((tau, auxs))
+            &TreeStorage::Mmap(std::env::temp_dir()),
+        )
     }
 
     fn extract_all<'b>(
@@ -677,5 +701,4 @@ mod tests {
             assert_eq!(*expected_count as usize, calculated_count);
         }
     }
-
 }
@@ -1,22 +1,774 @@
 #![allow(clippy::len_without_is_empty)]
 
+use std::cmp;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-// Reexport here, so we don't depend on merkle_light directly in other places.
-use merkle_light::hash::Algorithm;
-pub use merkle_light::merkle::MerkleTree;
+use memmap::{Mmap, MmapMut, MmapOptions};
+use merkle_light::hash::{Algorithm, Hashable};
 use merkle_light::proof;
 use pairing::bls12_381::Fr;
+use rayon::prelude::*;
 
-use crate::hasher::{Domain, Hasher};
+use crate::error::{Error, Result};
+use crate::hasher::{Domain, HashFunction, Hasher};
+
+/// The number of bytes a node occupies in a `MmapStore`.
+const NODE_SIZE: usize = 32;
+
+/// Leaves computed in parallel are stored this many at a time, which bounds
+/// the memory they occupy before they reach a tree's store.
+const LEAF_CHUNK_SIZE: usize = 1 << 16;
+
+/// The arity of trees built without one: they are binary.
+pub const DEFAULT_ARITY: usize = 2;
+
+/// The greatest arity of a tree: the children of each node are hashed at once,
+/// and Pedersen hashes have generators for at most that many.
+pub const MAX_ARITY: usize = 16;
+
+/// Where the nodes of a merkle tree are kept.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TreeStorage {
+    /// In memory.
+    Memory,
+    /// In a memory-mapped temporary file in the given directory, which is
+    /// removed when the tree is dropped. The kernel may then page the tree out
+    /// to the file instead of keeping all of it resident, so the directory
+    /// should not be on a memory-backed filesystem (e.g. tmpfs).
+    Mmap(PathBuf),
+}
+
+impl TreeStorage {
+    /// Returns an empty store of this kind with room for `capacity` nodes.
+    pub fn new_store<T: Domain>(&self, capacity: usize) -> Result<Box<Store<T>>> {
+        match self {
+            TreeStorage::Memory => Ok(Box::new(VecStore::new(capacity))),
+            TreeStorage::Mmap(dir) => Ok(Box::new(MmapStore::new(dir, capacity)?)),
+        }
+    }
+}
+
+/// Storage for the nodes of a merkle tree, to which the leaves and then each
+/// level of the tree up to the root are appended.
+pub trait Store<T>: Send + Sync {
+    /// Returns the number of nodes held.
+    fn len(&self) -> usize;
+
+    /// Returns the `i`th node. Panics if there is no such node.
+    fn read_at(&self, i: usize) -> T;
+
+    /// Appends a node. Panics if the store is full or read-only.
+    fn push(&mut self, node: T);
+
+    /// Returns a store of the same kind holding the same nodes.
+    fn try_clone(&self) -> Result<Box<Store<T>>>;
+}
+
+/// Keeps nodes in memory.
+#[derive(Clone, Debug)]
+pub struct VecStore<T>(Vec<T>);
+
+impl<T> VecStore<T> {
+    pub fn new(capacity: usize) -> VecStore<T> {
+        VecStore(Vec::with_capacity(capacity))
+    }
+}
+
+impl<T: Domain> Store<T> for VecStore<T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn read_at(&self, i: usize) -> T {
+        self.0[i]
+    }
+
+    fn push(&mut self, node: T) {
+        self.0.push(node);
+    }
+
+    fn try_clone(&self) -> Result<Box<Store<T>>> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+/// Keeps nodes, serialized with `Domain::write_bytes`, in a memory-mapped
+/// temporary file.
+pub struct MmapStore<T> {
+    map: MmapMut,
+    dir: PathBuf,
+    capacity: usize,
+    len: usize,
+    _t: PhantomData<T>,
+}
+
+impl<T: Domain> MmapStore<T> {
+    /// Returns an empty store with room for `capacity` nodes, kept in a
+    /// temporary file in `dir`.
+    pub fn new<P: AsRef<Path>>(dir: P, capacity: usize) -> Result<MmapStore<T>> {
+        let file = tempfile::tempfile_in(&dir)?;
+        file.set_len((capacity * NODE_SIZE) as u64)?;
+
+        // The mapping outlives the (already unlinked) file's descriptor.
+        let map = unsafe { MmapOptions::new().map_mut(&file)? };
+
+        Ok(MmapStore {
+            map,
+            dir: dir.as_ref().to_path_buf(),
+            capacity,
+            len: 0,
+            _t: PhantomData,
+        })
+    }
+}
+
+impl<T: Domain> Store<T> for MmapStore<T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn read_at(&self, i: usize) -> T {
+        assert!(i < self.len, "node {} is out of bounds ({})", i, self.len);
+
+        T::try_from_bytes(&self.map[i * NODE_SIZE..(i + 1) * NODE_SIZE])
+            .expect("stored node is invalid")
+    }
+
+    fn push(&mut self, node: T) {
+        assert!(self.len < self.capacity, "store is full");
+
+        node.write_bytes(&mut self.map[self.len * NODE_SIZE..(self.len + 1) * NODE_SIZE])
+            .expect("failed to store node");
+        self.len += 1;
+    }
+
+    fn try_clone(&self) -> Result<Box<Store<T>>> {
+        let mut store = MmapStore::new(&self.dir, self.capacity)?;
+
+        let num_bytes = self.len * NODE_SIZE;
+        store.map[..num_bytes].copy_from_slice(&self.map[..num_bytes]);
+        store.len = self.len;
+
+        Ok(Box::new(store))
+    }
+}
+
+/// Serves the upper levels of a tree from a file written by
+/// `MerkleTree::write_upper_levels` and recomputes the nodes of the levels
+/// below them, on demand, from the data the tree commits to. Read-only.
+pub struct PartialStore<T, A> {
+    data: Arc<Mmap>,
+    upper: Arc<Mmap>,
+    layout: Layout,
+    omitted_levels: usize,
+    _t: PhantomData<T>,
+    _a: PhantomData<A>,
+}
+
+impl<T: Domain, A: HashFunction<T>> PartialStore<T, A> {
+    // Returns the `j`th node of the given level, where level 0 holds the leaves.
+    fn node(&self, level: usize, j: usize) -> T {
+        // The node padding a level repeats the last node of that level.
+        let j = cmp::min(j, self.layout.widths[level] - 1);
+
+        if level >= self.omitted_levels {
+            let i = self.layout.offsets[level] + j - self.layout.offsets[self.omitted_levels];
+
+            return T::try_from_bytes(&self.upper[i * NODE_SIZE..(i + 1) * NODE_SIZE])
+                .expect("stored node is invalid");
+        }
+
+        if level == 0 {
+            let leaf = T::try_from_bytes(&self.data[j * NODE_SIZE..(j + 1) * NODE_SIZE])
+                .expect("leaf data is invalid");
+
+            return A::default().leaf(leaf);
+        }
+
+        let arity = self.layout.arity;
+        let children: Vec<T> = (arity * j..arity * (j + 1))
+            .map(|k| self.node(level - 1, k))
+            .collect();
+
+        hash_children::<T, A>(&children, level - 1)
+    }
+}
+
+impl<T: Domain, A: HashFunction<T> + 'static> Store<T> for PartialStore<T, A> {
+    fn len(&self) -> usize {
+        self.layout.len()
+    }
+
+    fn read_at(&self, i: usize) -> T {
+        assert!(
+            i < self.len(),
+            "node {} is out of bounds ({})",
+            i,
+            self.len()
+        );
+
+        let level = self
+            .layout
+            .offsets
+            .iter()
+            .rposition(|&offset| offset <= i)
+            .expect("level offsets start at zero");
+
+        self.node(level, i - self.layout.offsets[level])
+    }
+
+    fn push(&mut self, _node: T) {
+        panic!("partial stores are read-only");
+    }
+
+    fn try_clone(&self) -> Result<Box<Store<T>>> {
+        Ok(Box::new(PartialStore::<T, A> {
+            data: self.data.clone(),
+            upper: self.upper.clone(),
+            layout: self.layout.clone(),
+            omitted_levels: self.omitted_levels,
+            _t: PhantomData,
+            _a: PhantomData,
+        }))
+    }
+}
+
+// The number of nodes in each level of a tree and the index of the first node
+// of each level in its store, where every level but the root's occupies a
+// multiple of arity nodes.
+#[derive(Clone, Debug)]
+struct Layout {
+    arity: usize,
+    widths: Vec<usize>,
+    offsets: Vec<usize>,
+}
+
+impl Layout {
+    fn new(num_leaves: usize, arity: usize) -> Layout {
+        let mut widths = vec![num_leaves];
+        let mut offsets = vec![0];
+
+        let mut width = num_leaves;
+        while width > 1 {
+            let padded = padded_width(width, arity);
+
+            offsets.push(offsets[offsets.len() - 1] + padded);
+            width = padded / arity;
+            widths.push(width);
+        }
+
+        Layout {
+            arity,
+            widths,
+            offsets,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.offsets[self.offsets.len() - 1] + 1
+    }
+
+    // The number of levels which may be omitted from a file of upper levels:
+    // all of them but the root's.
+    fn max_omitted_levels(&self) -> usize {
+        self.widths.len() - 1
+    }
+}
+
+/// A merkle tree whose nodes are kept in a `Store`. Each node of the tree
+/// hashes, with `hash_children`, `arity` nodes of the level below it.
+///
+/// Binary trees are built, and proofs generated from them, exactly as by
+/// merkle_light's `MerkleTree`: a level with an odd number of nodes is padded
+/// by repeating its last node. Both produce identical roots and proofs. Levels
+/// of trees of greater arity are padded, in the same way, to a multiple of
+/// their arity.
+pub struct MerkleTree<T, A> {
+    nodes: Box<Store<T>>,
+    leafs: usize,
+    height: usize,
+    arity: usize,
+    _a: PhantomData<A>,
+}
+
+impl<T: Domain, A: HashFunction<T>> MerkleTree<T, A> {
+    /// Builds a tree, kept in memory, over the provided leaves.
+    pub fn new<I: IntoIterator<Item = T>>(leaves: I) -> MerkleTree<T, A> {
+        let mut a = A::default();
+
+        let mut nodes: Vec<T> = leaves
+            .into_iter()
+            .map(|leaf| {
+                a.reset();
+                a.leaf(leaf)
+            })
+            .collect();
+
+        let num_leaves = nodes.len();
+        nodes.reserve(tree_size(num_leaves) - num_leaves);
+
+        Self::build(Box::new(VecStore(nodes)), DEFAULT_ARITY)
+    }
+
+    /// Builds a tree, kept in memory, over the hashes of the provided items.
+    pub fn from_data<O: Hashable<A>, I: IntoIterator<Item = O>>(items: I) -> MerkleTree<T, A> {
+        let mut a = A::default();
+
+        Self::new(items.into_iter().map(|item| {
+            a.reset();
+            item.hash(&mut a);
+            a.hash()
+        }))
+    }
+
+    /// Builds a tree, kept in the provided storage, over the provided leaves.
+    pub fn new_in<I>(storage: TreeStorage, leaves: I) -> Result<MerkleTree<T, A>>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let leaves = leaves.into_iter();
+        let mut nodes = storage.new_store(tree_size(leaves.len()))?;
+
+        let mut a = A::default();
+        for leaf in leaves {
+            a.reset();
+            nodes.push(a.leaf(leaf));
+        }
+
+        Ok(Self::build(nodes, DEFAULT_ARITY))
+    }
+
+    /// Builds a tree, kept in the provided storage, over `num_leaves` leaves,
+    /// the `i`th of which is `leaf(i)`. Leaves are computed in parallel.
+    pub fn from_par_leaves<F>(
+        storage: TreeStorage,
+        num_leaves: usize,
+        leaf: F,
+    ) -> Result<MerkleTree<T, A>>
+    where
+        F: Fn(usize) -> T + Sync,
+    {
+        let mut nodes = storage.new_store(tree_size(num_leaves))?;
+        let mut chunk = Vec::with_capacity(cmp::min(num_leaves, LEAF_CHUNK_SIZE));
+
+        for start in (0..num_leaves).step_by(LEAF_CHUNK_SIZE) {
+            let end = cmp::min(start + LEAF_CHUNK_SIZE, num_leaves);
+
+            (start..end)
+                .into_par_iter()
+                .map(|i| A::default().leaf(leaf(i)))
+                .collect_into_vec(&mut chunk);
+
+            for node in &chunk {
+                nodes.push(*node);
+            }
+        }
+
+        Ok(Self::build(nodes, DEFAULT_ARITY))
+    }
+
+    /// Builds a tree of the given arity, which must be a power of two no greater
+    /// than `MAX_ARITY`, kept in the provided storage, over the provided leaves.
+    pub fn with_arity<I>(storage: TreeStorage, arity: usize, leaves: I) -> Result<MerkleTree<T, A>>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        check_arity(arity)?;
+
+        let leaves = leaves.into_iter();
+        let mut nodes = storage.new_store(Layout::new(leaves.len(), arity).len())?;
+
+        let mut a = A::default();
+        for leaf in leaves {
+            a.reset();
+            nodes.push(a.leaf(leaf));
+        }
+
+        Ok(Self::build(nodes, arity))
+    }
+
+    // Appends each level of the tree above the leaves held by nodes.
+    fn build(mut nodes: Box<Store<T>>, arity: usize) -> MerkleTree<T, A> {
+        let leafs = nodes.len();
+        assert!(leafs > 1, "a merkle tree needs more than one leaf");
+
+        let mut width = leafs;
+        let mut i = 0;
+        let mut j = width;
+        let mut height = 0;
+        let mut children = Vec::with_capacity(arity);
+
+        while width > 1 {
+            while width % arity != 0 {
+                let last = nodes.read_at(nodes.len() - 1);
+                nodes.push(last);
+
+                width += 1;
+                j += 1;
+            }
+
+            while i < j {
+                children.clear();
+                children.extend((i..i + arity).map(|k| nodes.read_at(k)));
+                nodes.push(hash_children::<T, A>(&children, height));
+
+                i += arity;
+            }
+
+            width /= arity;
+            j += width;
+            height += 1;
+        }
+
+        MerkleTree {
+            nodes,
+            leafs,
+            height: height + 1,
+            arity,
+            _a: PhantomData,
+        }
+    }
+
+    /// Generates a proof of inclusion of the `i`th leaf of a binary tree. Trees
+    /// of greater arity are proven with `MerkleProof::from_tree`.
+    pub fn gen_proof(&self, i: usize) -> Result<proof::Proof<T>> {
+        assert!(
+            i < self.leafs,
+            "leaf {} is out of bounds ({})",
+            i,
+            self.leafs
+        );
+        if self.arity != 2 {
+            return Err(Error::InvalidArity(self.arity));
+        }
+
+        let mut lemma = Vec::with_capacity(self.height + 1);
+        let mut path = Vec::with_capacity(self.height - 1);
+
+        let mut base = 0;
+        let mut j = i;
+
+        let mut width = self.leafs;
+        if width & 1 == 1 {
+            width += 1;
+        }
+
+        lemma.push(self.nodes.read_at(j));
+
+        while base + 1 < self.len() {
+            lemma.push(if j & 1 == 0 {
+                self.nodes.read_at(base + j + 1)
+            } else {
+                self.nodes.read_at(base + j - 1)
+            });
+            path.push(j & 1 == 0);
+
+            base += width;
+            width >>= 1;
+            if width & 1 == 1 {
+                width += 1;
+            }
+            j >>= 1;
+        }
+
+        lemma.push(self.root());
+
+        Ok(proof::Proof::new(lemma, path))
+    }
+
+    /// Returns the authentication path of the `i`th leaf in a tree of any
+    /// arity. For each level below the root, it holds the siblings of the
+    /// leaf's ancestor on that level (or of the leaf itself), in order, and
+    /// the index of the ancestor among its siblings.
+    pub fn gen_auth_path(&self, i: usize) -> Vec<(Vec<T>, usize)> {
+        assert!(
+            i < self.leafs,
+            "leaf {} is out of bounds ({})",
+            i,
+            self.leafs
+        );
+
+        let mut path = Vec::with_capacity(self.height - 1);
+
+        let mut base = 0;
+        let mut j = i;
+        let mut width = self.leafs;
+
+        while base + 1 < self.len() {
+            let first = j - j % self.arity;
+            let siblings = (first..first + self.arity)
+                .filter(|&k| k != j)
+                .map(|k| self.nodes.read_at(base + k))
+                .collect();
+            path.push((siblings, j % self.arity));
+
+            base += padded_width(width, self.arity);
+            width = padded_width(width, self.arity) / self.arity;
+            j /= self.arity;
+        }
+
+        path
+    }
+
+    pub fn root(&self) -> T {
+        self.nodes.read_at(self.nodes.len() - 1)
+    }
+
+    /// Returns the `i`th node, counting the leaves first and the root last.
+    pub fn read_at(&self, i: usize) -> T {
+        self.nodes.read_at(i)
+    }
+
+    /// Returns the number of nodes in the tree.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the number of levels in the tree, including the leaves.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn leafs(&self) -> usize {
+        self.leafs
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Returns a copy of the tree, kept in the same kind of storage. Copying a
+    /// tree kept in a file copies it to a new file, which may fail.
+    pub fn try_clone(&self) -> Result<MerkleTree<T, A>> {
+        Ok(MerkleTree {
+            nodes: self.nodes.try_clone()?,
+            leafs: self.leafs,
+            height: self.height,
+            arity: self.arity,
+            _a: PhantomData,
+        })
+    }
+
+    /// Writes the nodes of the tree to a file at `path`, omitting its lowest
+    /// `omitted_levels` levels (but never the root). Each omitted level halves
+    /// the size of the file and doubles the work of recomputing a node when
+    /// the tree is opened with `MerkleTree::open_partial`.
+    pub fn write_upper_levels<P: AsRef<Path>>(&self, path: P, omitted_levels: usize) -> Result<()> {
+        self.write_upper_levels_to(File::create(path)?, omitted_levels)
+    }
+
+    /// Writes the nodes of the tree to `writer` like `write_upper_levels`.
+    pub fn write_upper_levels_to<W: Write>(&self, writer: W, omitted_levels: usize) -> Result<()> {
+        let layout = Layout::new(self.leafs, self.arity);
+        let omitted_levels = cmp::min(omitted_levels, layout.max_omitted_levels());
+
+        let mut writer = BufWriter::new(writer);
+        let mut buf = [0; NODE_SIZE];
+
+        for i in layout.offsets[omitted_levels]..self.len() {
+            self.read_at(i).write_bytes(&mut buf)?;
+            writer.write_all(&buf)?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+impl<T: Domain, A: HashFunction<T> + 'static> MerkleTree<T, A> {
+    /// Opens the tree of the given arity over the leaves in the file at
+    /// `data_path`, whose upper levels were written to the file at
+    /// `upper_path` by `MerkleTree::write_upper_levels` with the same
+    /// `omitted_levels`. Nodes of the omitted levels are recomputed from the
+    /// leaves when read.
+    ///
+    /// Every stored level above the lowest is recomputed from the level below
+    /// it, and the recomputed root must equal `root`, the commitment the caller
+    /// expects: a file of upper levels which was corrupted, or written for
+    /// another tree, is rejected rather than served. The leaves themselves are
+    /// not checked against the lowest stored level, which would take as long
+    /// as rebuilding the tree.
+    pub fn open_partial<P: AsRef<Path>, Q: AsRef<Path>>(
+        data_path: P,
+        upper_path: Q,
+        arity: usize,
+        omitted_levels: usize,
+        root: &T,
+    ) -> Result<MerkleTree<T, A>> {
+        check_arity(arity)?;
+
+        let data = map_file(data_path)?;
+        let leafs = data.len() / NODE_SIZE;
+
+        if data.len() % NODE_SIZE != 0 || leafs < 2 {
+            return Err(Error::MerkleTreeGenerationError(format!(
+                "data of {} bytes is not a whole number of leaves",
+                data.len()
+            )));
+        }
+
+        let layout = Layout::new(leafs, arity);
+        let omitted_levels = cmp::min(omitted_levels, layout.max_omitted_levels());
+
+        let upper = map_file(upper_path)?;
+        let expected_bytes = (layout.len() - layout.offsets[omitted_levels]) * NODE_SIZE;
+
+        if upper.len() != expected_bytes {
+            return Err(Error::MerkleTreeGenerationError(format!(
+                "expected {} bytes of upper levels, found {}",
+                expected_bytes,
+                upper.len()
+            )));
+        }
+
+        verify_upper_levels::<T, A>(&upper, &layout, omitted_levels, root)?;
+
+        Ok(MerkleTree {
+            height: layout.widths.len(),
+            nodes: Box::new(PartialStore::<T, A> {
+                data: Arc::new(data),
+                upper: Arc::new(upper),
+                layout,
+                omitted_levels,
+                _t: PhantomData,
+                _a: PhantomData,
+            }),
+            leafs,
+            arity,
+            _a: PhantomData,
+        })
+    }
+}
+
+// Recomputes each level of the upper levels written by write_upper_levels,
+// but the lowest, from the level below it, and checks that the stored nodes,
+// and the root they hash to, match.
+fn verify_upper_levels<T: Domain, A: HashFunction<T>>(
+    upper: &[u8],
+    layout: &Layout,
+    omitted_levels: usize,
+    root: &T,
+) -> Result<()> {
+    let first = layout.offsets[omitted_levels];
+    let node =
+        |i: usize| T::try_from_bytes(&upper[(i - first) * NODE_SIZE..(i - first + 1) * NODE_SIZE]);
+
+    for level in omitted_levels..layout.max_omitted_levels() {
+        let width = layout.widths[level];
+
+        for j in 0..layout.widths[level + 1] {
+            // The node padding a level repeats the last node of that level.
+            let children = (layout.arity * j..layout.arity * (j + 1))
+                .map(|k| node(layout.offsets[level] + cmp::min(k, width - 1)))
+                .collect::<Result<Vec<T>>>()?;
+
+            if node(layout.offsets[level + 1] + j)? != hash_children::<T, A>(&children, level) {
+                return Err(Error::MerkleTreeGenerationError(format!(
+                    "stored node {} of level {} does not match its children",
+                    j,
+                    level + 1
+                )));
+            }
+        }
+    }
+
+    if node(layout.len() - 1)? != *root {
+        return Err(Error::InvalidCommitment);
+    }
+
+    Ok(())
+}
+
+impl<T: Domain, A: HashFunction<T>> FromParallelIterator<T> for MerkleTree<T, A> {
+    fn from_par_iter<I: IntoParallelIterator<Item = T>>(leaves: I) -> MerkleTree<T, A> {
+        let mut nodes: Vec<T> = leaves
+            .into_par_iter()
+            .map(|leaf| A::default().leaf(leaf))
+            .collect();
+
+        let num_leaves = nodes.len();
+        nodes.reserve(tree_size(num_leaves) - num_leaves);
+
+        Self::build(Box::new(VecStore(nodes)), DEFAULT_ARITY)
+    }
+}
+
+impl<T: Domain, A> fmt::Debug for MerkleTree<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MerkleTree")
+            .field("leafs", &self.leafs)
+            .field("height", &self.height)
+            .field("arity", &self.arity)
+            .field("root", &self.nodes.read_at(self.nodes.len() - 1))
+            .finish()
+    }
+}
+
+/// Hashes the children of a node, at the given height, of a tree of any arity,
+/// all at once, with `HashFunction::hash_children`.
+pub fn hash_children<T: Domain, A: HashFunction<T>>(children: &[T], height: usize) -> T {
+    assert!(
+        children.len() > 1 && children.len() <= MAX_ARITY,
+        "a node needs more than one child and at most MAX_ARITY"
+    );
+
+    A::hash_children(children, height)
+}
+
+/// Checks that trees may be built of the given arity: a power of two no
+/// greater than `MAX_ARITY`.
+pub fn check_arity(arity: usize) -> Result<()> {
+    if !arity.is_power_of_two() || arity < 2 || arity > MAX_ARITY {
+        return Err(Error::InvalidArity(arity));
+    }
+
+    Ok(())
+}
+
+/// Returns the number of levels above the leaves of a tree of the given arity
+/// over `leaves` leaves, which is the length of their authentication paths.
+pub fn tree_depth(leaves: usize, arity: usize) -> usize {
+    Layout::new(leaves, arity).widths.len() - 1
+}
+
+// Returns the number of nodes a level of the given width occupies in a store,
+// once padded to a multiple of arity (unless it is the root).
+fn padded_width(width: usize, arity: usize) -> usize {
+    if width <= 1 {
+        width
+    } else {
+        width + (arity - width % arity) % arity
+    }
+}
+
+// Maps the file at path into memory, read-only.
+fn map_file<P: AsRef<Path>>(path: P) -> Result<Mmap> {
+    let file = File::open(path)?;
+
+    // The file may not be modified while it is mapped.
+    Ok(unsafe { MmapOptions::new().map(&file)? })
+}
+
+// Returns the greatest number of nodes in a tree with num_leaves leaves.
+fn tree_size(num_leaves: usize) -> usize {
+    2 * num_leaves.next_power_of_two() - 1
+}
 
 /// Representation of a merkle proof.
-/// Each element in the `path` vector consists of a tuple `(hash, is_right)`, with `hash` being the the hash of the node at the current level and `is_right` a boolean indicating if the path is taking the right path.
+/// Each element in the `path` vector consists of a tuple `(siblings, index)`, with `siblings` being the hashes of the siblings of the node at the current level and `index` the position of the node among them. In a binary tree, `index` is 1 if the path is taking the right path.
 /// The first element is the hash of leaf itself, and the last is the root hash.
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct MerkleProof<H: Hasher> {
     pub root: H::Domain,
-    path: Vec<(H::Domain, bool)>,
+    path: Vec<(Vec<H::Domain>, usize)>,
     leaf: H::Domain,
 
     #[serde(skip)]
@@ -29,7 +781,10 @@ pub fn make_proof_for_test<H: Hasher>(
     path: Vec<(H::Domain, bool)>,
 ) -> MerkleProof<H> {
     MerkleProof {
-        path,
+        path: path
+            .into_iter()
+            .map(|(hash, is_right)| (vec![hash], is_right as usize))
+            .collect(),
         root,
         leaf,
         _h: PhantomData,
@@ -38,8 +793,13 @@ pub fn make_proof_for_test<H: Hasher>(
 
 impl<H: Hasher> MerkleProof<H> {
     pub fn new(n: usize) -> MerkleProof<H> {
+        Self::new_with_arity(n, DEFAULT_ARITY)
+    }
+
+    /// Returns a proof, of default values, through `n` levels of a tree of the given arity.
+    pub fn new_with_arity(n: usize, arity: usize) -> MerkleProof<H> {
         let mut m = MerkleProof::default();
-        m.path = vec![(Default::default(), false); n];
+        m.path = vec![(vec![Default::default(); arity - 1], 0); n];
 
         m
     }
@@ -51,7 +811,7 @@ impl<H: Hasher> MerkleProof<H> {
                 .iter()
                 .skip(1)
                 .zip(p.path().iter())
-                .map(|(hash, is_left)| (*hash, !is_left))
+                .map(|(hash, is_left)| (vec![*hash], !is_left as usize))
                 .collect::<Vec<_>>(),
             root: p.root(),
             leaf: p.item(),
@@ -59,43 +819,68 @@ impl<H: Hasher> MerkleProof<H> {
         }
     }
 
+    /// Returns a proof of inclusion of the `i`th leaf of a tree of any arity.
+    pub fn from_tree(tree: &MerkleTree<H::Domain, H::Function>, i: usize) -> MerkleProof<H> {
+        MerkleProof {
+            path: tree.gen_auth_path(i),
+            root: tree.root(),
+            leaf: tree.read_at(i),
+            _h: PhantomData,
+        }
+    }
+
     /// Convert the merkle path into the format expected by the circuits, which is a vector of options of the tuples.
-    /// This does __not__ include the root and the leaf.
-    pub fn as_options(&self) -> Vec<Option<(Fr, bool)>> {
+    /// This does __not__ include the root and the leaf. Fails if the proof is not of a binary tree.
+    pub fn as_options(&self) -> Result<Vec<Option<(Fr, bool)>>> {
+        Ok(self.as_pairs()?.into_iter().map(Some).collect::<Vec<_>>())
+    }
+
+    pub fn as_pairs(&self) -> Result<Vec<(Fr, bool)>> {
         self.path
             .iter()
-            .map(|v| Some((v.0.into(), v.1)))
-            .collect::<Vec<_>>()
+            .map(|(siblings, index)| match siblings.as_slice() {
+                [sibling] if *index < 2 => Ok(((*sibling).into(), *index == 1)),
+                _ => Err(Error::InvalidArity(siblings.len() + 1)),
+            })
+            .collect()
     }
 
-    pub fn as_pairs(&self) -> Vec<(Fr, bool)> {
+    /// Convert the merkle path, of a tree of any arity, into the format expected by the
+    /// `circuit::por` gadgets. This does __not__ include the root and the leaf.
+    pub fn as_auth_path(&self) -> Vec<Option<(Vec<Fr>, usize)>> {
         self.path
             .iter()
-            .map(|v| (v.0.into(), v.1))
+            .map(|v| Some((v.0.iter().map(|hash| (*hash).into()).collect(), v.1)))
             .collect::<Vec<_>>()
     }
 
-    /// Validates the MerkleProof and that it corresponds to the supplied node.
-    pub fn validate(&self, node: usize) -> bool {
-        let mut a = H::Function::default();
+    /// Validates the MerkleProof and that it corresponds to the supplied node
+    /// of a tree of the given arity.
+    pub fn validate(&self, node: usize, arity: usize) -> bool {
+        if check_arity(arity).is_err() {
+            return false;
+        }
+
+        let well_formed = self
+            .path
+            .iter()
+            .all(|(siblings, index)| siblings.len() == arity - 1 && *index < arity);
 
-        if path_index(&self.path) != node {
+        if !well_formed || path_index(&self.path, arity) != node {
             return false;
         }
 
         self.root()
-            == &(0..self.path.len()).fold(self.leaf, |h, i| {
-                a.reset();
-                let is_right = self.path[i].1;
-
-                let (left, right) = if is_right {
-                    (self.path[i].0, h)
-                } else {
-                    (h, self.path[i].0)
-                };
+            == &self
+                .path
+                .iter()
+                .enumerate()
+                .fold(self.leaf, |h, (i, (siblings, index))| {
+                    let mut children = siblings.clone();
+                    children.insert(*index, h);
 
-                a.node(left, right, i)
-            })
+                    hash_children::<H::Domain, H::Function>(&children, i)
+                })
     }
 
     /// Validates that the data hashes to the leaf of the merkle path.
@@ -124,9 +909,11 @@ impl<H: Hasher> MerkleProof<H> {
     pub fn serialize(&self) -> Vec<u8> {
         let mut out = Vec::new();
 
-        for (hash, is_right) in &self.path {
-            out.extend(hash.serialize());
-            out.push(*is_right as u8);
+        for (siblings, index) in &self.path {
+            for hash in siblings {
+                out.extend(hash.serialize());
+            }
+            out.push(*index as u8);
         }
         out.extend(self.leaf().serialize());
         out.extend(self.root().serialize());
@@ -134,15 +921,15 @@ impl<H: Hasher> MerkleProof<H> {
         out
     }
 
-    pub fn path(&self) -> &Vec<(H::Domain, bool)> {
+    pub fn path(&self) -> &Vec<(Vec<H::Domain>, usize)> {
         &self.path
     }
 }
 
-fn path_index<T: Domain>(path: &[(T, bool)]) -> usize {
-    path.iter().rev().fold(0, |acc, (_, is_right)| {
-        (acc << 1) + if *is_right { 1 } else { 0 }
-    })
+fn path_index<T: Domain>(path: &[(Vec<T>, usize)], arity: usize) -> usize {
+    path.iter()
+        .rev()
+        .fold(0, |acc, (_, index)| acc * arity + index)
 }
 
 #[cfg(test)]
@@ -152,6 +939,8 @@ mod tests {
     use rand::{self, Rng};
     use std::io::Write;
 
+    use merkle_light::merkle::MerkleTree as LightTree;
+
     use crate::drgraph::new_seed;
     use crate::drgraph::{BucketGraph, Graph};
     use crate::hasher::{Blake2sHasher, PedersenHasher, Sha256Hasher};
@@ -169,7 +958,7 @@ mod tests {
 
         let tree = g.merkle_tree(data.as_slice()).unwrap();
         for i in 0..10 {
-            let proof = tree.gen_proof(i);
+            let proof = tree.gen_proof(i).unwrap();
 
             assert!(proof.validate::<H::Function>());
             let len = proof.lemma().len();
@@ -177,7 +966,10 @@ mod tests {
 
             assert_eq!(mp.len(), len);
 
-            assert!(mp.validate(i), "failed to validate valid merkle path");
+            assert!(
+                mp.validate(i, DEFAULT_ARITY),
+                "failed to validate valid merkle path"
+            );
             let data_slice = &data[i * node_size..(i + 1) * node_size].to_vec();
             assert!(
                 mp.validate_data(data_slice),
@@ -200,4 +992,235 @@ mod tests {
     fn merklepath_blake2s() {
         merklepath::<Blake2sHasher>();
     }
+
+    fn matches_merkle_light<H: Hasher>(storage: TreeStorage) {
+        let mut rng = rand::thread_rng();
+
+        for num_leaves in vec![2, 3, 5, 8, 13, 64] {
+            let leaves: Vec<H::Domain> = (0..num_leaves).map(|_| rng.gen()).collect();
+
+            let light = LightTree::<H::Domain, H::Function>::new(leaves.clone());
+
+            let tree =
+                MerkleTree::<H::Domain, H::Function>::new_in(storage.clone(), leaves.clone())
+                    .unwrap();
+            let par_tree = MerkleTree::<H::Domain, H::Function>::from_par_leaves(
+                storage.clone(),
+                num_leaves,
+                |i| leaves[i],
+            )
+            .unwrap();
+
+            for tree in &[tree.try_clone().unwrap(), par_tree] {
+                assert_eq!(tree.root(), light.root());
+                assert_eq!(tree.len(), light.len());
+                assert_eq!(tree.height(), light.height());
+                assert_eq!(tree.leafs(), light.leafs());
+
+                for i in 0..num_leaves {
+                    let proof = tree.gen_proof(i).unwrap();
+                    let light_proof = light.gen_proof(i);
+
+                    assert_eq!(proof.lemma(), light_proof.lemma());
+                    assert_eq!(proof.path(), light_proof.path());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn matches_merkle_light_pedersen() {
+        matches_merkle_light::<PedersenHasher>(TreeStorage::Memory);
+        matches_merkle_light::<PedersenHasher>(TreeStorage::Mmap(std::env::temp_dir()));
+    }
+
+    #[test]
+    fn matches_merkle_light_sha256() {
+        matches_merkle_light::<Sha256Hasher>(TreeStorage::Mmap(std::env::temp_dir()));
+    }
+
+    #[test]
+    fn matches_merkle_light_blake2s() {
+        matches_merkle_light::<Blake2sHasher>(TreeStorage::Mmap(std::env::temp_dir()));
+    }
+
+    fn partial_tree<H: Hasher>() {
+        let mut rng = rand::thread_rng();
+
+        for num_leaves in vec![2, 3, 5, 8, 13, 64] {
+            let leaves: Vec<H::Domain> = (0..num_leaves).map(|_| rng.gen()).collect();
+
+            let mut data_file = tempfile::NamedTempFile::new().unwrap();
+            for leaf in &leaves {
+                data_file.write_all(&leaf.into_bytes()).unwrap();
+            }
+            data_file.flush().unwrap();
+
+            for arity in vec![2, 4] {
+                let tree = MerkleTree::<H::Domain, H::Function>::with_arity(
+                    TreeStorage::Memory,
+                    arity,
+                    leaves.clone(),
+                )
+                .unwrap();
+
+                for omitted_levels in vec![0, 1, 2, 10] {
+                    let upper_file = tempfile::NamedTempFile::new().unwrap();
+                    tree.write_upper_levels(upper_file.path(), omitted_levels)
+                        .unwrap();
+
+                    let partial = MerkleTree::<H::Domain, H::Function>::open_partial(
+                        data_file.path(),
+                        upper_file.path(),
+                        arity,
+                        omitted_levels,
+                        &tree.root(),
+                    )
+                    .unwrap();
+
+                    assert_eq!(partial.root(), tree.root());
+                    assert_eq!(partial.len(), tree.len());
+                    assert_eq!(partial.height(), tree.height());
+                    assert_eq!(partial.leafs(), tree.leafs());
+
+                    for i in 0..tree.len() {
+                        assert_eq!(partial.read_at(i), tree.read_at(i));
+                    }
+
+                    for i in 0..num_leaves {
+                        assert_eq!(partial.gen_auth_path(i), tree.gen_auth_path(i));
+                    }
+                }
+
+                // The upper levels must have been written omitting as many levels.
+                let upper_file = tempfile::NamedTempFile::new().unwrap();
+                tree.write_upper_levels(upper_file.path(), 0).unwrap();
+
+                assert!(MerkleTree::<H::Domain, H::Function>::open_partial(
+                    data_file.path(),
+                    upper_file.path(),
+                    arity,
+                    1,
+                    &tree.root(),
+                )
+                .is_err());
+
+                // The upper levels must commit to the expected root.
+                assert!(MerkleTree::<H::Domain, H::Function>::open_partial(
+                    data_file.path(),
+                    upper_file.path(),
+                    arity,
+                    0,
+                    &leaves[0],
+                )
+                .is_err());
+
+                // A stored node which does not match its children is rejected,
+                // even though the root still matches.
+                let mut upper = std::fs::read(upper_file.path()).unwrap();
+                upper[..NODE_SIZE].copy_from_slice(&leaves[1].into_bytes());
+                std::fs::write(upper_file.path(), &upper).unwrap();
+
+                assert!(MerkleTree::<H::Domain, H::Function>::open_partial(
+                    data_file.path(),
+                    upper_file.path(),
+                    arity,
+                    0,
+                    &tree.root(),
+                )
+                .is_err());
+            }
+        }
+    }
+
+    fn nary_tree<H: Hasher>(storage: TreeStorage) {
+        let mut rng = rand::thread_rng();
+
+        for arity in vec![2, 4, 8] {
+            for num_leaves in vec![2, 3, 5, 8, 13, 64, 65] {
+                let leaves: Vec<H::Domain> = (0..num_leaves).map(|_| rng.gen()).collect();
+
+                let tree = MerkleTree::<H::Domain, H::Function>::with_arity(
+                    storage.clone(),
+                    arity,
+                    leaves.clone(),
+                )
+                .unwrap();
+
+                let depth = tree_depth(num_leaves, arity);
+                assert_eq!(tree.height(), depth + 1);
+                assert_eq!(tree.arity(), arity);
+
+                if arity == 2 {
+                    let binary = MerkleTree::<H::Domain, H::Function>::new(leaves.clone());
+                    assert_eq!(tree.root(), binary.root());
+                    assert_eq!(tree.len(), binary.len());
+                }
+
+                for i in 0..num_leaves {
+                    let proof = MerkleProof::<H>::from_tree(&tree, i);
+
+                    assert_eq!(proof.len(), depth + 2);
+                    assert_eq!(proof.leaf(), &leaves[i]);
+                    assert_eq!(proof.root(), &tree.root());
+                    assert!(
+                        proof.validate(i, arity),
+                        "failed to validate valid merkle path"
+                    );
+                    assert!(
+                        !proof.validate((i + 1) % num_leaves, arity),
+                        "validated merkle path of another leaf"
+                    );
+                    assert!(
+                        !proof.validate(i, 2 * arity),
+                        "validated merkle path of a tree of another arity"
+                    );
+                    assert_eq!(proof.as_pairs().is_ok(), arity == 2);
+                }
+
+                assert_eq!(tree.gen_proof(0).is_ok(), arity == 2);
+            }
+        }
+
+        // A proof without siblings is of no arity, rather than of arity 1.
+        let proof = MerkleProof::<H>::new_with_arity(3, 1);
+        assert!(!proof.validate(0, 2));
+        assert!(!proof.validate(0, 1));
+
+        // A node of a tree with as many leaves as its arity hashes all of them.
+        let leaves: Vec<H::Domain> = (0..4).map(|_| rng.gen()).collect();
+        let tree =
+            MerkleTree::<H::Domain, H::Function>::with_arity(storage.clone(), 4, leaves.clone())
+                .unwrap();
+
+        assert_eq!(
+            tree.root(),
+            hash_children::<H::Domain, H::Function>(&leaves, 0)
+        );
+
+        assert!(
+            MerkleTree::<H::Domain, H::Function>::with_arity(storage, 3, leaves.clone()).is_err()
+        );
+    }
+
+    #[test]
+    fn nary_tree_pedersen() {
+        nary_tree::<PedersenHasher>(TreeStorage::Memory);
+        nary_tree::<PedersenHasher>(TreeStorage::Mmap(std::env::temp_dir()));
+    }
+
+    #[test]
+    fn nary_tree_blake2s() {
+        nary_tree::<Blake2sHasher>(TreeStorage::Memory);
+    }
+
+    #[test]
+    fn partial_tree_pedersen() {
+        partial_tree::<PedersenHasher>();
+    }
+
+    #[test]
+    fn partial_tree_sha256() {
+        partial_tree::<Sha256Hasher>();
+    }
 }
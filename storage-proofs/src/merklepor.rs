@@ -1,10 +1,9 @@
 use std::marker::PhantomData;
 
 use crate::drgporep::DataProof;
-use crate::drgraph::graph_height;
 use crate::error::*;
 use crate::hasher::{Domain, Hasher};
-use crate::merkle::{MerkleProof, MerkleTree};
+use crate::merkle::{check_arity, tree_depth, MerkleProof, MerkleTree};
 use crate::parameter_cache::ParameterSetIdentifier;
 use crate::proof::ProofScheme;
 
@@ -14,13 +13,15 @@ pub struct PublicParams {
     /// How many leaves the underlying merkle tree has.
     pub leaves: usize,
     pub private: bool,
+    /// How many children each node of the underlying merkle tree has.
+    pub arity: usize,
 }
 
 impl ParameterSetIdentifier for PublicParams {
     fn parameter_set_identifier(&self) -> String {
         format!(
-            "merklepor::PublicParams{{leaves: {}; private: {}}}",
-            self.leaves, self.private
+            "merklepor::PublicParams{{leaves: {}; private: {}; arity: {}}}",
+            self.leaves, self.private, self.arity
         )
     }
 }
@@ -61,6 +62,7 @@ pub type Proof<H> = DataProof<H>;
 pub struct SetupParams {
     pub leaves: usize,
     pub private: bool,
+    pub arity: usize,
 }
 
 /// Merkle tree based proof of retrievability.
@@ -77,9 +79,12 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for MerklePoR<H> {
     type Proof = Proof<H>;
 
     fn setup(sp: &SetupParams) -> Result<PublicParams> {
+        check_arity(sp.arity)?;
+
         Ok(PublicParams {
             leaves: sp.leaves,
             private: sp.private,
+            arity: sp.arity,
         })
     }
 
@@ -97,8 +102,12 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for MerklePoR<H> {
             }
         }
 
+        if tree.arity() != pub_params.arity {
+            return Err(Error::MalformedInput);
+        }
+
         Ok(Proof {
-            proof: MerkleProof::new_from_proof(&tree.gen_proof(challenge)),
+            proof: MerkleProof::from_tree(tree, challenge),
             data: priv_inputs.leaf,
         })
     }
@@ -115,14 +124,15 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for MerklePoR<H> {
                 None => true,
             };
 
-            let path_length_match = graph_height(pub_params.leaves) == proof.proof.path().len();
+            let path_length_match =
+                tree_depth(pub_params.leaves, pub_params.arity) == proof.proof.path().len();
 
             if !(commitments_match && path_length_match) {
                 return Ok(false);
             }
         }
         let data_valid = proof.proof.validate_data(&proof.data.into_bytes());
-        let path_valid = proof.proof.validate(pub_inputs.challenge);
+        let path_valid = proof.proof.validate(pub_inputs.challenge, pub_params.arity);
 
         Ok(data_valid && path_valid)
     }
@@ -138,7 +148,7 @@ mod tests {
     use crate::drgraph::{new_seed, BucketGraph, Graph};
     use crate::fr32::fr_into_bytes;
     use crate::hasher::{Blake2sHasher, HashFunction, PedersenHasher, Sha256Hasher};
-    use crate::merkle::make_proof_for_test;
+    use crate::merkle::{make_proof_for_test, TreeStorage};
     use crate::util::data_at_node;
 
     fn test_merklepor<H: Hasher>() {
@@ -147,6 +157,7 @@ mod tests {
         let pub_params = PublicParams {
             leaves: 32,
             private: false,
+            arity: 2,
         };
 
         let data: Vec<u8> = (0..32)
@@ -187,6 +198,64 @@ mod tests {
         test_merklepor::<Blake2sHasher>();
     }
 
+    fn test_merklepor_arity<H: Hasher>(arity: usize) {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let pub_params = MerklePoR::<H>::setup(&SetupParams {
+            leaves: 32,
+            private: false,
+            arity,
+        })
+        .unwrap();
+
+        let data: Vec<u8> = (0..32)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&rng.gen()))
+            .collect();
+
+        let leaves = (0..32)
+            .map(|i| H::Domain::try_from_bytes(data_at_node(data.as_slice(), i).unwrap()).unwrap());
+        let tree = MerkleTree::with_arity(TreeStorage::Memory, arity, leaves).unwrap();
+
+        for challenge in 0..32 {
+            let pub_inputs = PublicInputs {
+                challenge,
+                commitment: Some(tree.root()),
+            };
+
+            let leaf = H::Domain::try_from_bytes(data_at_node(data.as_slice(), challenge).unwrap())
+                .unwrap();
+
+            let priv_inputs = PrivateInputs::<H>::new(leaf, &tree);
+
+            let proof = MerklePoR::<H>::prove(&pub_params, &pub_inputs, &priv_inputs).unwrap();
+
+            assert!(MerklePoR::<H>::verify(&pub_params, &pub_inputs, &proof).unwrap());
+
+            // The proof is not of a binary tree.
+            let binary_params = PublicParams {
+                arity: 2,
+                ..pub_params.clone()
+            };
+
+            assert!(!MerklePoR::<H>::verify(&binary_params, &pub_inputs, &proof).unwrap());
+        }
+    }
+
+    #[test]
+    fn merklepor_arity_4_pedersen() {
+        test_merklepor_arity::<PedersenHasher>(4);
+    }
+
+    #[test]
+    fn merklepor_arity_8_pedersen() {
+        test_merklepor_arity::<PedersenHasher>(8);
+    }
+
+    #[test]
+    fn merklepor_arity_8_sha256() {
+        test_merklepor_arity::<Sha256Hasher>(8);
+    }
+
     // Construct a proof that satisfies a cursory validation:
     // Data and proof are minimally consistent.
     // Proof root matches that requested in public inputs.
@@ -215,6 +284,7 @@ mod tests {
         let pub_params = PublicParams {
             leaves: 32,
             private: false,
+            arity: 2,
         };
 
         let data: Vec<u8> = (0..32)
@@ -258,6 +328,7 @@ mod tests {
         let pub_params = PublicParams {
             leaves: 32,
             private: false,
+            arity: 2,
         };
 
         let data: Vec<u8> = (0..32)
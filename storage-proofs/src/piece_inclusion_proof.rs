@@ -27,10 +27,11 @@ pub struct PieceInclusionProof<H: Hasher> {
 /// piece begins at offset 0, and that each piece begins directly after the previous piece ends.
 /// For this method to work, the piece data used to validate pieces will need to be padded as necessary,
 /// and pieces will need to be aligned (to 128-byte chunks for Fr32 bit-padding) when written.
+/// The tree must be binary.
 pub fn file_inclusion_proofs<H: Hasher>(
     tree: &MerkleTree<H::Domain, H::Function>,
     piece_lengths: &[usize],
-) -> Vec<PieceInclusionProof<H>> {
+) -> Result<Vec<PieceInclusionProof<H>>> {
     bounds(piece_lengths)
         .iter()
         .map(|(start, end)| file_inclusion_proof(tree, *start, end - 1))
@@ -51,17 +52,17 @@ fn bounds(lengths: &[usize]) -> Vec<(usize, usize)> {
 
 /// file_inclusion_proof takes a merkle tree and the index positions of the first and last nodes
 /// of the piece whose inclusion should be proved. It returns a corresponding file_inclusion_proof.
-/// For the resulting proof to be valid, first_node must be <= last_node.
+/// For the resulting proof to be valid, first_node must be <= last_node. The tree must be binary.
 pub fn file_inclusion_proof<H: Hasher>(
     tree: &MerkleTree<H::Domain, H::Function>,
     first_node: usize,
     last_node: usize,
-) -> PieceInclusionProof<H> {
-    PieceInclusionProof {
-        first_node_proof: tree.gen_proof(first_node),
-        last_node_proof: tree.gen_proof(last_node),
+) -> Result<PieceInclusionProof<H>> {
+    Ok(PieceInclusionProof {
+        first_node_proof: tree.gen_proof(first_node)?,
+        last_node_proof: tree.gen_proof(last_node)?,
         _h: PhantomData,
-    }
+    })
 }
 
 impl<H: Hasher> PieceInclusionProof<H> {
@@ -220,7 +221,7 @@ mod tests {
         let tree = g.merkle_tree(&data).unwrap();
         let lengths: Vec<usize> = node_lengths.iter().map(|x| x * 32).collect();
 
-        let proofs = file_inclusion_proofs::<H>(&tree, &node_lengths);
+        let proofs = file_inclusion_proofs::<H>(&tree, &node_lengths).unwrap();
         let bounds = bounds(lengths.as_slice());
         let mut pieces = Vec::new();
         for (start, end) in &bounds {
@@ -2,13 +2,14 @@ use std::marker::PhantomData;
 
 use num_bigint::BigUint;
 use num_traits::ToPrimitive;
+use pairing::bls12_381::Fr;
 use serde::de::Deserialize;
 use serde::ser::Serialize;
 
 use crate::drgraph::graph_height;
 use crate::error::{Error, Result};
 use crate::hasher::{Domain, Hasher};
-use crate::merkle::{MerkleProof, MerkleTree};
+use crate::merkle::{MerkleProof, MerkleTree, DEFAULT_ARITY};
 use crate::parameter_cache::ParameterSetIdentifier;
 use crate::proof::ProofScheme;
 
@@ -70,8 +71,10 @@ impl<H: Hasher> Proof<H> {
         self.0.iter().map(|p| p.root()).collect()
     }
 
-    pub fn paths(&self) -> Vec<&Vec<(H::Domain, bool)>> {
-        self.0.iter().map(|p| p.path()).collect()
+    /// Returns the paths of the proofs in the format expected by the circuits.
+    /// Fails if any proof is not of a binary tree.
+    pub fn paths(&self) -> Result<Vec<Vec<Option<(Fr, bool)>>>> {
+        self.0.iter().map(|p| p.as_options()).collect()
     }
 }
 
@@ -120,9 +123,11 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for PoRC<'a, H> {
                     return Err(Error::InvalidCommitment);
                 }
 
-                Ok(MerkleProof::new_from_proof(
-                    &tree.gen_proof(*challenged_leaf),
-                ))
+                if tree.arity() != DEFAULT_ARITY {
+                    return Err(Error::InvalidArity(tree.arity()));
+                }
+
+                Ok(MerkleProof::from_tree(tree, *challenged_leaf))
             })
             .collect::<Result<Vec<_>>>()?;
 
@@ -151,7 +156,7 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for PoRC<'a, H> {
                 return Ok(false);
             }
 
-            if !merkle_proof.validate(*challenged_leaf) {
+            if !merkle_proof.validate(*challenged_leaf, DEFAULT_ARITY) {
                 return Ok(false);
             }
         }